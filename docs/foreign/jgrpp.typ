@@ -29,8 +29,25 @@ Each orders export is a JSON file that contains these fields:
 - Vehicle orders, i.e., the `go to`, `full load` commands.
 - Other information, such as game version, and export format version.
 
-The import function `jgrpp.read()` primarily uses game properties and vehicle orders, but you can customize the
-data produced by the import function by writing your own functions for the import function.
+The import function `jgrpp.read()` primarily uses game properties, vehicle orders, and scheduled dispatch schedules.
+Any number of exports could be passed to it at once.
+
+```typc
+import "@preview/paiagram:0.1.2": *
+let data = jgrpp.read(
+  json("<vehicle 1>.json"),
+  json("<vehicle 2>.json"),
+  // or "euclidean"
+  distance: "manhattan",
+  // meters per tile
+  tile-length: 64,
+)
+```
+
+Each order with a scheduled dispatch starts a train, which runs until the next order with a scheduled dispatch. The
+slots of the dispatch schedule become the departures of the train. Vehicles without scheduled dispatch repeat after
+their whole timetable. Times are converted with the ticks per minute of the export, which could be overridden with
+`ticks-per-minute`.
 
 = Labels
 
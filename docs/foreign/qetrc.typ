@@ -24,6 +24,18 @@ let data = qetrc.read(
 
 The function returns a dictionary with `trains`, `stations`, and `intervals` keys, which you can use to render the timetable diagram.
 
+Large files are much faster to read with `qetrc.load()`, which parses the raw file inside the plugin. It also reads the
+mileage in both directions, files with more than one line, and the circuits of trains. The type, colour, line width,
+line style, and circuit of each train are kept in its `metadata`.
+
+```typc
+let data = qetrc.load(
+  read("<file>.pyetgr", encoding: none),
+  // only needed if the file has more than one line
+  line: "<line name>",
+)
+```
+
 #TODO
 
 = Customization
@@ -0,0 +1,22 @@
+#import "../utils.typ": *
+#import "qetrc.typ": match-stroke
+
+/// Reads an ETRC `.trc` file. Mileage becomes interval lengths, and the type of each train is
+/// found from its number. Trains keep their type, down and up numbers, origin, destination, and
+/// circuit in `metadata`.
+///
+/// - data (bytes): The file, e.g. `read("<file>.trc", encoding: none)`. Files that are not UTF-8 are read as GBK.
+/// -> dictionary
+#let load(
+  data,
+  train-label: make-train-label,
+  station-label: station => text(top-edge: "ascender", bottom-edge: "descender")[#station.name],
+  train-stroke: train => match-stroke(train),
+) = {
+  finish-import(
+    cbor(plg.import_etrc(data)),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
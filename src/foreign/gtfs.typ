@@ -0,0 +1,37 @@
+#import "../utils.typ": *
+
+/// The default stroke of a train read by `load`, in the colour of its route.
+///
+/// - train (dictionary): The train
+/// -> stroke
+#let route-stroke(train) = {
+  let metadata = train.at("metadata", default: (:))
+  if "color" in metadata { rgb(metadata.color) } else { black }
+}
+
+/// Reads a GTFS static feed. Stops with a parent station are read as tracks of the parent station,
+/// and `frequencies.txt` is read as repeating trains. Trains keep their trip ID, route, route type,
+/// headsign, and direction in `metadata`.
+///
+/// - data (bytes): The zipped feed, e.g. `read("<feed>.zip", encoding: none)`
+/// - routes (array): Only read these routes, given by their IDs or short names. All routes are read if empty.
+/// - shape-dist-scale (none, float): Meters per unit of `shape_dist_traveled`. Guessed from the stop coordinates if `none`.
+/// -> dictionary
+#let load(
+  data,
+  routes: (),
+  shape-dist-scale: none,
+  train-label: make-train-label,
+  station-label: station => [#station.name],
+  train-stroke: route-stroke,
+) = {
+  finish-import(
+    cbor(plg.import_gtfs(data, cbor.encode((
+      routes: routes,
+      shape_dist_scale: if shape-dist-scale == none { none } else { float(shape-dist-scale) },
+    )))),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
@@ -1,84 +1,35 @@
 #import "../utils.typ": *
-#let calc-manhattan(a, b, factor: 64) = {
-  (
-    (
-      calc.abs(a.destination-location.X - b.destination-location.X)
-        + calc.abs(a.destination-location.Y - b.destination-location.Y)
-    )
-      * factor
-  )
-}
 
-#let calc-euclidean(a, b, factor: 64) = {
-  let dx = a.destination-location.X - b.destination-location.X
-  let dy = a.destination-location.Y - b.destination-location.Y
-  int(calc.sqrt(dx * dx + dy * dy) * factor)
-}
-
-#let read(distance-func: calc-manhattan, ..inputs) = {
-  let stations = (:)
-  let intervals = ()
-  let trains = (:)
-  for train in inputs.pos() {
-    let tpm = if "game-properties" in train and "ticks-per-minute" in train.game-properties {
-      train.game-properties.ticks-per-minute
-    } else {
-      74 // default value in most cases
-    }
-    let sd-schedules = train
-      .at("schedules", default: ())
-      .map(
-        it => (
-          entries: it.slots.map(
-            slot => {
-              let t = type(slot)
-              if t == int {
-                slot
-              } else if t == dictionary and "offset" in slot {
-                slot.offset
-              } else {
-                panic("Invalid slot type: " + t)
-              }
-            },
-          ),
-          duration: it.at("duration", default: tpm * 60 * 24),
-          max-delay: it.at("max-delay", default: 0),
-        ),
-      )
-    // this is where the order matters
-    let schedule = ()
-    let orders = train.orders.filter(it => it.type == "go-to-station")
-    let prev-order = orders.last()
-    for idx in range(orders.len()) {
-      let order = orders.at(idx)
-      if order.type == "go-to-station" {
-        let label = measure[#order.destination-name]
-        stations.insert(order.destination-name, (label_size: (label.width / 1pt, label.height / 1pt)))
-        if prev-order == none {
-          prev-order = order
-          continue
-        }
-        if (
-          intervals.find(
-            it => (
-              it.at(0) == (prev-order.destination-name, order.destination-name)
-                or it.at(0) == (order.destination-name, prev-order.destination-name)
-            ),
-          )
-            == none
-        ) {
-          intervals.push((
-            (prev-order.destination-name, order.destination-name),
-            (
-              length: distance-func(prev-order, order),
-            ),
-          ))
-          prev-order = order
-        }
-      }
-    }
-    trains.insert(train.source, (schedule: schedule))
-  }
-  let trains = (:)
-  (stations: stations, intervals: intervals, trains: trains)
+/// Reads OpenTTD JGRPP orders exports. The orders of each vehicle are followed until they loop.
+/// Each order with a scheduled dispatch starts a train whose instances are the dispatch slots,
+/// and vehicles without scheduled dispatch repeat after their whole timetable. Depots are skipped,
+/// and conditional orders are only followed if they always jump.
+///
+/// - distance (str): How interval lengths are found from the station locations, either `"manhattan"` or `"euclidean"`.
+/// - tile-length (int, float): Meters per tile.
+/// - lengths (array): Lengths in meters that are used instead of `distance`, e.g. `((("A", "B"), 1200),)`.
+/// - ticks-per-minute (none, int): Used instead of the ticks per minute of the exports.
+/// - inputs (dictionary): The exports, e.g. `json("<vehicle>.json")`
+/// -> dictionary
+#let read(
+  distance: "manhattan",
+  tile-length: 64,
+  lengths: (),
+  ticks-per-minute: none,
+  train-label: make-train-label,
+  station-label: station => [#station.name],
+  train-stroke: train => black,
+  ..inputs,
+) = {
+  finish-import(
+    cbor(plg.import_jgrpp(cbor.encode(inputs.pos()), cbor.encode((
+      distance: distance,
+      tile_length: float(tile-length),
+      lengths: lengths,
+      ticks_per_minute: ticks-per-minute,
+    )))),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
 }
@@ -162,3 +162,44 @@
     stations-to-draw: stations-to-draw,
   )
 }
+
+/// The default stroke of a train read by `load`, in the colour of its train type.
+///
+/// - train (dictionary): The train
+/// -> array
+#let type-stroke(train) = {
+  let metadata = train.at("metadata", default: (:))
+  let paint = if "color" in metadata { rgb(metadata.color) } else { black }
+  let thickness = metadata.at("line_width", default: 1) * 1pt
+  let dash = (
+    "1": "solid",
+    "2": "dashed",
+    "3": "dotted",
+    "4": "dash-dotted",
+  ).at(str(metadata.at("line_style", default: 1)), default: "solid")
+  (white + (thickness + 1pt), stroke(paint: paint, thickness: thickness, dash: dash))
+}
+
+/// Reads an OuDia (`.oud`) or OuDiaSecond (`.oud2`) file with the native importer. Unlike `read`,
+/// it reads the raw file, and keeps station tracks. Trains keep their type, colour, direction,
+/// and operations in `metadata`. The `Operation*` fields of each train are listed under
+/// `operations` with their station, side (`before` or `after`), kind and detail. Operations whose
+/// detail is the number of another train link the two trains through `previous` and `next`.
+///
+/// - data (bytes): The raw file, e.g. `read("<file>.oud2", encoding: none)`
+/// - diagram (none, str): The name of the diagram to read. The first diagram is read if `none`.
+/// -> dictionary
+#let load(
+  data,
+  diagram: none,
+  train-label: make-train-label,
+  station-label: station => [#station.name],
+  train-stroke: type-stroke,
+) = {
+  finish-import(
+    cbor(plg.import_oudia(data, cbor.encode((diagram: diagram)))),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
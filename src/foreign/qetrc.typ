@@ -44,12 +44,37 @@
 )
 
 #let match-color(train, dict: name-color-dict) = {
-  dict.at(match-type(train.name)).at(1)
+  let train-type = train.at("metadata", default: (:)).at("type", default: none)
+  if train-type not in dict {
+    train-type = match-type(train.name)
+  }
+  dict.at(train-type).at(1)
+}
+
+/// Gets the display settings of a train, either from a raw qETRC train or from the metadata
+/// given by `load`.
+///
+/// - train (dictionary): The train
+/// -> dictionary
+#let train-ui(train) = {
+  if "metadata" in train {
+    let metadata = train.metadata
+    let ui = (:)
+    if "color" in metadata { ui.insert("Color", metadata.color) }
+    if "line_width" in metadata { ui.insert("LineWidth", metadata.line_width) }
+    if "line_style" in metadata { ui.insert("LineStyle", metadata.line_style) }
+    ui
+  } else if "raw" in train and "UI" in train.raw and type(train.raw.UI) == dictionary {
+    train.raw.UI
+  } else {
+    (:)
+  }
 }
 
 #let original-color(train, fallback: match-color) = {
-  if "UI" in train.raw and "Color" in train.raw.UI {
-    rgb(train.raw.UI.Color)
+  let ui = train-ui(train)
+  if "Color" in ui {
+    rgb(ui.Color)
   } else {
     fallback(train, dict: name-color-dict)
   }
@@ -63,9 +88,10 @@
   } else {
     paint
   }
+  let ui = train-ui(train)
   thickness = if thickness == auto {
-    if "UI" in train.raw and "LineWidth" in train.raw.UI {
-      train.raw.UI.LineWidth / 1.5 * 1pt
+    if "LineWidth" in ui {
+      ui.LineWidth / 1.5 * 1pt
     } else {
       1pt
     }
@@ -75,8 +101,8 @@
     thickness
   }
   dash = if dash == auto {
-    if "UI" in train.raw and "LineStyle" in train.raw.UI {
-      let style = train.raw.UI.LineStyle
+    if "LineStyle" in ui {
+      let style = ui.LineStyle
       if style == 0 {
         thickness = 0pt
       } else if style == 1 {
@@ -206,3 +232,25 @@
   }
   (stations: stations, trains: trains, intervals: intervals)
 }
+
+/// Reads a qETRC or pyETRC file with the native importer. It is much faster than `read`,
+/// and could read files with more than one line. Trains keep their type, colour, line width,
+/// line style, and circuit in `metadata`.
+///
+/// - data (bytes): The raw file, e.g. `read("<file>.pyetgr", encoding: none)`
+/// - line (none, str): The name of the line to read. Could be `none` if the file has only one line.
+/// -> dictionary
+#let load(
+  data,
+  line: none,
+  train-label: make-train-label,
+  station-label: station => text(top-edge: "ascender", bottom-edge: "descender")[#station.name],
+  train-stroke: train => { red },
+) = {
+  finish-import(
+    cbor(plg.import_qetrc(data, cbor.encode((line: line)))),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
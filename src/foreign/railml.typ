@@ -0,0 +1,26 @@
+#import "../utils.typ": *
+
+/// Reads the operation control points and the timetable of a railML 2 or railML 3 file. Intervals
+/// join the points that are next to each other on a track or in a train run, and mileage
+/// differences become their lengths. The track of each stop is read from `trackInfo` in railML 2,
+/// and from the platform edge, track or stop post it refers to in railML 3.
+/// Elements that are not supported are skipped. Trains keep their railML ID and category in
+/// `metadata`.
+///
+/// - data (bytes): The file, e.g. `read("<file>.xml", encoding: none)`
+/// - time-scope (none, str): The scope of the times to read in railML 2, e.g. `"published"`. The scheduled times are read if `none`.
+/// -> dictionary
+#let load(
+  data,
+  time-scope: none,
+  train-label: make-train-label,
+  station-label: station => [#station.name],
+  train-stroke: train => black,
+) = {
+  finish-import(
+    cbor(plg.import_railml(data, cbor.encode((time_scope: time-scope)))),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
@@ -0,0 +1,47 @@
+#import "../utils.typ": *
+
+/// Reads a timetable laid out as a spreadsheet, with one column per train and one row per
+/// station. The first column has the stations, and stations could have arrival and departure
+/// sub-rows, marked in another column. Times after midnight could be written as `24:xx`.
+///
+/// - data (bytes): The CSV or TSV file, e.g. `read("<file>.csv", encoding: none)`
+/// - delimiter (none, str): The delimiter of the fields. Tabs are used if the first row has one, and commas otherwise, if `none`.
+/// - time-formats (none, array): Formats of the times, such as `"%H:%M"`, tried in order.
+/// - pass-markers (none, array): Marks of the stations that are passed, such as `"↓"`.
+/// - not-running-markers (none, array): Marks of the stations where the train does not run, such as `"‥"`.
+/// - arrival-labels (none, array): Labels of the arrival sub-rows.
+/// - departure-labels (none, array): Labels of the departure sub-rows.
+/// - mileage-column (none, str): The header of the column with the mileage of the stations, in kilometers.
+/// -> dictionary
+#let load(
+  data,
+  delimiter: none,
+  time-formats: none,
+  pass-markers: none,
+  not-running-markers: none,
+  arrival-labels: none,
+  departure-labels: none,
+  mileage-column: none,
+  train-label: make-train-label,
+  station-label: station => [#station.name],
+  train-stroke: train => black,
+) = {
+  let config = (
+    delimiter: delimiter,
+    time_formats: time-formats,
+    pass_markers: pass-markers,
+    not_running_markers: not-running-markers,
+    arrival_labels: arrival-labels,
+    departure_labels: departure-labels,
+    mileage_column: mileage-column,
+  )
+  finish-import(
+    cbor(plg.import_spreadsheet(
+      data,
+      cbor.encode(config.pairs().filter(((key, value)) => value != none).to-dict()),
+    )),
+    station-label: station-label,
+    train-label: train-label,
+    train-stroke: train-stroke,
+  )
+}
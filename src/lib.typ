@@ -1,6 +1,11 @@
 #import "paiagram.typ": paiagram
+#import "occupancy.typ": occupancy
 #import "foreign/qetrc.typ"
+#import "foreign/etrc.typ"
 #import "foreign/jgrpp.typ"
 #import "foreign/oudiasecond.typ"
+#import "foreign/gtfs.typ"
+#import "foreign/railml.typ"
+#import "foreign/spreadsheet.typ"
 #import "timetable.typ"
 #import "utils.typ": *
@@ -0,0 +1,182 @@
+#import "utils.typ": *
+#let to-point((x, y)) = (x * 1pt, y * 1pt)
+
+/// Gets a colour out of a train stroke.
+#let _stroke-paint(train-stroke) = {
+  if type(train-stroke) == array {
+    train-stroke = train-stroke.last()
+  }
+  if type(train-stroke) == color {
+    train-stroke
+  } else if type(train-stroke) == stroke and train-stroke.paint != auto {
+    train-stroke.paint
+  } else {
+    gray
+  }
+}
+
+/// Draws a track occupancy diagram of a station.
+/// -> content
+#let occupancy(
+  /// Available trains.
+  /// -> dictionary
+  trains: (:),
+  /// Available stations.
+  /// -> dictionary
+  stations: (:),
+  /// Available intervals. They are not used, but accepted so that the same data could be spread.
+  /// -> array
+  intervals: (:),
+  /// Named sets of holidays, with dates written as `"YYYY-MM-DD"`. Train calendars could refer to them.
+  /// -> dictionary
+  holidays: (:),
+  /// The station to draw.
+  /// -> str
+  station: none,
+  /// When to start drawing the diagram.
+  /// -> int
+  start-hour: 0,
+  /// When to stop drawing the diagram.
+  /// -> int
+  end-hour: 24,
+  /// Unit length of the diagram.
+  /// -> length
+  unit-length: 1cm,
+  /// How much to scale the time axis.
+  /// -> float
+  time-axis-scale: 4.0,
+  /// How much space to leave between tracks.
+  /// -> length
+  track-space: 1cm,
+  /// Only draw trains running on this day. Either a date, a weekday such as `"monday"`, or `"holiday"`.
+  /// All trains are drawn if `none`.
+  /// -> none | datetime | str
+  operating-day: none,
+  /// What to do with train labels that could not be placed without overlapping others. `"drop"` leaves
+  /// them out, `"best_effort"` draws them where they overlap the least, and `"overflow"` draws them
+  /// in rows below the tracks.
+  /// -> string
+  label-fallback: "drop",
+  /// Warnings of the importer the network was read with. The native importers return them with
+  /// the network, so they are passed along when it is spread.
+  /// -> array
+  warnings: (),
+  /// What to draw below the diagram for the warnings of the importer and of the layout, e.g. parts
+  /// of the network that were skipped or approximated. A function that receives the array of
+  /// warnings, each a dictionary with `code`, `severity`, `message`, `train`, `station`, `entry` and
+  /// `times`, and returns content. `list-warnings` lists them one per line. If `none`, nothing is
+  /// drawn, except in debug mode, where the warnings are listed.
+  /// -> none | function
+  show-warnings: none,
+  /// Debug mode flick
+  /// -> bool
+  debug: false,
+) = context {
+  let measure-size(it) = {
+    let size = measure(it)
+    (size.width / 1pt, size.height / 1pt)
+  }
+  let new-stations = (:)
+  for (k, v) in stations {
+    v.insert("label_size", measure-size(v.label))
+    new-stations.insert(k, v)
+  }
+  let new-trains = (:)
+  for (k, v) in trains {
+    v.insert("label_size", measure-size(v.label))
+    new-trains.insert(k, v)
+  }
+  let hours = end-hour - start-hour
+  let a = check-errors(cbor(plg.process_occupancy(
+    cbor.encode((
+      version: schema-version,
+      stations: new-stations,
+      trains: new-trains,
+      intervals: intervals,
+      holidays: holidays,
+    )),
+    cbor.encode((
+      version: schema-version,
+      station: station,
+      start_time: int(start-hour) * 60 * 60,
+      end_time: int(end-hour) * 60 * 60,
+      unit_length: unit-length / 1pt,
+      time_axis_scale: float(time-axis-scale),
+      track_space: track-space / 1pt,
+      operating_day: encode-operating-day(operating-day),
+      label_fallback: label-fallback,
+    )),
+  )))
+  assert.eq(a.version, schema-version, message: "The plugin returned an output of another version")
+  box(
+    stroke: if debug { blue },
+    width: (a.collision_manager.x_max - a.collision_manager.x_min) * 1pt,
+    height: (a.collision_manager.y_max - a.collision_manager.y_min) * 1pt,
+    {
+      let place-curve = place.with(dx: a.collision_manager.x_min * -1pt, dy: a.collision_manager.y_min * -1pt)
+      let width = hours * time-axis-scale * unit-length
+
+      place-curve({
+        for i in range(hours + 1) {
+          place(dx: width / hours * i, dy: -track-space / 2, line(angle: 90deg, length: a.tracks.len() * track-space, stroke: gray))
+          if i < hours {
+            place(dx: width / hours * i, dy: -track-space / 2, place(bottom + center, dy: -5pt)[
+              #calc.rem(calc.rem(i + start-hour, 24) + 24, 24)
+            ])
+          }
+        }
+        for track in a.tracks {
+          place(dy: track.position * 1pt, line(length: width, stroke: if track.main { gray } else {
+            stroke(paint: gray, dash: "dashed")
+          }))
+          place(dy: track.position * 1pt, place(horizon + right, dx: -3pt, text(
+            fill: if track.extra { red } else { text.fill },
+            track.name,
+          )))
+        }
+      })
+
+      place-curve({
+        for block in a.blocks {
+          let ((x0, y0), (x1, y1)) = block.rect
+          let paint = _stroke-paint(trains.at(block.train).at("stroke", default: gray))
+          place(dx: x0 * 1pt, dy: y0 * 1pt, rect(
+            width: calc.max(x1 - x0, 1) * 1pt,
+            height: (y1 - y0) * 1pt,
+            fill: paint.transparentize(60%),
+            stroke: if block.conflict { 1pt + red } else { .5pt + paint },
+          ))
+          if block.placement != "dropped" {
+            let (lx, ly) = block.label
+            place(dx: lx * 1pt, dy: ly * 1pt, place(bottom + left, trains.at(block.train).label))
+          }
+        }
+      })
+
+      if debug {
+        for col in a.collision_manager.collisions {
+          let (first, ..rest) = col
+          let ops = (
+            curve.move(to-point(first)),
+            ..rest.map(it => curve.line(to-point(it))),
+          )
+          place-curve(curve(
+            stroke: stroke(
+              paint: blue,
+              join: "round",
+            ),
+            fill: blue.transparentize(80%),
+            ..ops,
+            curve.close(),
+          ))
+        }
+      }
+    },
+  )
+  let all-warnings = warnings + a.warnings
+  if show-warnings != none {
+    show-warnings(all-warnings)
+  } else if debug {
+    list-warnings(all-warnings)
+  }
+}
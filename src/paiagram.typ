@@ -12,7 +12,10 @@
   stations: (:),
   /// Available intervals.
   /// -> array
-  intervals: (:),
+  intervals: (),
+  /// Named sets of holidays, with dates written as `"YYYY-MM-DD"`. Train calendars could refer to them.
+  /// -> dictionary
+  holidays: (:),
   /// Stations to draw.
   /// -> array
   stations-to-draw: (),
@@ -40,6 +43,51 @@
   /// How much space to leave between stacked lines.
   /// -> length
   line-stack-space: 2pt,
+  /// Only draw trains running on this day. Either a date, a weekday such as `"monday"`, or `"holiday"`.
+  /// All trains are drawn if `none`.
+  /// -> none | datetime | str
+  operating-day: none,
+  /// What to do with train labels that could not be placed without overlapping others. `"drop"` leaves
+  /// them out, `"best_effort"` draws them where they overlap the least, and `"overflow"` draws them
+  /// in rows below the diagram.
+  /// -> string
+  label-fallback: "drop",
+  /// The order in which trains are laid out. Trains laid out earlier get the preferred spots for
+  /// their labels. `"first_departure"` lays out the train that departs first first, `"priority"`
+  /// the train with the highest `priority` first, and `"class"` follows `class-priorities`.
+  /// -> string
+  layout-order: "first_departure",
+  /// Train classes, i.e. the `type` metadata of trains, from the highest priority to the lowest.
+  /// -> array
+  class-priorities: (),
+  /// How the places of the labels are chosen. `"greedy"` places them one at a time in the layout
+  /// order, and `"global"` starts from those places and searches for a combination that keeps the
+  /// labels closer to their lines, without placing fewer of them.
+  /// -> string
+  label-layout: "greedy",
+  /// How many steps the global search for label places takes. More steps take longer, but find
+  /// better places. The search is bounded by steps rather than by time, so that the same document
+  /// always gives the same diagram; the plugin has no clock to measure time with anyway.
+  /// -> int
+  label-search-steps: 100000,
+  /// The seed of the global search. The same seed always gives the same places.
+  /// -> int
+  label-search-seed: 0,
+  /// What to draw where trains stop. A function that receives the kind of the entry, i.e. `"stop"`,
+  /// `"pass"`, or `"technical_stop"`, and returns content. Nothing is drawn if `none`.
+  /// -> none | function
+  stop-marker: none,
+  /// Warnings of the importer the network was read with. The native importers return them with
+  /// the network, so they are passed along when it is spread.
+  /// -> array
+  warnings: (),
+  /// What to draw below the diagram for the warnings of the importer and of the layout, e.g. parts
+  /// of the network that were skipped or approximated. A function that receives the array of
+  /// warnings, each a dictionary with `code`, `severity`, `message`, `train`, `station`, `entry` and
+  /// `times`, and returns content. `list-warnings` lists them one per line. If `none`, nothing is
+  /// drawn, except in debug mode, where the warnings are listed.
+  /// -> none | function
+  show-warnings: none,
   /// Debug mode flick
   /// -> bool
   debug: false,
@@ -59,13 +107,16 @@
     new-trains.insert(k, v)
   }
   let hours = end-hour - start-hour
-  let a = cbor(plg.process(
+  let a = check-errors(cbor(plg.process(
     cbor.encode((
+      version: schema-version,
       stations: new-stations,
       trains: new-trains,
       intervals: intervals,
+      holidays: holidays,
     )),
     cbor.encode((
+      version: schema-version,
       stations_to_draw: stations-to-draw,
       start_time: int(start-hour) * 60 * 60,
       end_time: int(end-hour) * 60 * 60,
@@ -75,8 +126,16 @@
       time_axis_scale: float(time-axis-scale),
       label_angle: label-angle.rad(),
       line_stack_space: line-stack-space / 1pt,
+      operating_day: encode-operating-day(operating-day),
+      label_fallback: label-fallback,
+      layout_order: layout-order,
+      class_priorities: class-priorities,
+      label_layout: label-layout,
+      label_search_steps: label-search-steps,
+      label_search_seed: label-search-seed,
     )),
-  ))
+  )))
+  assert.eq(a.version, schema-version, message: "The plugin returned an output of another version")
   box(
     stroke: if debug { blue },
     width: (a.collision_manager.x_max - a.collision_manager.x_min) * 1pt,
@@ -161,16 +220,30 @@
                 ..ops,
               ))
             }
+            if stop-marker != none {
+              for entry in edge.entries {
+                let (x, y) = entry.arrival
+                place(dx: x * 1pt, dy: y * 1pt, place(center + horizon, stop-marker(entry.kind)))
+              }
+            }
             let (start_angle, end_angle) = edge.labels.angles
+            let (start_placement, end_placement) = edge.labels.placements
             let train-label = trains.at(train.name).label
-            place(dx: first.at(0) * 1pt, dy: first.at(1) * 1pt, rotate(origin: top + left, start_angle * 1rad, place(
-              bottom + left,
-              train-label,
-            )))
-            place(dx: last.at(0) * 1pt, dy: last.at(1) * 1pt, rotate(origin: top + left, end_angle * 1rad, place(
-              bottom + right,
-              train-label,
-            )))
+            for (placement, (x, y), angle, alignment) in (
+              (start_placement, first, start_angle, bottom + left),
+              (end_placement, last, end_angle, bottom + right),
+            ) {
+              if type(placement) == dictionary {
+                // moved to the overflow area, where labels are level and start at the corner
+                let (x, y) = placement.overflow
+                place(dx: x * 1pt, dy: y * 1pt, place(bottom + left, train-label))
+              } else if placement != "dropped" {
+                place(dx: x * 1pt, dy: y * 1pt, rotate(origin: top + left, angle * 1rad, place(
+                  alignment,
+                  train-label,
+                )))
+              }
+            }
             if debug {
               for (i, pt) in edge.edges.enumerate() {
                 place(center + horizon, dx: pt.at(0) * 1pt, dy: pt.at(1) * 1pt, text(size: .7em, weight: 600)[#i])
@@ -200,4 +273,10 @@
       }
     },
   )
+  let all-warnings = warnings + a.warnings
+  if show-warnings != none {
+    show-warnings(all-warnings)
+  } else if debug {
+    list-warnings(all-warnings)
+  }
 }
@@ -5,7 +5,7 @@
   let hour = int(normalized-time / 3600)
   let minute = int(calc.rem(normalized-time, 3600) / 60)
   let c = strfmt("{:02}{:02}", hour, minute)
-  if matched.arrival == matched.departure {
+  if is-pass(matched) {
     text(fill: gray, size: .8em, weight: 600, c)
   } else {
     c
@@ -157,7 +157,7 @@
       .at(hour - start)
       .push({
         let c = strfmt("{:02}", hour, minute)
-        if e.arrival == e.departure {
+        if is-pass(e) {
           text(fill: gray, size: .8em, weight: 600, c)
         } else {
           c
@@ -1,5 +1,41 @@
 #import "@preview/oxifmt:1.0.0": strfmt
 #let plg = plugin("paiagram_wasm.wasm")
+/// The version of the documents passed to and returned by the plugin.
+#let schema-version = 1
+/// The JSON Schema of the documents passed to and returned by the plugin.
+/// -> dictionary
+#let schema() = json(plg.schema())
+/// Formats an error or a warning of the plugin as one line, with its code and where it happened.
+///
+/// - diagnostic (dictionary): The error or the warning
+/// -> str
+#let format-diagnostic(diagnostic) = {
+  let location = ()
+  if diagnostic.train != none { location.push("train '" + diagnostic.train + "'") }
+  if diagnostic.station != none { location.push("station '" + diagnostic.station + "'") }
+  if diagnostic.entry != none { location.push("entry " + str(diagnostic.entry)) }
+  "[" + diagnostic.code + "] " + location.map(it => it + ": ").join() + diagnostic.message
+}
+/// Panics with the errors the plugin returned instead of its output, if any. Every function of the
+/// plugin, including the importers, returns its errors this way.
+///
+/// - result (dictionary): The decoded output of the plugin
+/// -> dictionary
+#let check-errors(result) = {
+  if "errors" in result {
+    panic(result.errors.map(format-diagnostic).join("\n"))
+  }
+  result
+}
+/// Lists warnings of the plugin, one per line. Draws nothing if there are none.
+///
+/// - warnings (array): The warnings
+/// -> content
+#let list-warnings(warnings) = {
+  if warnings.len() > 0 {
+    block(text(size: .8em, fill: orange.darken(30%), warnings.map(format-diagnostic).join("\n")))
+  }
+}
 #let make-train-label(train) = {
   pad(bottom: .14em, text(top-edge: "cap-height", bottom-edge: "baseline")[
     #place(center + horizon, text(stroke: .1em + white)[#train.name])
@@ -15,3 +51,59 @@
     ),
   )
 }
+
+/// Adds labels and strokes to a network given by one of the native importers, so that it could
+/// be passed to `paiagram`. The warnings of the importer are kept under `warnings`, which
+/// `paiagram` passes on to its `show-warnings`.
+///
+/// - imported (dictionary): The decoded output of the importer
+/// - station-label (function): Makes the label of a station out of its name and data
+/// - train-label (function): Makes the label of a train out of its name and data
+/// - train-stroke (function): Makes the stroke of a train out of its name and data
+/// -> dictionary
+#let finish-import(imported, station-label: none, train-label: none, train-stroke: none) = {
+  let imported = check-errors(imported)
+  let stations = (:)
+  for (name, station) in imported.stations {
+    station.insert("label", station-label((name: name, ..station)))
+    stations.insert(name, station)
+  }
+  let trains = (:)
+  for (name, train) in imported.trains {
+    let info = (name: name, ..train)
+    train.insert("label", train-label(info))
+    train.insert("stroke", train-stroke(info))
+    trains.insert(name, train)
+  }
+  (stations: stations, trains: trains, intervals: imported.intervals, warnings: imported.at("warnings", default: ()))
+}
+
+/// Whether a schedule entry is a pass. Entries without a `kind` are passes if they do not dwell.
+///
+/// - entry (dictionary): The schedule entry
+/// -> bool
+#let is-pass(entry) = {
+  if "kind" in entry {
+    entry.kind == "pass"
+  } else {
+    entry.arrival == entry.departure
+  }
+}
+
+/// Turns an operating day into the form the plugin accepts.
+///
+/// - day (none, datetime, str): A date, a weekday such as `"monday"`, or `"holiday"`
+/// -> none | str | dictionary
+#let encode-operating-day(day) = {
+  if day == none {
+    none
+  } else if type(day) == datetime {
+    (date: day.display("[year]-[month]-[day]"))
+  } else if day == "holiday" {
+    day
+  } else if day in ("monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday") {
+    (weekday: day)
+  } else {
+    (date: day)
+  }
+}
@@ -0,0 +1,407 @@
+use crate::input::*;
+use anyhow::Result;
+use std::collections::HashMap;
+
+pub use crate::diagnostic::{Diagnostic, ErrorCode, Severity};
+pub use crate::input::{
+    EntryKind, LabelFallback, LabelLayout, LayoutOrder, Metadata, Network, NetworkConfig,
+    OperatingDay, Repetition, TrainFrequency,
+};
+pub use crate::output::{LabelPlacement, Output, OutputEdge, OutputEntry, OutputTrain};
+pub use crate::types::{Date, GraphLength, IntervalLength, Node, ScaleMode, Time, Weekday};
+
+/// The version of this module. It changes whenever its items change in a way that could break
+/// existing programs.
+pub const API_VERSION: u32 = 1;
+
+/// Lay out the trains of the network on the diagram described by the configuration.
+pub fn layout(network: Network, config: NetworkConfig) -> Result<Output> {
+    let mut output = Output::new(config);
+    output.populate(network)?;
+    Ok(output)
+}
+
+/// Builds a `Network`. Stations and trains are checked when the network is built.
+#[derive(Default)]
+pub struct NetworkBuilder {
+    stations: Vec<(String, StationHelper)>,
+    trains: Vec<(String, TrainHelper)>,
+    intervals: Vec<((String, String), IntervalHelper)>,
+    holidays: HashMap<String, Vec<Date>>,
+}
+
+impl NetworkBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn station(mut self, name: impl Into<String>, station: StationBuilder) -> Self {
+        self.stations.push((name.into(), station.helper));
+        self
+    }
+
+    pub fn train(mut self, name: impl Into<String>, train: TrainBuilder) -> Self {
+        self.trains.push((name.into(), train.helper));
+        self
+    }
+
+    /// Add an interval between two stations. Bidirectional intervals could be travelled both
+    /// ways.
+    pub fn interval(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        length: IntervalLength,
+        bidirectional: bool,
+    ) -> Self {
+        self.intervals.push((
+            (from.into(), to.into()),
+            IntervalHelper {
+                length,
+                bidirectional: Some(bidirectional),
+            },
+        ));
+        self
+    }
+
+    /// Add a named set of holidays, which calendars could refer to.
+    pub fn holidays(mut self, name: impl Into<String>, dates: Vec<Date>) -> Self {
+        self.holidays.insert(name.into(), dates);
+        self
+    }
+
+    pub fn build(self) -> Result<Network> {
+        Network::try_from(NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations: self.stations,
+            trains: self.trains,
+            intervals: self.intervals,
+            holidays: self.holidays,
+        })
+    }
+}
+
+/// Builds a station of a `NetworkBuilder`.
+pub struct StationBuilder {
+    helper: StationHelper,
+}
+
+impl Default for StationBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StationBuilder {
+    pub fn new() -> Self {
+        Self {
+            helper: StationHelper {
+                label_size: Default::default(),
+                tracks: None,
+                metadata: Metadata::new(),
+            },
+        }
+    }
+
+    /// The width and the height of the label.
+    pub fn label_size(mut self, width: f64, height: f64) -> Self {
+        self.helper.label_size = (width.into(), height.into());
+        self
+    }
+
+    /// Give the station tracks numbered from 1.
+    pub fn track_count(mut self, count: u16) -> Self {
+        self.helper.tracks = Some(TracksHelper::Count(count));
+        self
+    }
+
+    /// Give the station tracks with the given names. Sidings are not main tracks.
+    pub fn tracks(mut self, tracks: impl IntoIterator<Item = (String, bool)>) -> Self {
+        self.helper.tracks = Some(TracksHelper::List(
+            tracks
+                .into_iter()
+                .map(|(name, main)| TrackHelper {
+                    name,
+                    main: Some(main),
+                })
+                .collect(),
+        ));
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: ciborium::Value) -> Self {
+        self.helper.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// Builds a train of a `NetworkBuilder`. Entries are added in the order the train runs.
+pub struct TrainBuilder {
+    helper: TrainHelper,
+}
+
+impl Default for TrainBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrainBuilder {
+    /// A train that runs once a day, every day.
+    pub fn new() -> Self {
+        Self {
+            helper: TrainHelper {
+                frequency: None,
+                calendar: None,
+                label_size: Default::default(),
+                priority: None,
+                schedule: Vec::new(),
+                metadata: Metadata::new(),
+            },
+        }
+    }
+
+    /// The width and the height of the label.
+    pub fn label_size(mut self, width: f64, height: f64) -> Self {
+        self.helper.label_size = (width.into(), height.into());
+        self
+    }
+
+    /// Trains with a higher priority are laid out first if the layout order is by priority.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.helper.priority = Some(priority);
+        self
+    }
+
+    /// Add a station the train stops at.
+    pub fn stop(self, station: impl Into<String>, arrival: Time, departure: Time) -> Self {
+        self.entry(station, arrival, departure, EntryKind::Stop)
+    }
+
+    /// Add a station the train passes without stopping.
+    pub fn pass(self, station: impl Into<String>, time: Time) -> Self {
+        self.entry(station, time, time, EntryKind::Pass)
+    }
+
+    pub fn entry(
+        mut self,
+        station: impl Into<String>,
+        arrival: Time,
+        departure: Time,
+        kind: EntryKind,
+    ) -> Self {
+        self.helper.schedule.push(ScheduleEntryHelper {
+            arrival,
+            departure,
+            station: station.into(),
+            track: None,
+            kind: Some(kind),
+        });
+        self
+    }
+
+    /// Put the last entry on the track with the given index.
+    pub fn track(mut self, index: u16) -> Self {
+        if let Some(entry) = self.helper.schedule.last_mut() {
+            entry.track = Some(TrackRef::Index(index));
+        }
+        self
+    }
+
+    /// Put the last entry on the track with the given name.
+    pub fn track_name(mut self, name: impl Into<String>) -> Self {
+        if let Some(entry) = self.helper.schedule.last_mut() {
+            entry.track = Some(TrackRef::Name(name.into()));
+        }
+        self
+    }
+
+    pub fn frequency(mut self, frequency: TrainFrequency) -> Self {
+        self.helper.frequency = Some(frequency);
+        self
+    }
+
+    /// Only run on the days of the calendar.
+    pub fn calendar(mut self, calendar: CalendarBuilder) -> Self {
+        self.helper.calendar = Some(calendar.helper);
+        self
+    }
+
+    pub fn metadata(mut self, key: impl Into<String>, value: ciborium::Value) -> Self {
+        self.helper.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// Builds the days on which a train runs. A calendar runs every day until it is narrowed down.
+pub struct CalendarBuilder {
+    helper: CalendarHelper,
+}
+
+impl Default for CalendarBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CalendarBuilder {
+    pub fn new() -> Self {
+        Self {
+            helper: CalendarHelper {
+                weekdays: None,
+                date_ranges: Vec::new(),
+                include: Vec::new(),
+                exclude: Vec::new(),
+                holidays: Vec::new(),
+            },
+        }
+    }
+
+    /// Only run on these weekdays, and on holidays if `holidays` is true.
+    pub fn weekdays(mut self, weekdays: impl IntoIterator<Item = Weekday>, holidays: bool) -> Self {
+        let mut mask = if holidays { HOLIDAY_BIT } else { 0 };
+        for weekday in weekdays {
+            mask |= 1 << weekday.index();
+        }
+        self.helper.weekdays = Some(mask);
+        self
+    }
+
+    /// Only run between these dates, both included. Could be given many times.
+    pub fn date_range(mut self, first: Date, last: Date) -> Self {
+        self.helper.date_ranges.push((first, last));
+        self
+    }
+
+    /// Always run on this date.
+    pub fn include(mut self, date: Date) -> Self {
+        self.helper.include.push(date);
+        self
+    }
+
+    /// Never run on this date.
+    pub fn exclude(mut self, date: Date) -> Self {
+        self.helper.exclude.push(date);
+        self
+    }
+
+    /// Treat the dates of the named holiday set as holidays.
+    pub fn holiday_set(mut self, name: impl Into<String>) -> Self {
+        self.helper.holidays.push(name.into());
+        self
+    }
+}
+
+/// Builds a `NetworkConfig`. The defaults are the ones of `paiagram` in Typst, with lengths in
+/// points.
+pub struct NetworkConfigBuilder {
+    helper: NetworkConfigHelper,
+}
+
+impl NetworkConfigBuilder {
+    pub fn new(stations_to_draw: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            helper: NetworkConfigHelper {
+                stations_to_draw: stations_to_draw.into_iter().map(Into::into).collect(),
+                start_time: Time::new(0),
+                end_time: Time::new(24 * 60 * 60),
+                // 1cm
+                unit_length: (72.0 / 2.54).into(),
+                position_axis_scale_mode: ScaleMode::Logarithmic,
+                position_axis_scale: 1.0,
+                time_axis_scale: 4.0,
+                label_angle: 30.0f64.to_radians(),
+                line_stack_space: 2.0.into(),
+                operating_day: None,
+                label_fallback: LabelFallback::Drop,
+                layout_order: LayoutOrder::FirstDeparture,
+                class_priorities: Vec::new(),
+                label_layout: LabelLayout::Greedy,
+                label_search_steps: DEFAULT_LABEL_SEARCH_STEPS,
+                label_search_seed: 0,
+            },
+        }
+    }
+
+    /// When to start and stop drawing the diagram.
+    pub fn time_range(mut self, start: Time, end: Time) -> Self {
+        self.helper.start_time = start;
+        self.helper.end_time = end;
+        self
+    }
+
+    pub fn unit_length(mut self, unit_length: f64) -> Self {
+        self.helper.unit_length = unit_length.into();
+        self
+    }
+
+    /// How to scale the position axis, and by how much.
+    pub fn position_axis(mut self, mode: ScaleMode, scale: f64) -> Self {
+        self.helper.position_axis_scale_mode = mode;
+        self.helper.position_axis_scale = scale;
+        self
+    }
+
+    pub fn time_axis_scale(mut self, scale: f64) -> Self {
+        self.helper.time_axis_scale = scale;
+        self
+    }
+
+    /// How much to rotate the labels, in radians.
+    pub fn label_angle(mut self, angle: f64) -> Self {
+        self.helper.label_angle = angle;
+        self
+    }
+
+    pub fn line_stack_space(mut self, space: f64) -> Self {
+        self.helper.line_stack_space = space.into();
+        self
+    }
+
+    /// Only draw trains running on this day.
+    pub fn operating_day(mut self, day: OperatingDay) -> Self {
+        self.helper.operating_day = Some(day);
+        self
+    }
+
+    /// What to do with labels that could not be placed without overlapping others.
+    pub fn label_fallback(mut self, fallback: LabelFallback) -> Self {
+        self.helper.label_fallback = fallback;
+        self
+    }
+
+    /// The order in which trains are laid out.
+    pub fn layout_order(mut self, order: LayoutOrder) -> Self {
+        self.helper.layout_order = order;
+        self
+    }
+
+    /// Train classes, from the highest priority to the lowest. Implies laying out by class.
+    pub fn class_priorities(
+        mut self,
+        classes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.helper.class_priorities = classes.into_iter().map(Into::into).collect();
+        self.helper.layout_order = LayoutOrder::Class;
+        self
+    }
+
+    /// How the places of the labels are chosen.
+    pub fn label_layout(mut self, layout: LabelLayout) -> Self {
+        self.helper.label_layout = layout;
+        self
+    }
+
+    /// Place the labels with a global search of `steps` steps, seeded by `seed`. Implies the
+    /// global label layout.
+    pub fn label_search(mut self, steps: u32, seed: u64) -> Self {
+        self.helper.label_search_steps = steps;
+        self.helper.label_search_seed = seed;
+        self.helper.label_layout = LabelLayout::Global;
+        self
+    }
+
+    pub fn build(self) -> Result<NetworkConfig> {
+        NetworkConfig::try_from(self.helper)
+    }
+}
@@ -44,3 +44,11 @@ pub static PATTERNS: Lazy<Vec<PatternType>> = Lazy::new(|| vec![
     PatternType { regex: Lazy::new(|| Regex::new(r"^5[3-4]\d{3}").unwrap()),   typ: "补机" },
     PatternType { regex: Lazy::new(|| Regex::new(r"^55\d{3}").unwrap()),       typ: "试运转" },
 ]);
+
+/// Get the type of a train from its number. Defaults to `普客`.
+pub fn match_type(name: &str) -> &'static str {
+    PATTERNS
+        .iter()
+        .find(|pt| pt.regex.is_match(name))
+        .map_or("普客", |pt| pt.typ)
+}
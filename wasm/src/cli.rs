@@ -0,0 +1,258 @@
+use crate::foreign;
+use crate::input::*;
+use crate::output::Output;
+use crate::render;
+use anyhow::{Context, Result, anyhow};
+use clap::{Parser, ValueEnum};
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Draws train diagrams without Typst.
+#[derive(Parser)]
+#[command(name = "paiagram", version)]
+pub struct Args {
+    /// The network, as JSON or CBOR, or a file of one of the supported programs.
+    input: PathBuf,
+    /// The format of the input. Guessed from the extension if not given.
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+    /// Options of the importer, as JSON or CBOR.
+    #[arg(long)]
+    import_config: Option<PathBuf>,
+    /// The configuration of the diagram, as JSON or CBOR, with the same fields the plugin takes.
+    /// Missing fields take the defaults of `paiagram`, and all stations are drawn if
+    /// `stations_to_draw` is missing.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Where to write the diagram. It is drawn as SVG or PNG, depending on the extension.
+    #[arg(short, long)]
+    output: PathBuf,
+    /// The size of the labels, in points.
+    #[arg(long, default_value_t = 11.0)]
+    font_size: f64,
+    /// Pixels per inch of PNG images.
+    #[arg(long, default_value_t = 96.0)]
+    dpi: f64,
+    /// Fonts to draw the labels of PNG images with, e.g. for CJK names. Characters that none of
+    /// them have are drawn with a built-in font.
+    #[arg(long)]
+    font: Vec<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// A network as JSON, in the same shape as the one passed to the plugin.
+    Json,
+    /// A network as CBOR, in the same shape as the one passed to the plugin.
+    Cbor,
+    /// A qETRC or pyETRC diagram.
+    Qetrc,
+    /// An ETRC `.trc` file.
+    Etrc,
+    /// An OuDia or OuDiaSecond file.
+    Oudia,
+    /// A zipped GTFS static feed.
+    Gtfs,
+    /// A railML 2 or railML 3 file.
+    Railml,
+    /// A CSV or TSV timetable with one column per train.
+    Spreadsheet,
+    /// A JSON array of vehicles exported by JGRPP.
+    Jgrpp,
+}
+
+/// The defaults of `paiagram` in Typst, in the units of the plugin.
+fn default_config(network: &NetworkHelper) -> serde_json::Value {
+    serde_json::json!({
+        "stations_to_draw": network.stations.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        "start_time": 0,
+        "end_time": 24 * 60 * 60,
+        // 1cm
+        "unit_length": 72.0 / 2.54,
+        "position_axis_scale_mode": "logarithmic",
+        "position_axis_scale": 1.0,
+        "time_axis_scale": 4.0,
+        "label_angle": 30.0f64.to_radians(),
+        "line_stack_space": 2.0,
+        "operating_day": null,
+        "label_fallback": "drop",
+        "layout_order": "first_departure",
+        "label_layout": "greedy",
+    })
+}
+
+pub fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", crate::format_error_chain(error));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let mut network = read_network(&args)?;
+    fill_label_sizes(&mut network, args.font_size);
+
+    let mut config = default_config(&network);
+    if let Some(path) = &args.config {
+        let given: serde_json::Value = read_document(path)?;
+        let (Some(config), Some(given)) = (config.as_object_mut(), given.as_object()) else {
+            return Err(anyhow!("The config must be a map"));
+        };
+        config.extend(given.clone());
+    }
+    let config: NetworkConfig =
+        serde_json::from_value(config).context("Failed to deserialize config")?;
+
+    let network = Network::try_from(network).context("Failed to read network")?;
+    let decorations = render::Decorations::new(&network);
+    let mut output = Output::new(config);
+    output
+        .populate(network)
+        .context("Failed to populate output from network and config")?;
+
+    for warning in output.warnings() {
+        eprintln!("warning: {}", warning.message);
+    }
+
+    let scene = render::draw(&output, &decorations, args.font_size);
+    let image = match extension(&args.output).as_deref() {
+        Some("svg") => render::svg::write(&scene).into_bytes(),
+        Some("png") => {
+            let fonts = args
+                .font
+                .iter()
+                .map(|path| {
+                    std::fs::read(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let fonts = render::png::Fonts::new(fonts)?;
+            render::png::write(&scene, args.dpi, &fonts).context("Failed to draw the image")?
+        }
+        _ => {
+            return Err(anyhow!(
+                "Cannot tell the format of {}, use .svg or .png",
+                args.output.display()
+            ));
+        }
+    };
+    std::fs::write(&args.output, image)
+        .with_context(|| format!("Failed to write {}", args.output.display()))
+}
+
+/// Read the network from the input, using the importers for files of other programs.
+fn read_network(args: &Args) -> Result<NetworkHelper> {
+    let data = std::fs::read(&args.input)
+        .with_context(|| format!("Failed to read {}", args.input.display()))?;
+    let format = match args.format {
+        Some(format) => format,
+        None => guess_format(&args.input, &data)?,
+    };
+    let import_config = match &args.import_config {
+        Some(path) => read_document(path)?,
+        None => ciborium::Value::Map(Vec::new()),
+    };
+    let imported = match format {
+        InputFormat::Json => {
+            let document = serde_json::from_slice(&data).context("Failed to parse JSON")?;
+            return NetworkHelper::from_document(document).context("Failed to deserialize network");
+        }
+        InputFormat::Cbor => {
+            let document = ciborium::from_reader(&data[..]).context("Failed to parse CBOR")?;
+            return NetworkHelper::from_document(document).context("Failed to deserialize network");
+        }
+        InputFormat::Qetrc => foreign::qetrc::import(&data, options(&import_config)?)
+            .context("Failed to import qETRC file")?,
+        InputFormat::Etrc => foreign::etrc::import(&data).context("Failed to import ETRC file")?,
+        InputFormat::Oudia => foreign::oudia::import(&data, options(&import_config)?)
+            .context("Failed to import OuDia file")?,
+        InputFormat::Gtfs => foreign::gtfs::import(&data, options(&import_config)?)
+            .context("Failed to import GTFS feed")?,
+        InputFormat::Railml => foreign::railml::import(&data, options(&import_config)?)
+            .context("Failed to import railML file")?,
+        InputFormat::Spreadsheet => foreign::spreadsheet::import(&data, options(&import_config)?)
+            .context("Failed to import spreadsheet")?,
+        InputFormat::Jgrpp => {
+            let vehicles =
+                serde_json::from_slice(&data).context("Failed to deserialize vehicles")?;
+            foreign::jgrpp::import(vehicles, options(&import_config)?)
+                .context("Failed to import JGRPP vehicles")?
+        }
+    };
+    for warning in &imported.warnings {
+        eprintln!("warning: {warning}");
+    }
+    Ok(imported.network)
+}
+
+/// Deserialize the options of an importer.
+fn options<T: DeserializeOwned>(import_config: &ciborium::Value) -> Result<T> {
+    import_config
+        .deserialized()
+        .context("Failed to deserialize import config")
+}
+
+fn guess_format(path: &Path, data: &[u8]) -> Result<InputFormat> {
+    Ok(match extension(path).as_deref() {
+        Some("json") => {
+            // qETRC files are JSON as well, but they do not have intervals
+            let value: serde_json::Value =
+                serde_json::from_slice(data).context("Failed to parse JSON")?;
+            if value.get("intervals").is_some() {
+                InputFormat::Json
+            } else if value.is_array() {
+                InputFormat::Jgrpp
+            } else {
+                InputFormat::Qetrc
+            }
+        }
+        Some("cbor") => InputFormat::Cbor,
+        Some("pyetgr") => InputFormat::Qetrc,
+        Some("trc") => InputFormat::Etrc,
+        Some("oud" | "oud2") => InputFormat::Oudia,
+        Some("zip") => InputFormat::Gtfs,
+        Some("xml" | "railml" | "railmlx") => InputFormat::Railml,
+        Some("csv" | "tsv") => InputFormat::Spreadsheet,
+        _ => {
+            return Err(anyhow!(
+                "Cannot tell the format of {}, use --format",
+                path.display()
+            ));
+        }
+    })
+}
+
+/// Read a JSON file, or a CBOR file if it is not JSON.
+fn read_document<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if extension(path).as_deref() == Some("cbor") {
+        return ciborium::from_reader(&data[..])
+            .with_context(|| format!("Failed to deserialize {}", path.display()));
+    }
+    serde_json::from_slice(&data)
+        .or_else(|_| ciborium::from_reader(&data[..]))
+        .with_context(|| format!("Failed to deserialize {}", path.display()))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+/// Estimate the sizes of the labels that were not measured before.
+fn fill_label_sizes(network: &mut NetworkHelper, font_size: f64) {
+    for (name, station) in &mut network.stations {
+        if station.label_size == Default::default() {
+            station.label_size = render::metrics::station_label_size(name, font_size);
+        }
+    }
+    for (name, train) in &mut network.trains {
+        if train.label_size == Default::default() {
+            train.label_size = render::metrics::train_label_size(name, font_size);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::diagnostic::*;
 use crate::types::*;
 use anyhow::Result;
 use ordered_float::OrderedFloat;
@@ -6,7 +7,7 @@ use std::collections::{BTreeMap, HashMap};
 
 /// Compute the Axis-Aligned Bounding Box (AABB) for a collection of nodes.
 /// Returns (x_min, x_max, y_min, y_max) or None if the collection is empty.
-fn aabb(nodes: &[Node]) -> Option<(GraphLength, GraphLength, GraphLength, GraphLength)> {
+pub(crate) fn aabb(nodes: &[Node]) -> Option<(GraphLength, GraphLength, GraphLength, GraphLength)> {
     if nodes.is_empty() {
         return None;
     }
@@ -28,18 +29,59 @@ fn aabb(nodes: &[Node]) -> Option<(GraphLength, GraphLength, GraphLength, GraphL
     ))
 }
 
+/// How far a polygon has to move along the angle to stop overlapping another one, or `None` if
+/// they do not overlap.
+pub(crate) fn required_distance(collision: &[Node], existing: &[Node], angle: f64) -> Option<f64> {
+    let (mtv_x, mtv_y, overlap) = sat_collision_test_with_mtv(collision, existing)?;
+    Some(distance_along((mtv_x, mtv_y), overlap, angle))
+}
+
+/// How far a polygon has to move along the angle to resolve an overlap, given the minimum
+/// translation vector and the depth of the overlap.
+pub(crate) fn distance_along(mtv: (f64, f64), overlap: f64, angle: f64) -> f64 {
+    // Calculate the dot product of MTV with movement direction
+    let mtv_dot_movement = mtv.0 * angle.cos() + mtv.1 * angle.sin();
+
+    if mtv_dot_movement.abs() < f64::EPSILON {
+        // Movement direction is perpendicular to MTV
+        // This can happen when:
+        // 1. Label tries to move vertically but collision requires horizontal movement
+        // 2. Two rectangles are side-by-side but we're trying to move up/down
+        // 3. Polygon shapes create complex collision normals
+
+        // Instead of erroring, move along MTV direction instead
+        return overlap;
+    }
+
+    // Calculate required distance to resolve collision (always positive)
+    overlap / mtv_dot_movement.abs()
+}
+
+/// How many times a polygon is moved before giving up.
+const MAX_ITERATIONS: u32 = 255;
+/// Overlaps that would need a shorter move than this to resolve are ignored.
+pub(crate) const SLIDE_TOLERANCE: f64 = 0.5;
+
+/// Where a polygon could be placed.
+pub enum Slide {
+    /// A place without overlaps.
+    Free(Vec<Node>),
+    /// No place without overlaps was found. Holds the place with the smallest overlap.
+    Blocked(Vec<Node>),
+}
+
 /// A collision manager based on the SAT theorem.
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct CollisionManager {
     #[serde(skip)]
     indices: HashMap<(u32, u32), Vec<usize>>,
     collisions: Vec<Vec<Node>>,
     #[serde(skip)]
     unit_size: GraphLength,
-    x_min: GraphLength,
-    x_max: GraphLength,
-    y_min: GraphLength,
-    y_max: GraphLength,
+    pub(crate) x_min: GraphLength,
+    pub(crate) x_max: GraphLength,
+    pub(crate) y_min: GraphLength,
+    pub(crate) y_max: GraphLength,
 }
 
 impl CollisionManager {
@@ -128,65 +170,22 @@ impl CollisionManager {
         };
 
         // Get candidate collisions from spatial index
-        let unit_size_f64: f64 = self.unit_size.into();
-        let x_idx_min = (f64::from(x_min) / unit_size_f64).floor() as u32;
-        let x_idx_max = (f64::from(x_max) / unit_size_f64).ceil() as u32;
-        let y_idx_min = (f64::from(y_min) / unit_size_f64).floor() as u32;
-        let y_idx_max = (f64::from(y_max) / unit_size_f64).ceil() as u32;
-
-        let mut candidates: std::collections::HashSet<usize> = std::collections::HashSet::new();
-        for i in x_idx_min..=x_idx_max {
-            for j in y_idx_min..=y_idx_max {
-                if let Some(indices) = self.indices.get(&(i, j)) {
-                    candidates.extend(indices);
-                }
-            }
-        }
+        let candidates = self.candidates(x_min, x_max, y_min, y_max);
 
         // If no candidates, no collision
         if candidates.is_empty() {
             return Ok(None);
         }
 
-        // Movement direction vector
-        let movement_x = angle.cos();
-        let movement_y = angle.sin();
-
         let mut max_required_distance: f64 = 0.0;
 
         // Check SAT collision with each candidate
         for &idx in &candidates {
-            if let Some(existing_polygon) = self.collisions.get(idx) {
-                if let Some((mtv_x, mtv_y, overlap)) =
-                    sat_collision_test_with_mtv(collision, existing_polygon)
-                {
-                    // We found a collision, calculate required movement distance
-
-                    // Calculate the dot product of MTV with movement direction
-                    let mtv_dot_movement = mtv_x * movement_x + mtv_y * movement_y;
-
-                    if mtv_dot_movement.abs() < f64::EPSILON {
-                        // Movement direction is perpendicular to MTV
-                        // This can happen when:
-                        // 1. Label tries to move vertically but collision requires horizontal movement
-                        // 2. Two rectangles are side-by-side but we're trying to move up/down
-                        // 3. Polygon shapes create complex collision normals
-
-                        // Instead of erroring, try alternative strategies:
-                        // Option 1: Move along MTV direction instead
-                        let required_distance = overlap;
-                        max_required_distance = max_required_distance.max(required_distance);
-
-                        // Option 2: Could also try a slight angle adjustment
-                        // But for now, we'll allow MTV-based movement
-                        continue;
-                    }
-
-                    // Calculate required distance to resolve collision (always positive)
-                    let required_distance = overlap / mtv_dot_movement.abs();
-
-                    max_required_distance = max_required_distance.max(required_distance);
-                }
+            if let Some(existing_polygon) = self.collisions.get(idx)
+                && let Some(required_distance) =
+                    required_distance(collision, existing_polygon, angle)
+            {
+                max_required_distance = max_required_distance.max(required_distance);
             }
         }
 
@@ -197,97 +196,100 @@ impl CollisionManager {
         }
     }
 
-    /// Recursively resolve collisions by moving along the specified angle until no more collisions occur.
-    /// Returns the total distance needed to move to resolve all collisions.
-    ///
-    /// # Arguments
-    /// * `collision` - The polygon to test and move
-    /// * `angle` - The angle in radians to move along
-    /// * `max_iterations` - Maximum number of recursive iterations to prevent infinite loops
-    ///
-    /// # Returns
-    /// * `Ok(GraphLength)` - Total distance needed to resolve all collisions
-    /// * `Err(_)` - Input invalid, too many iterations, or unresolvable collision
-    pub fn resolve_collisions_recursive(
-        &mut self,
-        collision: Vec<Node>,
-        angle: f64,
-        max_iterations: u32,
-    ) -> Result<(&[Node], GraphLength)> {
-        self.resolve_collisions_recursive_impl(
-            collision,
-            angle,
-            max_iterations,
-            0,
-            GraphLength::from(0.0),
-        )
+    /// The indices of the collisions in the cells covered by the bounding box.
+    fn candidates(
+        &self,
+        x_min: GraphLength,
+        x_max: GraphLength,
+        y_min: GraphLength,
+        y_max: GraphLength,
+    ) -> std::collections::HashSet<usize> {
+        let unit_size_f64: f64 = self.unit_size.into();
+        let x_idx_min = (f64::from(x_min) / unit_size_f64).floor() as u32;
+        let x_idx_max = (f64::from(x_max) / unit_size_f64).ceil() as u32;
+        let y_idx_min = (f64::from(y_min) / unit_size_f64).floor() as u32;
+        let y_idx_max = (f64::from(y_max) / unit_size_f64).ceil() as u32;
+
+        let mut candidates: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        for i in x_idx_min..=x_idx_max {
+            for j in y_idx_min..=y_idx_max {
+                if let Some(indices) = self.indices.get(&(i, j)) {
+                    candidates.extend(indices);
+                }
+            }
+        }
+        candidates
     }
 
-    /// Convenience function to resolve collisions with a default maximum of 100 iterations.
-    /// This is the recommended function to use in most cases.
-    pub fn resolve_collisions(
-        &mut self,
-        collision: Vec<Node>,
-        angle: f64,
-    ) -> Result<(&[Node], GraphLength)> {
-        self.resolve_collisions_recursive(collision, angle, 255)
+    /// How deep a polygon overlaps the existing collisions, summed over all of them.
+    pub fn overlap(&self, collision: &[Node]) -> f64 {
+        let Some((x_min, x_max, y_min, y_max)) = aabb(collision) else {
+            return 0.0;
+        };
+        let mut candidates: Vec<usize> = self
+            .candidates(x_min, x_max, y_min, y_max)
+            .into_iter()
+            .collect();
+        // sum in a fixed order, so that the result does not depend on the hasher
+        candidates.sort_unstable();
+        candidates
+            .into_iter()
+            .filter_map(|idx| sat_collision_test_with_mtv(collision, &self.collisions[idx]))
+            .map(|(_, _, overlap)| overlap)
+            .sum()
     }
 
-    /// Internal recursive implementation
-    fn resolve_collisions_recursive_impl(
-        &mut self,
-        collision: Vec<Node>,
+    /// Whether a polygon could stay where it is if it were slid along the angle, i.e. whether it
+    /// overlaps the existing collisions by no more than `slide` allows.
+    pub fn fits(&self, collision: &[Node], angle: f64) -> Result<bool> {
+        Ok(self
+            .check_collision(collision, angle)?
+            .is_none_or(|required_distance| required_distance.value() <= SLIDE_TOLERANCE))
+    }
+
+    /// Find where a polygon could be placed by moving it along the angle, without adding it.
+    pub fn slide(&self, collision: Vec<Node>, angle: f64) -> Result<Slide> {
+        self.slide_with_limit(collision, angle, MAX_ITERATIONS)
+    }
+
+    fn slide_with_limit(
+        &self,
+        mut collision: Vec<Node>,
         angle: f64,
         max_iterations: u32,
-        current_iteration: u32,
-        accumulated_distance: GraphLength,
-    ) -> Result<(&[Node], GraphLength)> {
-        // Check iteration limit
-        if current_iteration >= max_iterations {
-            return Err(anyhow::anyhow!(
-                "Maximum iterations ({}) reached while resolving collisions",
-                max_iterations
-            ));
-        }
-
-        // Check for collision at current position
-        match self.check_collision(&collision, angle)? {
-            None => {
-                // No collision, we're done
-                Ok((self.add_collision(collision).unwrap(), accumulated_distance))
+    ) -> Result<Slide> {
+        // the place with the smallest overlap so far
+        let mut best: Option<(Vec<Node>, f64)> = None;
+        for _ in 0..max_iterations {
+            let Some(required_distance) = self.check_collision(&collision, angle)? else {
+                return Ok(Slide::Free(collision));
+            };
+            if required_distance.value() <= SLIDE_TOLERANCE {
+                // No movement needed, but still a collision
+                return Ok(Slide::Free(collision));
             }
-            Some(required_distance) => {
-                if required_distance.value() <= 0.5 {
-                    // No movement needed, but still a collision
-                    return Ok((self.add_collision(collision).unwrap(), accumulated_distance));
-                }
-                // Found collision, move and check again
-                let new_total_distance = accumulated_distance + required_distance;
-
-                // Calculate new polygon position after movement
-                let movement_x = angle.cos() * f64::from(required_distance);
-                let movement_y = angle.sin() * f64::from(required_distance);
-
-                let moved_collision: Vec<Node> = collision
-                    .iter()
-                    .map(|node| {
-                        Node(
-                            node.0 + GraphLength::from(movement_x),
-                            node.1 + GraphLength::from(movement_y),
-                        )
-                    })
-                    .collect();
-
-                // Recursively check the moved polygon
-                self.resolve_collisions_recursive_impl(
-                    moved_collision,
-                    angle,
-                    max_iterations,
-                    current_iteration + 1,
-                    new_total_distance,
-                )
+            let overlap = self.overlap(&collision);
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_overlap)| overlap < *best_overlap)
+            {
+                best = Some((collision.clone(), overlap));
             }
+
+            // Calculate new polygon position after movement
+            let movement_x = angle.cos() * f64::from(required_distance);
+            let movement_y = angle.sin() * f64::from(required_distance);
+            collision = collision
+                .iter()
+                .map(|node| {
+                    Node(
+                        node.0 + GraphLength::from(movement_x),
+                        node.1 + GraphLength::from(movement_y),
+                    )
+                })
+                .collect();
         }
+        Ok(Slide::Blocked(best.map_or(collision, |(best, _)| best)))
     }
 }
 
@@ -344,7 +346,10 @@ fn calculate_overlap(min1: f64, max1: f64, min2: f64, max2: f64) -> f64 {
 
 /// SAT collision test that returns the minimum translation vector (MTV).
 /// Returns None if no collision, Some((axis_x, axis_y, overlap)) if collision detected.
-fn sat_collision_test_with_mtv(polygon1: &[Node], polygon2: &[Node]) -> Option<(f64, f64, f64)> {
+pub(crate) fn sat_collision_test_with_mtv(
+    polygon1: &[Node],
+    polygon2: &[Node],
+) -> Option<(f64, f64, f64)> {
     if polygon1.len() < 3 || polygon2.len() < 3 {
         return None; // Not valid polygons
     }
@@ -497,6 +502,29 @@ impl LineCollisionManager {
         }
     }
 
+    /// Convert an index of `collisions` to a stacking level. The levels go 0, 1, -1, 2, -2...
+    /// Fails if the level does not fit in an `i16`, e.g. for a very large track index.
+    #[inline]
+    fn level(idx: usize) -> Result<i16> {
+        let half = i64::try_from(idx / 2).unwrap_or(i64::MAX);
+        let level = if idx % 2 == 1 { half + 1 } else { -half };
+        i16::try_from(level).map_err(|_| {
+            Diagnostic::error(
+                ErrorCode::LineStackingFailed,
+                format!("A line cannot be stacked {idx} places away from the station line"),
+            )
+            .into()
+        })
+    }
+
+    /// Occupy the segment on the given index regardless of existing segments, and return its
+    /// stacking level. This is used for segments that have a fixed place, e.g. station tracks.
+    pub fn occupy(&mut self, start: GraphLength, end: GraphLength, idx: usize) -> Result<i16> {
+        let level = Self::level(idx)?;
+        self.add_collision(start, end, idx);
+        Ok(level)
+    }
+
     pub fn resolve_collisions(&mut self, start: GraphLength, end: GraphLength) -> Result<i16> {
         self.resolve_collisions_impl(start, end, 255)
     }
@@ -508,22 +536,41 @@ impl LineCollisionManager {
     ) -> Result<i16> {
         // Remove the epsilon check since we're now storing actual line segments
         // Empty or point segments are valid in some cases
-        for idx in 0..=max_depth {
-            let level = if idx == 0 {
-                0
-            } else if idx % 2 == 1 {
-                (idx + 1) / 2
-            } else {
-                -(idx / 2)
-            };
-            if self.check_collision(start, end, idx as usize) {
+        for idx in 0..=max_depth as usize {
+            if self.check_collision(start, end, idx) {
                 continue;
             }
-            self.add_collision(start, end, idx as usize);
-            return Ok(level);
+            self.add_collision(start, end, idx);
+            return Self::level(idx);
         }
-        Err(
-            anyhow::anyhow! {"Reached maximum recursion depth ({max_depth}) when processing station line collisions"},
+        Err(Diagnostic::error(
+            ErrorCode::LineStackingFailed,
+            format!(
+                "Reached maximum recursion depth ({max_depth}) when processing station line collisions"
+            ),
         )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacking_levels() {
+        let levels: Vec<i16> = (0..5)
+            .map(|idx| LineCollisionManager::level(idx).unwrap())
+            .collect();
+        assert_eq!(levels, [0, 1, -1, 2, -2]);
+        assert_eq!(LineCollisionManager::level(65533).unwrap(), 32767);
+        let mut manager = LineCollisionManager::new();
+        let error = manager
+            .occupy(0.0.into(), 1.0.into(), u16::MAX as usize)
+            .unwrap_err();
+        assert_eq!(
+            crate::diagnostic::code_of(&error),
+            Some(ErrorCode::LineStackingFailed)
+        );
     }
 }
@@ -0,0 +1,185 @@
+use crate::types::Time;
+use serde::Serialize;
+use std::fmt;
+
+/// What went wrong. The names are stable, so that callers could match on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The document does not match the schema.
+    InvalidDocument,
+    /// The document is of a newer version than the plugin.
+    UnsupportedVersion,
+    /// Two different names have the same ID.
+    NameCollision,
+    DuplicateStation,
+    DuplicateTrain,
+    DuplicateInterval,
+    /// A train departs from a station before it arrives.
+    DepartureBeforeArrival,
+    /// A train arrives at a station before it departs from the previous one.
+    ArrivalBeforePreviousDeparture,
+    /// A train uses a track the station does not have.
+    UnknownTrack,
+    /// A calendar refers to a holiday set the network does not have.
+    UnknownHolidaySet,
+    /// A date range of a calendar ends before it begins.
+    InvalidDateRange,
+    /// A repeating train has a zero interval.
+    ZeroInterval,
+    /// The config does not list any stations to draw.
+    NoStationsToDraw,
+    /// A station to draw, or an end of an interval, is not in the network.
+    UnknownStation,
+    /// Two adjacent stations to draw have no interval between them.
+    MissingInterval,
+    /// The same station is listed twice in a row, or is both ends of two adjacent intervals.
+    InvalidStationOrder,
+    /// The diagram ends before it begins.
+    InvalidTimeRange,
+    /// A label could not be placed without overlapping others. Only a warning if the label was
+    /// dropped instead.
+    LabelPlacementFailed,
+    /// A line could not be stacked on a station line.
+    LineStackingFailed,
+    /// An imported file could not be read.
+    InvalidFile,
+    /// A name is used more than once in an imported file, and was renamed.
+    RenamedDuplicate,
+    /// A train of an imported file, or of an exported network, was skipped.
+    SkippedTrain,
+    /// A part of a train of an imported file was skipped.
+    SkippedEntry,
+    /// An imported file refers to something it does not have.
+    UnknownReference,
+    /// An interval of an imported file could not be measured, and got a default length.
+    UnmeasuredInterval,
+    /// The times of an imported train are approximate.
+    ApproximateTimes,
+    /// A feature of an imported file is not supported, and was ignored.
+    UnsupportedFeature,
+    /// A train was written in a simpler form than it has, since the format cannot express it.
+    SimplifiedSchedule,
+    /// A station has no coordinates.
+    MissingCoordinates,
+    /// Any other error.
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or a warning with the train, the station, and the schedule entry it is about.
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    pub severity: Severity,
+    pub message: String,
+    pub train: Option<String>,
+    pub station: Option<String>,
+    /// The index of the schedule entry of the train.
+    pub entry: Option<usize>,
+    /// The times at fault, e.g. the arrival and the departure that are out of order.
+    pub times: Vec<Time>,
+}
+
+impl Diagnostic {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            train: None,
+            station: None,
+            entry: None,
+            times: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    pub fn train(mut self, train: impl Into<String>) -> Self {
+        self.train = Some(train.into());
+        self
+    }
+
+    pub fn station(mut self, station: impl Into<String>) -> Self {
+        self.station = Some(station.into());
+        self
+    }
+
+    pub fn entry(mut self, entry: usize) -> Self {
+        self.entry = Some(entry);
+        self
+    }
+
+    pub fn times(mut self, times: impl IntoIterator<Item = Time>) -> Self {
+        self.times = times.into_iter().collect();
+        self
+    }
+
+    /// The diagnostic of an error. Errors that were not raised as diagnostics are `Other`. The
+    /// message is the whole chain of the error, from the outermost context.
+    pub fn of(error: &anyhow::Error) -> Self {
+        let message = error
+            .chain()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(": ");
+        let diagnostic = error
+            .downcast_ref::<Diagnostic>()
+            .cloned()
+            .unwrap_or_else(|| Diagnostic::error(ErrorCode::Other, ""));
+        Diagnostic {
+            message,
+            ..diagnostic
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// The code of the diagnostic of an error, if it was raised as one.
+pub(crate) fn code_of(error: &anyhow::Error) -> Option<ErrorCode> {
+    error
+        .downcast_ref::<Diagnostic>()
+        .map(|diagnostic| diagnostic.code)
+}
+
+/// Add the context to an error, as a diagnostic with the code if the error does not have one yet.
+pub(crate) fn with_default_code(
+    error: anyhow::Error,
+    code: ErrorCode,
+    context: &'static str,
+) -> anyhow::Error {
+    if code_of(&error).is_some() {
+        error.context(context)
+    } else {
+        error.context(Diagnostic::error(code, context))
+    }
+}
+
+/// Fill in the train of the diagnostic of an error, if it is not known yet.
+pub(crate) fn with_train(mut error: anyhow::Error, train: &str) -> anyhow::Error {
+    if let Some(diagnostic) = error.downcast_mut::<Diagnostic>()
+        && diagnostic.train.is_none()
+    {
+        diagnostic.train = Some(train.to_string());
+    }
+    error
+}
@@ -0,0 +1,149 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+/// A table read from a CSV file. The first row is the header.
+pub struct Table {
+    columns: HashMap<String, usize>,
+    rows: Vec<Vec<String>>,
+}
+
+/// A row of a `Table`.
+pub struct Row<'a> {
+    columns: &'a HashMap<String, usize>,
+    fields: &'a [String],
+    /// The line number of the row, counting the header as line 1.
+    pub line: usize,
+}
+
+impl Table {
+    /// Parse a table with the given delimiter. See `parse_records`.
+    pub fn parse(text: &str, delimiter: char) -> Result<Self> {
+        let mut records = parse_records(text, delimiter)?.into_iter();
+        let header = records.next().unwrap_or_default();
+        let columns = header
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| (name.trim().to_string(), idx))
+            .collect();
+        Ok(Self {
+            columns,
+            rows: records.collect(),
+        })
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        self.rows.iter().enumerate().map(|(idx, fields)| Row {
+            columns: &self.columns,
+            fields,
+            line: idx + 2,
+        })
+    }
+}
+
+impl<'a> Row<'a> {
+    /// Get the trimmed field of the column. Empty fields are treated as missing.
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let field = self.fields.get(*self.columns.get(column)?)?.trim();
+        (!field.is_empty()).then_some(field)
+    }
+
+    /// Get the field of the column, or fail if it is missing.
+    pub fn require(&self, column: &str) -> Result<&'a str> {
+        self.get(column)
+            .ok_or_else(|| anyhow!("Line {} does not have a value for '{}'", self.line, column))
+    }
+
+    /// Parse the field of the column if it is there.
+    pub fn parse<T: std::str::FromStr>(&self, column: &str) -> Result<Option<T>> {
+        self.get(column)
+            .map(|field| {
+                field.parse().map_err(|_| {
+                    anyhow!(
+                        "Line {} has an invalid value '{}' for '{}'",
+                        self.line,
+                        field,
+                        column
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Parse the records of a CSV file with the given delimiter. Fields could be quoted with `"`, and
+/// quotes in quoted fields are written as `""`. Blank lines are skipped.
+pub fn parse_records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            // skip blank lines
+            if !(record.len() == 1 && record[0].is_empty()) {
+                records.push(std::mem::take(&mut record));
+            } else {
+                record.clear();
+            }
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return Err(anyhow!("A quoted field is not closed"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Write rows as a CSV file. Fields are quoted when they need to be.
+pub fn write(header: &[&str], rows: &[Vec<String>]) -> String {
+    write_delimited(',', header, rows)
+}
+
+/// Write rows with the given delimiter. Fields are quoted when they need to be.
+pub fn write_delimited(delimiter: char, header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut content = String::new();
+    let mut write_row = |fields: &mut dyn Iterator<Item = &str>| {
+        for (idx, field) in fields.enumerate() {
+            if idx > 0 {
+                content.push(delimiter);
+            }
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                content.push('"');
+                content.push_str(&field.replace('"', "\"\""));
+                content.push('"');
+            } else {
+                content.push_str(field);
+            }
+        }
+        content.push_str("\r\n");
+    };
+    write_row(&mut header.iter().copied());
+    for row in rows {
+        write_row(&mut row.iter().map(String::as_str));
+    }
+    content
+}
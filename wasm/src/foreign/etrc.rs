@@ -0,0 +1,371 @@
+use super::*;
+use crate::chinese_railway_type::match_type;
+use anyhow::{Result, anyhow};
+use std::collections::{HashMap, HashSet};
+
+/// Separates the line from the trains, and the trains from each other.
+const TRAIN_SEPARATOR: &str = "===Train===";
+
+/// A station of the line, as `name,mileage,level`.
+struct EtrcStation<'a> {
+    name: &'a str,
+    /// Mileage in kilometers.
+    mileage: f64,
+    level: Option<i64>,
+}
+
+/// Read an ETRC (`.trc`) file. The file starts with the line, which is its name followed by one
+/// `name,mileage,level` row per station. Each train follows a `===Train===` row, with a
+/// `trf2,number,down number,up number` row, its origin, its destination, and one
+/// `station,arrival,departure` row per station. Files that are not UTF-8 are read as GBK.
+pub fn import(data: &[u8]) -> Result<Imported> {
+    let content = match std::str::from_utf8(data) {
+        Ok(content) => std::borrow::Cow::Borrowed(content),
+        Err(_) => {
+            let (content, _, had_errors) = encoding_rs::GBK.decode(data);
+            if had_errors {
+                return Err(anyhow!("The file is neither UTF-8 nor GBK"));
+            }
+            content
+        }
+    };
+    let mut warnings = Vec::new();
+
+    // the file ends at the first row starting with '-', which is followed by display settings
+    let mut rows = content
+        .lines()
+        .map(|row| row.trim().trim_start_matches('\u{feff}'))
+        .filter(|row| !row.is_empty())
+        .take_while(|row| !row.starts_with('-'));
+
+    // line
+    let mut line_name = None;
+    let mut line_stations: Vec<EtrcStation> = Vec::new();
+    let mut seen_stations = HashSet::new();
+    for (row_idx, row) in rows.by_ref().enumerate() {
+        if row == TRAIN_SEPARATOR {
+            break;
+        }
+        if row.starts_with("***") {
+            continue;
+        }
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        let [name, mileage, ref rest @ ..] = fields[..] else {
+            // the name of the line, followed by its length
+            line_name.get_or_insert(row);
+            continue;
+        };
+        let mileage: f64 = mileage
+            .parse()
+            .map_err(|_| anyhow!("Line {} has an invalid mileage '{}'", row_idx + 1, mileage))?;
+        if !seen_stations.insert(name) {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::DuplicateStation,
+                    format!(
+                        "Station '{name}' is on the line more than once, only the first is kept"
+                    ),
+                )
+                .station(name),
+            );
+            continue;
+        }
+        line_stations.push(EtrcStation {
+            name,
+            mileage,
+            level: rest.first().and_then(|level| level.parse().ok()),
+        });
+    }
+    if line_stations.is_empty() {
+        return Err(anyhow!("The file does not have any stations"));
+    }
+
+    let stations = line_stations
+        .iter()
+        .map(|station| {
+            let mut metadata = Metadata::new();
+            if let Some(level) = station.level {
+                metadata.insert("level".to_string(), ciborium::Value::Integer(level.into()));
+            }
+            if let Some(line_name) = line_name {
+                metadata.insert("line".to_string(), text(line_name));
+            }
+            (
+                station.name.to_string(),
+                StationHelper {
+                    label_size: Default::default(),
+                    tracks: None,
+                    metadata,
+                },
+            )
+        })
+        .collect();
+    let intervals = line_stations
+        .windows(2)
+        .map(|pair| {
+            (
+                (pair[0].name.to_string(), pair[1].name.to_string()),
+                IntervalHelper {
+                    length: IntervalLength::new(
+                        ((pair[1].mileage - pair[0].mileage).abs() * 1000.0).round() as u32,
+                    ),
+                    bidirectional: Some(true),
+                },
+            )
+        })
+        .collect();
+
+    // trains
+    let mut blocks: Vec<Vec<&str>> = vec![Vec::new()];
+    for row in rows {
+        if row == TRAIN_SEPARATOR {
+            blocks.push(Vec::new());
+        } else {
+            blocks.last_mut().unwrap().push(row);
+        }
+    }
+    let mut seen_trains = HashSet::new();
+    let mut trains = Vec::new();
+    for block in blocks {
+        let [header, origin, destination, entries @ ..] = &block[..] else {
+            if !block.is_empty() {
+                warnings.push(Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!("The train '{}' is incomplete and is skipped", block[0]),
+                ));
+            }
+            continue;
+        };
+        let numbers: Vec<&str> = header.split(',').map(str::trim).collect();
+        let Some(&full_number) = numbers.get(1).filter(|number| !number.is_empty()) else {
+            warnings.push(Diagnostic::warning(
+                ErrorCode::SkippedTrain,
+                format!("The train '{header}' does not have a number and is skipped"),
+            ));
+            continue;
+        };
+        let name = unique_name(full_number, &mut seen_trains, &mut warnings);
+        let schedule = make_schedule(&name, entries, &seen_stations, &mut warnings)?;
+        if schedule.is_empty() {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!(
+                        "Train '{name}' does not stop at any station of the line and is skipped"
+                    ),
+                )
+                .train(&name),
+            );
+            continue;
+        }
+
+        let mut metadata = Metadata::new();
+        metadata.insert("type".to_string(), text(match_type(full_number)));
+        for (key, number) in [("down", numbers.get(2)), ("up", numbers.get(3))] {
+            if let Some(number) = number.filter(|number| !number.is_empty()) {
+                metadata.insert(key.to_string(), text(*number));
+            }
+        }
+        if let Some(circuit) = numbers
+            .get(4)
+            .filter(|circuit| !matches!(**circuit, "" | "NA"))
+        {
+            metadata.insert("circuit".to_string(), text(*circuit));
+        }
+        metadata.insert("origin".to_string(), text(*origin));
+        metadata.insert("destination".to_string(), text(*destination));
+        trains.push((
+            name,
+            TrainHelper {
+                frequency: None,
+                calendar: None,
+                label_size: Default::default(),
+                priority: None,
+                schedule,
+                metadata,
+            },
+        ));
+    }
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Read the `station,arrival,departure` rows of a train. Stations that are not on the line are
+/// skipped, and a day is added whenever a time goes backwards.
+fn make_schedule(
+    train_name: &str,
+    entries: &[&str],
+    line_stations: &HashSet<&str>,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Vec<ScheduleEntryHelper>> {
+    let mut unwrapper = DayUnwrapper::default();
+    let mut schedule = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let fields: Vec<&str> = entry.split(',').map(str::trim).collect();
+        let [station, arrival, departure, ..] = fields[..] else {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedEntry,
+                    format!(
+                        "Train '{train_name}' has an invalid row '{entry}', the row is skipped"
+                    ),
+                )
+                .train(train_name),
+            );
+            continue;
+        };
+        if !line_stations.contains(station) {
+            warnings.push(Diagnostic::warning(ErrorCode::UnknownStation, format!(
+                "Train '{train_name}' stops at '{station}', which is not on the line, the stop \
+                 is skipped"
+            )).train(train_name).station(station));
+            continue;
+        }
+        let parse = |time: &str| -> Result<Time> {
+            time.parse().map_err(|_| {
+                anyhow!(
+                    "Train '{}' has an invalid time '{}' at '{}'",
+                    train_name,
+                    time,
+                    station
+                )
+            })
+        };
+        let arrival = unwrapper.unwrap(parse(arrival)?);
+        let departure = unwrapper.unwrap(parse(departure)?);
+        schedule.push(ScheduleEntryHelper {
+            arrival,
+            departure,
+            station: station.to_string(),
+            track: None,
+            kind: Some(if arrival == departure {
+                EntryKind::Pass
+            } else {
+                EntryKind::Stop
+            }),
+        });
+    }
+    // the first and the last stations are always stops
+    if let Some(first) = schedule.first_mut() {
+        first.kind = Some(EntryKind::Stop);
+    }
+    if let Some(last) = schedule.last_mut() {
+        last.kind = Some(EntryKind::Stop);
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const FILE: &str = "***Circuit***
+京沪线
+300
+北京南,0,1,false
+廊坊,60,2
+天津南,120.5,1
+济南西,300,1
+===Train===
+trf2,G1,G1,G2,NA
+北京南
+济南西
+北京南,23:50:00,23:50:00
+廊坊,00:05,00:05
+天津南,00:20,00:22
+上海,01:00,01:00
+济南西,01:30,01:30
+===Train===
+trf2,K101,K101,K102,C1
+北京南
+天津南
+北京南,08:00,08:00
+天津南,09:00,09:00
+===Train===
+trf2
+-----
+color
+";
+
+    #[test]
+    fn import_line_and_trains() {
+        let imported = import(FILE.as_bytes()).unwrap();
+        // 上海 is not on the line, and the last train is incomplete
+        assert_eq!(
+            codes(&imported),
+            [ErrorCode::UnknownStation, ErrorCode::SkippedTrain]
+        );
+        assert_eq!(
+            station_names(&imported),
+            ["北京南", "廊坊", "天津南", "济南西"]
+        );
+        let network = &imported.network;
+        assert_eq!(
+            network.stations[0]
+                .1
+                .metadata
+                .get("line")
+                .and_then(|line| line.as_text()),
+            Some("京沪线")
+        );
+        let lengths: Vec<u32> = network
+            .intervals
+            .iter()
+            .map(|(_, interval)| interval.length.meters())
+            .collect();
+        assert_eq!(lengths, [60000, 60500, 179500]);
+
+        // seconds may be left out, and the times run on past midnight
+        let (name, high_speed) = &network.trains[0];
+        assert_eq!(name, "G1");
+        let times: Vec<(Time, Time)> = high_speed
+            .schedule
+            .iter()
+            .map(|entry| (entry.arrival, entry.departure))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (hm(23, 50), hm(23, 50)),
+                (hm(24, 5), hm(24, 5)),
+                (hm(24, 20), hm(24, 22)),
+                (hm(25, 30), hm(25, 30)),
+            ]
+        );
+        assert!(matches!(high_speed.schedule[1].kind, Some(EntryKind::Pass)));
+        assert!(matches!(high_speed.schedule[3].kind, Some(EntryKind::Stop)));
+        assert!(!high_speed.metadata.contains_key("circuit"));
+
+        let (name, express) = &network.trains[1];
+        assert_eq!(name, "K101");
+        assert_eq!(
+            express
+                .metadata
+                .get("circuit")
+                .and_then(|circuit| circuit.as_text()),
+            Some("C1")
+        );
+        assert_eq!(
+            express.metadata.get("up").and_then(|up| up.as_text()),
+            Some("K102")
+        );
+    }
+
+    #[test]
+    fn import_gbk() {
+        let (data, _, _) = encoding_rs::GBK.encode(FILE);
+        let imported = import(&data).unwrap();
+        assert_eq!(imported.network.stations[0].0, "北京南");
+        assert_eq!(imported.network.trains.len(), 2);
+    }
+}
@@ -0,0 +1,772 @@
+use super::csv::{Row, Table};
+use super::*;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+/// Used when an interval has neither shape distances nor stop coordinates.
+const DEFAULT_INTERVAL_LENGTH: u32 = 1000;
+
+/// Options of the GTFS importer.
+#[derive(Deserialize)]
+pub struct GtfsConfig {
+    /// Only import these routes, given by their IDs or short names. All routes are imported if
+    /// empty.
+    #[serde(default)]
+    routes: Vec<String>,
+    /// Meters per unit of `shape_dist_traveled`. Guessed from the stop coordinates if `None`.
+    shape_dist_scale: Option<f64>,
+}
+
+/// A station made of GTFS stops. Stops that belong to a parent station are its tracks.
+struct GtfsStation {
+    name: String,
+    tracks: Vec<TrackHelper>,
+    coordinates: Option<(f64, f64)>,
+    metadata: Metadata,
+}
+
+/// Where a stop is, as the index of its station and the index of its track.
+#[derive(Clone, Copy)]
+struct StopLocation {
+    station: usize,
+    track: Option<u16>,
+}
+
+/// A stop time of a trip, with the stop already resolved.
+struct StopTime {
+    sequence: u32,
+    location: StopLocation,
+    arrival: Option<Time>,
+    departure: Option<Time>,
+    shape_dist: Option<f64>,
+    no_service: bool,
+}
+
+/// Read a GTFS feed from the bytes of its zip file.
+pub fn import(data: &[u8], config: GtfsConfig) -> Result<Imported> {
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(data)).context("Failed to open the zip file")?;
+    let mut read_table = |file_name: &str, required: bool| -> Result<Option<Table>> {
+        // feeds are sometimes zipped with their folder
+        let Some(path) = archive
+            .file_names()
+            .find(|path| *path == file_name || path.ends_with(&format!("/{file_name}")))
+            .map(str::to_string)
+        else {
+            return if required {
+                Err(anyhow!("The feed does not have {}", file_name))
+            } else {
+                Ok(None)
+            };
+        };
+        let mut content = String::new();
+        archive
+            .by_name(&path)?
+            .read_to_string(&mut content)
+            .with_context(|| format!("Failed to read {file_name}"))?;
+        let table =
+            Table::parse(&content, ',').with_context(|| format!("Failed to parse {file_name}"))?;
+        Ok(Some(table))
+    };
+    let stops = read_table("stops.txt", true)?.unwrap();
+    let routes = read_table("routes.txt", true)?.unwrap();
+    let trips = read_table("trips.txt", true)?.unwrap();
+    let stop_times = read_table("stop_times.txt", true)?.unwrap();
+    let calendar = read_table("calendar.txt", false)?;
+    let calendar_dates = read_table("calendar_dates.txt", false)?;
+    let frequencies = read_table("frequencies.txt", false)?;
+    let mut warnings = Vec::new();
+
+    // stops and stations
+    let (mut gtfs_stations, stop_locations) =
+        read_stops(&stops, &mut warnings).context("Failed to read stops.txt")?;
+
+    // routes
+    let mut route_metadata: HashMap<&str, Metadata> = HashMap::new();
+    for row in routes.rows() {
+        let route_id = row.require("route_id")?;
+        let short_name = row.get("route_short_name");
+        if !config.routes.is_empty()
+            && !config
+                .routes
+                .iter()
+                .any(|route| route == route_id || Some(route.as_str()) == short_name)
+        {
+            continue;
+        }
+        let mut metadata = Metadata::new();
+        if let Some(name) = short_name.or(row.get("route_long_name")) {
+            metadata.insert("route".to_string(), text(name));
+        }
+        if let Some(route_type) = row.parse::<i64>("route_type")? {
+            metadata.insert(
+                "route_type".to_string(),
+                ciborium::Value::Integer(route_type.into()),
+            );
+        }
+        if let Some(color) = row.get("route_color") {
+            metadata.insert("color".to_string(), text(format!("#{color}")));
+        }
+        route_metadata.insert(route_id, metadata);
+    }
+    if route_metadata.is_empty() {
+        return Err(anyhow!("None of the routes are imported"));
+    }
+
+    // calendars
+    let mut calendars: HashMap<&str, CalendarHelper> = HashMap::new();
+    if let Some(calendar) = &calendar {
+        for row in calendar.rows() {
+            let mut weekdays = 0;
+            for (bit, day) in [
+                "monday",
+                "tuesday",
+                "wednesday",
+                "thursday",
+                "friday",
+                "saturday",
+                "sunday",
+            ]
+            .iter()
+            .enumerate()
+            {
+                if row.get(day) == Some("1") {
+                    weekdays |= 1 << bit;
+                }
+            }
+            let range_start: Date = row.require("start_date")?.parse()?;
+            let range_end: Date = row.require("end_date")?.parse()?;
+            calendars.insert(
+                row.require("service_id")?,
+                CalendarHelper {
+                    weekdays: Some(weekdays),
+                    date_ranges: vec![(range_start, range_end)],
+                    include: Vec::new(),
+                    exclude: Vec::new(),
+                    holidays: Vec::new(),
+                },
+            );
+        }
+    }
+    if let Some(calendar_dates) = &calendar_dates {
+        for row in calendar_dates.rows() {
+            // services that only have exceptions do not run on any other day
+            let calendar = calendars
+                .entry(row.require("service_id")?)
+                .or_insert_with(|| CalendarHelper {
+                    weekdays: Some(0),
+                    date_ranges: Vec::new(),
+                    include: Vec::new(),
+                    exclude: Vec::new(),
+                    holidays: Vec::new(),
+                });
+            let date: Date = row.require("date")?.parse()?;
+            match row.require("exception_type")? {
+                "1" => calendar.include.push(date),
+                "2" => calendar.exclude.push(date),
+                other => warnings.push(Diagnostic::warning(
+                    ErrorCode::UnsupportedFeature,
+                    format!(
+                        "calendar_dates.txt line {} has an unknown exception type '{}'",
+                        row.line, other
+                    ),
+                )),
+            }
+        }
+    }
+
+    // frequencies
+    let mut trip_frequencies: HashMap<&str, Vec<(Time, Time, Time)>> = HashMap::new();
+    if let Some(frequencies) = &frequencies {
+        for row in frequencies.rows() {
+            let start_time: Time = row.require("start_time")?.parse()?;
+            let end_time: Time = row.require("end_time")?.parse()?;
+            let headway: i32 = row
+                .parse("headway_secs")?
+                .ok_or_else(|| anyhow!("frequencies.txt line {} has no headway", row.line))?;
+            if headway <= 0 || end_time <= start_time {
+                warnings.push(Diagnostic::warning(
+                    ErrorCode::SkippedEntry,
+                    format!(
+                        "frequencies.txt line {} has an empty or invalid period and is skipped",
+                        row.line
+                    ),
+                ));
+                continue;
+            }
+            trip_frequencies
+                .entry(row.require("trip_id")?)
+                .or_default()
+                .push((start_time, end_time, Time::new(headway)));
+        }
+    }
+
+    // stop times of the imported trips
+    let mut imported_trips: HashMap<&str, Row> = HashMap::new();
+    let mut trip_order: Vec<&str> = Vec::new();
+    for row in trips.rows() {
+        if route_metadata.contains_key(row.require("route_id")?) {
+            let trip_id = row.require("trip_id")?;
+            imported_trips.insert(trip_id, row);
+            trip_order.push(trip_id);
+        }
+    }
+    let mut trip_stop_times: HashMap<&str, Vec<StopTime>> = HashMap::new();
+    for row in stop_times.rows() {
+        let trip_id = row.require("trip_id")?;
+        if !imported_trips.contains_key(trip_id) {
+            continue;
+        }
+        let stop_id = row.require("stop_id")?;
+        let Some(&location) = stop_locations.get(stop_id) else {
+            return Err(anyhow!(
+                "stop_times.txt line {} refers to the unknown stop '{}'",
+                row.line,
+                stop_id
+            ));
+        };
+        let no_service =
+            row.get("pickup_type") == Some("1") && row.get("drop_off_type") == Some("1");
+        trip_stop_times.entry(trip_id).or_default().push(StopTime {
+            sequence: row
+                .parse("stop_sequence")?
+                .ok_or_else(|| anyhow!("stop_times.txt line {} has no stop sequence", row.line))?,
+            location,
+            arrival: row.parse("arrival_time")?,
+            departure: row.parse("departure_time")?,
+            shape_dist: row.parse("shape_dist_traveled")?,
+            no_service,
+        });
+    }
+
+    for stop_times in trip_stop_times.values_mut() {
+        stop_times.sort_by_key(|stop_time| stop_time.sequence);
+    }
+
+    // intervals are measured from every pair of neighbouring stops
+    let shape_dist_scale = match config.shape_dist_scale {
+        Some(scale) => scale,
+        None => guess_shape_dist_scale(&trip_stop_times, &gtfs_stations),
+    };
+    let mut interval_lengths: BTreeMap<(usize, usize), u32> = BTreeMap::new();
+    let mut unmeasured = false;
+    for stop_times in trip_stop_times.values() {
+        for pair in stop_times.windows(2) {
+            let [from, to] = pair else { continue };
+            let (from_station, to_station) = (from.location.station, to.location.station);
+            if from_station == to_station {
+                continue;
+            }
+            let length = match (from.shape_dist, to.shape_dist) {
+                (Some(from_dist), Some(to_dist)) if to_dist > from_dist => {
+                    Some((to_dist - from_dist) * shape_dist_scale)
+                }
+                _ => match (
+                    gtfs_stations[from_station].coordinates,
+                    gtfs_stations[to_station].coordinates,
+                ) {
+                    (Some(from_coordinates), Some(to_coordinates)) => {
+                        Some(haversine(from_coordinates, to_coordinates))
+                    }
+                    _ => None,
+                },
+            };
+            let length = length
+                .map(|length| length.round() as u32)
+                .unwrap_or_else(|| {
+                    unmeasured = true;
+                    DEFAULT_INTERVAL_LENGTH
+                });
+            interval_lengths
+                .entry((from_station, to_station))
+                .and_modify(|known| *known = (*known).min(length))
+                .or_insert(length);
+        }
+    }
+    if unmeasured {
+        warnings.push(Diagnostic::warning(ErrorCode::UnmeasuredInterval, format!(
+            "Some intervals have neither shape distances nor stop coordinates, they are {DEFAULT_INTERVAL_LENGTH} meters long"
+        )));
+    }
+
+    // trains
+    let mut seen_trains = HashSet::new();
+    let mut station_order: Vec<usize> = Vec::new();
+    let mut station_used = vec![false; gtfs_stations.len()];
+    let mut trains: Vec<(String, TrainHelper)> = Vec::new();
+    for trip_id in trip_order {
+        let row = &imported_trips[trip_id];
+        let Some(stop_times) = trip_stop_times.get(trip_id) else {
+            continue;
+        };
+        let mut schedule = Vec::with_capacity(stop_times.len());
+        let mut previous_departure: Option<Time> = None;
+        let mut backwards = false;
+        for stop_time in stop_times {
+            // stops without times are interpolated when drawn
+            let (arrival, departure) = match (stop_time.arrival, stop_time.departure) {
+                (Some(arrival), Some(departure)) => (arrival, departure),
+                (Some(time), None) | (None, Some(time)) => (time, time),
+                (None, None) => continue,
+            };
+            if previous_departure.is_some_and(|previous_departure| arrival < previous_departure) {
+                backwards = true;
+                break;
+            }
+            previous_departure = Some(departure);
+            let station = stop_time.location.station;
+            let kind = match (stop_time.no_service, arrival == departure) {
+                (false, _) => EntryKind::Stop,
+                (true, true) => EntryKind::Pass,
+                (true, false) => EntryKind::TechnicalStop,
+            };
+            schedule.push(ScheduleEntryHelper {
+                arrival,
+                departure,
+                station: gtfs_stations[station].name.clone(),
+                track: stop_time.location.track.map(TrackRef::Index),
+                kind: Some(kind),
+            });
+        }
+        if backwards {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!("The stop times of trip '{trip_id}' go backwards, the trip is skipped"),
+                )
+                .train(trip_id),
+            );
+            continue;
+        }
+        if schedule.is_empty() {
+            continue;
+        }
+        for stop_time in stop_times {
+            let station = stop_time.location.station;
+            if !station_used[station] {
+                station_used[station] = true;
+                station_order.push(station);
+            }
+        }
+
+        let mut metadata = route_metadata[row.require("route_id")?].clone();
+        metadata.insert("trip_id".to_string(), text(trip_id));
+        if let Some(headsign) = row.get("trip_headsign") {
+            metadata.insert("headsign".to_string(), text(headsign));
+        }
+        if let Some(direction) = row.parse::<i64>("direction_id")? {
+            metadata.insert(
+                "direction".to_string(),
+                ciborium::Value::Integer(direction.into()),
+            );
+        }
+        let calendar = row
+            .get("service_id")
+            .and_then(|service_id| calendars.get(service_id));
+        let name = row.get("trip_short_name").unwrap_or(trip_id);
+
+        // each period of frequencies.txt becomes a train that repeats within the period
+        let periods = trip_frequencies.get(trip_id).map_or(&[][..], Vec::as_slice);
+        if periods.is_empty() {
+            trains.push((
+                unique_name(name, &mut seen_trains, &mut warnings),
+                TrainHelper {
+                    frequency: None,
+                    calendar: calendar.cloned(),
+                    label_size: Default::default(),
+                    priority: None,
+                    schedule,
+                    metadata,
+                },
+            ));
+            continue;
+        }
+        let template_start = schedule[0].arrival;
+        for &(start_time, end_time, headway) in periods {
+            let period_name = if periods.len() == 1 {
+                name.to_string()
+            } else {
+                format!("{name} from {start_time}")
+            };
+            let shift = start_time - template_start;
+            trains.push((
+                unique_name(&period_name, &mut seen_trains, &mut warnings),
+                TrainHelper {
+                    frequency: Some(TrainFrequency::Repeating(Repetition {
+                        interval: headway,
+                        first: Some(start_time),
+                        // trips do not start at the end time itself
+                        last: Some(end_time - Time::new(1)),
+                        except: Vec::new(),
+                    })),
+                    calendar: calendar.cloned(),
+                    label_size: Default::default(),
+                    priority: None,
+                    schedule: schedule
+                        .iter()
+                        .map(|entry| ScheduleEntryHelper {
+                            arrival: entry.arrival + shift,
+                            departure: entry.departure + shift,
+                            ..entry.clone()
+                        })
+                        .collect(),
+                    metadata: metadata.clone(),
+                },
+            ));
+        }
+    }
+
+    let intervals = interval_lengths
+        .into_iter()
+        .filter(|((from, to), _)| station_used[*from] && station_used[*to])
+        .map(|((from, to), length)| {
+            (
+                (
+                    gtfs_stations[from].name.clone(),
+                    gtfs_stations[to].name.clone(),
+                ),
+                IntervalHelper {
+                    length: IntervalLength::new(length),
+                    bidirectional: Some(false),
+                },
+            )
+        })
+        .collect();
+    let stations = station_order
+        .into_iter()
+        .map(|station| {
+            let station = &mut gtfs_stations[station];
+            (
+                station.name.clone(),
+                StationHelper {
+                    label_size: Default::default(),
+                    tracks: (!station.tracks.is_empty())
+                        .then(|| TracksHelper::List(std::mem::take(&mut station.tracks))),
+                    metadata: std::mem::take(&mut station.metadata),
+                },
+            )
+        })
+        .collect();
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Turn stops into stations. Stops with a parent station become tracks of the parent, and
+/// other stops become stations of their own.
+fn read_stops<'a>(
+    stops: &'a Table,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(Vec<GtfsStation>, HashMap<&'a str, StopLocation>)> {
+    let mut seen_names = HashSet::new();
+    let mut stations: Vec<GtfsStation> = Vec::new();
+    let mut locations: HashMap<&str, StopLocation> = HashMap::new();
+
+    // parent stations first, so that their platforms could refer to them
+    let mut parent_stations: HashMap<&str, usize> = HashMap::new();
+    for row in stops.rows() {
+        if row.get("location_type") == Some("1") {
+            stations.push(make_station(&row, &mut seen_names, warnings)?);
+            parent_stations.insert(row.require("stop_id")?, stations.len() - 1);
+        }
+    }
+    for row in stops.rows() {
+        let stop_id = row.require("stop_id")?;
+        match row.get("location_type").unwrap_or("0") {
+            // stops and platforms
+            "0" => {}
+            "1" => {
+                let station = parent_stations[stop_id];
+                locations.insert(
+                    stop_id,
+                    StopLocation {
+                        station,
+                        track: None,
+                    },
+                );
+                continue;
+            }
+            // entrances, nodes, and boarding areas are not visited by trips
+            _ => continue,
+        }
+        let parent = row.get("parent_station");
+        let location =
+            match parent.and_then(|parent| parent_stations.get(parent)) {
+                Some(&station_idx) => {
+                    let station = &mut stations[station_idx];
+                    let track_name = row
+                        .get("platform_code")
+                        .or(row.get("stop_name"))
+                        .unwrap_or(stop_id);
+                    if station.coordinates.is_none() {
+                        station.coordinates = coordinates(&row)?;
+                    }
+                    station.tracks.push(TrackHelper {
+                        name: track_name.to_string(),
+                        main: None,
+                    });
+                    StopLocation {
+                        station: station_idx,
+                        track: Some((station.tracks.len() - 1) as u16),
+                    }
+                }
+                None => {
+                    if let Some(parent) = parent {
+                        warnings.push(Diagnostic::warning(ErrorCode::UnknownReference, format!(
+                        "Stop '{stop_id}' refers to the unknown parent station '{parent}'"
+                    )).station(stop_id));
+                    }
+                    stations.push(make_station(&row, &mut seen_names, warnings)?);
+                    StopLocation {
+                        station: stations.len() - 1,
+                        track: None,
+                    }
+                }
+            };
+        locations.insert(stop_id, location);
+    }
+    Ok((stations, locations))
+}
+
+fn make_station(
+    row: &Row,
+    seen_names: &mut HashSet<String>,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<GtfsStation> {
+    let stop_id = row.require("stop_id")?;
+    let name = row.get("stop_name").unwrap_or(stop_id);
+    let mut metadata = Metadata::new();
+    metadata.insert("stop_id".to_string(), text(stop_id));
+    let coordinates = coordinates(row)?;
+    if let Some((lat, lon)) = coordinates {
+        metadata.insert("lat".to_string(), ciborium::Value::Float(lat));
+        metadata.insert("lon".to_string(), ciborium::Value::Float(lon));
+    }
+    Ok(GtfsStation {
+        name: unique_name(name, seen_names, warnings),
+        tracks: Vec::new(),
+        coordinates,
+        metadata,
+    })
+}
+
+fn coordinates(row: &Row) -> Result<Option<(f64, f64)>> {
+    Ok(match (row.parse("stop_lat")?, row.parse("stop_lon")?) {
+        (Some(lat), Some(lon)) => Some((lat, lon)),
+        _ => None,
+    })
+}
+
+/// Guess whether shape distances are in meters or kilometers by comparing them with the
+/// distances between stop coordinates. The stop times of each trip must be sorted by their
+/// sequence.
+fn guess_shape_dist_scale(
+    trip_stop_times: &HashMap<&str, Vec<StopTime>>,
+    stations: &[GtfsStation],
+) -> f64 {
+    let mut ratios: Vec<f64> = Vec::new();
+    for stop_times in trip_stop_times.values() {
+        for pair in stop_times.windows(2) {
+            let [from, to] = pair else { continue };
+            if let (Some(from_dist), Some(to_dist)) = (from.shape_dist, to.shape_dist)
+                && let (Some(from_coordinates), Some(to_coordinates)) = (
+                    stations[from.location.station].coordinates,
+                    stations[to.location.station].coordinates,
+                )
+                && (to_dist - from_dist).abs() > 0.0
+            {
+                ratios.push(
+                    haversine(from_coordinates, to_coordinates) / (to_dist - from_dist).abs(),
+                );
+            }
+        }
+    }
+    if ratios.is_empty() {
+        return 1.0;
+    }
+    ratios.sort_by(f64::total_cmp);
+    // the boundary between meters and kilometers, on a logarithmic scale
+    if ratios[ratios.len() / 2] > 1000f64.sqrt() {
+        1000.0
+    } else {
+        1.0
+    }
+}
+
+/// The distance in meters between two coordinates given in degrees.
+fn haversine((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+    use std::io::Write;
+
+    const FEED: &[(&str, &str)] = &[
+        (
+            "stops.txt",
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station,platform_code
+A,Alpha,35.00,139.0,1,,
+A1,Alpha 1,35.00,139.0,0,A,1
+A2,Alpha 2,35.00,139.0,0,A,2
+B,Beta,35.01,139.0,,,
+C,Gamma,35.02,139.0,,,
+",
+        ),
+        (
+            "routes.txt",
+            "route_id,route_short_name,route_type,route_color
+R,Blue,2,0000FF
+",
+        ),
+        (
+            "trips.txt",
+            "route_id,service_id,trip_id,trip_short_name,direction_id
+R,WK,T1,Night,0
+R,SAT,T2,,0
+",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,stop_sequence,arrival_time,departure_time,stop_id,shape_dist_traveled
+T1,3,24:20:00,24:20:00,C,2.2
+T1,1,23:50:00,23:55:00,A2,0
+T1,2,24:05:00,24:06:00,B,1.1
+T2,1,08:00:00,08:00:00,A1,
+T2,2,08:10:00,08:11:00,B,
+T2,3,08:20:00,08:20:00,C,
+",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date
+WK,1,1,1,1,1,0,0,20260101,20261231
+",
+        ),
+        (
+            "calendar_dates.txt",
+            "service_id,date,exception_type
+WK,20260105,2
+SAT,20260103,1
+",
+        ),
+        (
+            "frequencies.txt",
+            "trip_id,start_time,end_time,headway_secs
+T2,08:00:00,10:00:00,1800
+",
+        ),
+    ];
+
+    fn zip_feed(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in files {
+            // feeds are sometimes zipped with their folder
+            writer
+                .start_file(
+                    format!("feed/{name}"),
+                    zip::write::SimpleFileOptions::default(),
+                )
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn config() -> GtfsConfig {
+        GtfsConfig {
+            routes: Vec::new(),
+            shape_dist_scale: None,
+        }
+    }
+
+    #[test]
+    fn import_feed() {
+        let imported = import(&zip_feed(FEED), config()).unwrap();
+        assert!(imported.warnings.is_empty());
+        assert_eq!(station_names(&imported), ["Alpha", "Beta", "Gamma"]);
+        let network = &imported.network;
+        let tracks: Vec<&str> = match &network.stations[0].1.tracks {
+            Some(TracksHelper::List(tracks)) => {
+                tracks.iter().map(|track| track.name.as_str()).collect()
+            }
+            _ => panic!("Alpha should have a list of tracks"),
+        };
+        assert_eq!(tracks, ["1", "2"]);
+
+        // shape distances are in kilometers, and are preferred over the stop coordinates
+        assert_eq!(interval_length(&imported, "Alpha", "Beta"), 1100);
+
+        // stop times are sorted by their sequence, and are past 24:00 on the next day
+        let (name, night) = &network.trains[0];
+        assert_eq!(name, "Night");
+        let stations: Vec<&str> = night
+            .schedule
+            .iter()
+            .map(|entry| entry.station.as_str())
+            .collect();
+        assert_eq!(stations, ["Alpha", "Beta", "Gamma"]);
+        assert!(matches!(night.schedule[0].track, Some(TrackRef::Index(1))));
+        assert_eq!(night.schedule[2].arrival, hm(24, 20));
+        assert!(night.frequency.is_none());
+        let weekdays = night.calendar.as_ref().unwrap();
+        assert_eq!(weekdays.weekdays, Some(0b11111));
+        assert_eq!(weekdays.exclude, [Date::from_ymd(2026, 1, 5).unwrap()]);
+
+        // a service that only has exceptions runs on those dates alone
+        let (name, repeating) = &network.trains[1];
+        assert_eq!(name, "T2");
+        let saturday = repeating.calendar.as_ref().unwrap();
+        assert_eq!(saturday.weekdays, Some(0));
+        assert_eq!(saturday.include, [Date::from_ymd(2026, 1, 3).unwrap()]);
+        match &repeating.frequency {
+            Some(TrainFrequency::Repeating(repetition)) => {
+                assert_eq!(repetition.interval, Time::new(1800));
+                assert_eq!(repetition.first, Some(hm(8, 0)));
+                assert_eq!(repetition.last, Some(hm(10, 0) - Time::new(1)));
+            }
+            _ => panic!("T2 should repeat"),
+        }
+    }
+
+    #[test]
+    fn import_selected_routes() {
+        let imported = import(
+            &zip_feed(FEED),
+            GtfsConfig {
+                routes: vec!["Red".to_string()],
+                shape_dist_scale: None,
+            },
+        );
+        assert!(imported.is_err());
+        let imported = import(
+            &zip_feed(FEED),
+            GtfsConfig {
+                routes: vec!["Blue".to_string()],
+                shape_dist_scale: Some(1.0),
+            },
+        )
+        .unwrap();
+        assert_eq!(imported.network.trains.len(), 2);
+        // the shape distances are taken as meters
+        let (_, alpha_beta) = &imported.network.intervals[0];
+        assert_eq!(alpha_beta.length.meters(), 1);
+    }
+}
@@ -0,0 +1,885 @@
+use super::csv;
+use crate::diagnostic::*;
+use crate::input::*;
+use crate::types::*;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Write;
+
+const DAY: i32 = 86400;
+/// Meters per degree of latitude, and of longitude at the equator.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Options of the GTFS exporter.
+#[derive(Deserialize)]
+pub struct GtfsExportConfig {
+    #[serde(default = "default_agency_name")]
+    agency_name: String,
+    #[serde(default = "default_agency_url")]
+    agency_url: String,
+    /// The timezone of all times in the feed, as a tz database name.
+    #[serde(default = "default_agency_timezone")]
+    agency_timezone: String,
+    /// The dates covered by calendars without date ranges. Defaults to the years 2000 to 2099.
+    date_range: Option<(Date, Date)>,
+    /// Write every instance of repeating trains as its own trip instead of using
+    /// frequencies.txt.
+    #[serde(default)]
+    expand_frequencies: bool,
+    /// Place stations without `lat` and `lon` metadata along the equator instead of failing.
+    #[serde(default)]
+    guess_coordinates: bool,
+}
+
+fn default_agency_name() -> String {
+    "Paiagram".to_string()
+}
+
+fn default_agency_url() -> String {
+    "https://example.com".to_string()
+}
+
+fn default_agency_timezone() -> String {
+    "Etc/UTC".to_string()
+}
+
+/// A zipped GTFS feed, and the problems found while writing it.
+#[derive(Serialize)]
+pub struct Exported {
+    pub feed: Bytes,
+    /// Parts of the network that could not be exported and were skipped or changed.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Bytes that are serialized as a byte string instead of an array of integers.
+pub struct Bytes(pub Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+/// The stops written for a station. Stations with tracks are written as a parent station with
+/// one platform per track.
+struct Stop {
+    id: String,
+    platforms: Vec<String>,
+    /// The platform used by entries without a track.
+    default_platform: Option<usize>,
+}
+
+/// An instance of a train, as the offset from its schedule.
+enum Instance {
+    Trip(Time),
+    /// Trips that start every `headway` until before `end`. The first trip is the schedule
+    /// shifted by `offset`.
+    Frequency {
+        offset: Time,
+        end: Time,
+        headway: Time,
+    },
+}
+
+/// The rows of a service in calendar.txt and calendar_dates.txt.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Service {
+    weekdays: u8,
+    start: Date,
+    end: Date,
+    /// Dates that are added (`true`) or removed (`false`).
+    exceptions: BTreeMap<Date, bool>,
+}
+
+/// Write the network as a zipped GTFS feed.
+pub fn export(network: &Network, config: GtfsExportConfig) -> Result<Exported> {
+    let mut warnings = Vec::new();
+    let date_range = config.date_range.unwrap_or((
+        Date::from_ymd(2000, 1, 1).unwrap(),
+        Date::from_ymd(2099, 12, 31).unwrap(),
+    ));
+
+    let mut trains: Vec<(&TrainID, &Train)> = network.trains.iter().collect();
+    trains.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+
+    // stations that are only referred to by schedules are written too
+    let mut station_ids: Vec<StationID> = network.stations.keys().copied().collect();
+    let mut known: HashSet<StationID> = station_ids.iter().copied().collect();
+    for (_, train) in &trains {
+        for entry in train.schedule.values() {
+            if known.insert(entry.station) {
+                station_ids.push(entry.station);
+            }
+        }
+    }
+    let station_name = |id: &StationID| -> String {
+        network.stations.get(id).map_or_else(
+            || {
+                network
+                    .names
+                    .get(*id)
+                    .map_or_else(|| id.to_string(), str::to_string)
+            },
+            |station| station.name.clone(),
+        )
+    };
+    station_ids.sort_by_cached_key(station_name);
+
+    // stops
+    let coordinates = locate_stations(
+        network,
+        &station_ids,
+        config.guess_coordinates,
+        &mut warnings,
+    )?;
+    let mut stops: HashMap<StationID, Stop> = HashMap::new();
+    let mut stop_ids = HashSet::new();
+    let mut stop_rows = Vec::new();
+    for station_id in &station_ids {
+        let station = network.stations.get(station_id);
+        let name = station_name(station_id);
+        let id = station
+            .and_then(|station| metadata_text(&station.metadata, "stop_id"))
+            .unwrap_or(&name);
+        let id = unique_id(id, &mut stop_ids);
+        let (lat, lon) = coordinates[station_id];
+        let (lat, lon) = (format!("{lat:.6}"), format!("{lon:.6}"));
+        let tracks = station.map_or(&[][..], |station| station.tracks.as_slice());
+        if tracks.is_empty() {
+            stop_rows.push(vec![
+                id.clone(),
+                name,
+                lat,
+                lon,
+                "0".into(),
+                "".into(),
+                "".into(),
+            ]);
+            stops.insert(
+                *station_id,
+                Stop {
+                    id,
+                    platforms: Vec::new(),
+                    default_platform: None,
+                },
+            );
+            continue;
+        }
+        stop_rows.push(vec![
+            id.clone(),
+            name.clone(),
+            lat.clone(),
+            lon.clone(),
+            "1".into(),
+            "".into(),
+            "".into(),
+        ]);
+        let platforms: Vec<String> = tracks
+            .iter()
+            .enumerate()
+            .map(|(idx, track)| {
+                let platform_id = unique_id(&format!("{id}:{}", idx + 1), &mut stop_ids);
+                stop_rows.push(vec![
+                    platform_id.clone(),
+                    name.clone(),
+                    lat.clone(),
+                    lon.clone(),
+                    "0".into(),
+                    id.clone(),
+                    track.name.clone(),
+                ]);
+                platform_id
+            })
+            .collect();
+        stops.insert(
+            *station_id,
+            Stop {
+                id,
+                platforms,
+                default_platform: Some(tracks.iter().position(|track| track.main).unwrap_or(0)),
+            },
+        );
+    }
+
+    // routes, grouped by the route or the type of the trains
+    let mut routes: BTreeMap<String, String> = BTreeMap::new();
+    let mut route_rows = Vec::new();
+    let mut train_routes = Vec::with_capacity(trains.len());
+    for (_, train) in &trains {
+        let key = metadata_text(&train.metadata, "route")
+            .or_else(|| metadata_text(&train.metadata, "type"))
+            .unwrap_or("Default")
+            .to_string();
+        let next_id = format!("R{}", routes.len() + 1);
+        let route_id = routes.entry(key.clone()).or_insert_with(|| {
+            let route_type = train
+                .metadata
+                .get("route_type")
+                .and_then(|value| value.as_integer())
+                .and_then(|value| i64::try_from(value).ok())
+                .unwrap_or(2);
+            let color = metadata_text(&train.metadata, "color")
+                .and_then(|color| color.strip_prefix('#'))
+                .filter(|color| color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()))
+                .map(str::to_uppercase);
+            route_rows.push(vec![
+                next_id.clone(),
+                "A1".into(),
+                key,
+                route_type.to_string(),
+                color.unwrap_or_default(),
+            ]);
+            next_id
+        });
+        train_routes.push(route_id.clone());
+    }
+
+    // trips, and the services they run on
+    let mut services: HashMap<Service, String> = HashMap::new();
+    let mut trip_ids = HashSet::new();
+    let mut trip_rows = Vec::new();
+    let mut stop_time_rows = Vec::new();
+    let mut frequency_rows = Vec::new();
+    for ((_, train), route_id) in trains.iter().zip(&train_routes) {
+        if train.schedule.len() < 2 {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!(
+                        "Train '{}' has fewer than two stops and is skipped",
+                        train.name
+                    ),
+                )
+                .train(&train.name),
+            );
+            continue;
+        }
+        let service = Service::new(train.calendar.as_ref(), date_range);
+        let next_id = format!("S{}", services.len() + 1);
+        let service_id = services.entry(service).or_insert(next_id).clone();
+        let instances = instances(train, config.expand_frequencies, &mut warnings);
+        let multiple = instances.len() > 1;
+        for instance in instances {
+            let offset = match instance {
+                Instance::Trip(offset) | Instance::Frequency { offset, .. } => offset,
+            };
+            let start = *train.schedule.first_key_value().unwrap().0 + offset;
+            let trip_id = if multiple {
+                unique_id(
+                    &format!("{} {}", train.name, format_time(start)),
+                    &mut trip_ids,
+                )
+            } else {
+                unique_id(&train.name, &mut trip_ids)
+            };
+            trip_rows.push(vec![
+                route_id.clone(),
+                service_id.clone(),
+                trip_id.clone(),
+                train.name.clone(),
+            ]);
+            for (sequence, (arrival, entry)) in train.schedule.iter().enumerate() {
+                let stop = &stops[&entry.station];
+                let stop_id = entry
+                    .track
+                    .map(usize::from)
+                    .or(stop.default_platform)
+                    .and_then(|track| stop.platforms.get(track))
+                    .or(stop.platforms.first())
+                    .unwrap_or(&stop.id);
+                // passengers cannot board or alight where the train does not stop for them
+                let boarding = if entry.kind == EntryKind::Stop {
+                    "0"
+                } else {
+                    "1"
+                };
+                stop_time_rows.push(vec![
+                    trip_id.clone(),
+                    format_time(*arrival + offset),
+                    format_time(entry.departure + offset),
+                    stop_id.clone(),
+                    (sequence + 1).to_string(),
+                    boarding.into(),
+                    boarding.into(),
+                ]);
+            }
+            if let Instance::Frequency { end, headway, .. } = instance {
+                frequency_rows.push(vec![
+                    trip_id,
+                    format_time(start),
+                    format_time(end),
+                    headway.seconds().to_string(),
+                    "1".into(),
+                ]);
+            }
+        }
+    }
+
+    // calendars
+    let mut services: Vec<(Service, String)> = services.into_iter().collect();
+    services.sort_by_key(|(_, id)| id[1..].parse::<usize>().unwrap_or_default());
+    let mut calendar_rows = Vec::new();
+    let mut calendar_date_rows = Vec::new();
+    for (service, id) in &services {
+        let mut row = vec![id.clone()];
+        row.extend((0..7).map(|day| ((service.weekdays >> day) & 1).to_string()));
+        row.push(format_date(service.start));
+        row.push(format_date(service.end));
+        calendar_rows.push(row);
+        for (date, added) in &service.exceptions {
+            calendar_date_rows.push(vec![
+                id.clone(),
+                format_date(*date),
+                if *added { "1" } else { "2" }.into(),
+            ]);
+        }
+    }
+
+    let agency_rows = vec![vec![
+        "A1".into(),
+        config.agency_name,
+        config.agency_url,
+        config.agency_timezone,
+    ]];
+    let mut files = vec![
+        (
+            "agency.txt",
+            csv::write(
+                &["agency_id", "agency_name", "agency_url", "agency_timezone"],
+                &agency_rows,
+            ),
+        ),
+        (
+            "stops.txt",
+            csv::write(
+                &[
+                    "stop_id",
+                    "stop_name",
+                    "stop_lat",
+                    "stop_lon",
+                    "location_type",
+                    "parent_station",
+                    "platform_code",
+                ],
+                &stop_rows,
+            ),
+        ),
+        (
+            "routes.txt",
+            csv::write(
+                &[
+                    "route_id",
+                    "agency_id",
+                    "route_short_name",
+                    "route_type",
+                    "route_color",
+                ],
+                &route_rows,
+            ),
+        ),
+        (
+            "trips.txt",
+            csv::write(
+                &["route_id", "service_id", "trip_id", "trip_short_name"],
+                &trip_rows,
+            ),
+        ),
+        (
+            "stop_times.txt",
+            csv::write(
+                &[
+                    "trip_id",
+                    "arrival_time",
+                    "departure_time",
+                    "stop_id",
+                    "stop_sequence",
+                    "pickup_type",
+                    "drop_off_type",
+                ],
+                &stop_time_rows,
+            ),
+        ),
+        (
+            "calendar.txt",
+            csv::write(
+                &[
+                    "service_id",
+                    "monday",
+                    "tuesday",
+                    "wednesday",
+                    "thursday",
+                    "friday",
+                    "saturday",
+                    "sunday",
+                    "start_date",
+                    "end_date",
+                ],
+                &calendar_rows,
+            ),
+        ),
+    ];
+    if !calendar_date_rows.is_empty() {
+        files.push((
+            "calendar_dates.txt",
+            csv::write(
+                &["service_id", "date", "exception_type"],
+                &calendar_date_rows,
+            ),
+        ));
+    }
+    if !frequency_rows.is_empty() {
+        files.push((
+            "frequencies.txt",
+            csv::write(
+                &[
+                    "trip_id",
+                    "start_time",
+                    "end_time",
+                    "headway_secs",
+                    "exact_times",
+                ],
+                &frequency_rows,
+            ),
+        ));
+    }
+
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for (file_name, content) in files {
+        archive
+            .start_file(file_name, options)
+            .and_then(|_| Ok(archive.write_all(content.as_bytes())?))
+            .with_context(|| format!("Failed to write {file_name}"))?;
+    }
+    let feed = archive
+        .finish()
+        .context("Failed to finish the zip file")?
+        .into_inner();
+    Ok(Exported {
+        feed: Bytes(feed),
+        warnings,
+    })
+}
+
+impl Service {
+    /// Describe the calendar as a weekly pattern within a date range, plus exceptions on dates
+    /// where the pattern is wrong. Trains without a calendar run daily within `date_range`.
+    fn new(calendar: Option<&Calendar>, date_range: (Date, Date)) -> Self {
+        let Some(calendar) = calendar else {
+            return Service {
+                weekdays: 0x7f,
+                start: date_range.0,
+                end: date_range.1,
+                exceptions: BTreeMap::new(),
+            };
+        };
+        let (start, end) = if calendar.date_ranges.is_empty() {
+            date_range
+        } else {
+            calendar.date_ranges[1..]
+                .iter()
+                .fold(calendar.date_ranges[0], |(start, end), range| {
+                    (start.min(range.0), end.max(range.1))
+                })
+        };
+        let weekdays = calendar.weekdays & 0x7f;
+        // the pattern could only be wrong on special dates, and between date ranges
+        let mut dates: BTreeSet<Date> = calendar
+            .include
+            .iter()
+            .chain(&calendar.exclude)
+            .chain(&calendar.holidays)
+            .copied()
+            .collect();
+        if calendar.date_ranges.len() > 1 {
+            let mut date = start;
+            while date <= end {
+                dates.insert(date);
+                date = date.add_days(1);
+            }
+        }
+        let exceptions = dates
+            .into_iter()
+            .filter_map(|date| {
+                let pattern =
+                    (start..=end).contains(&date) && weekdays & (1 << date.weekday().index()) != 0;
+                let runs = calendar.runs_on(OperatingDay::Date(date));
+                (pattern != runs).then_some((date, runs))
+            })
+            .collect();
+        Service {
+            weekdays,
+            start,
+            end,
+            exceptions,
+        }
+    }
+}
+
+/// Get the instances of a train. Instances on later days are moved back to the day the schedule
+/// starts on, as they belong to later operating days anyway.
+fn instances(train: &Train, expand: bool, warnings: &mut Vec<Diagnostic>) -> Vec<Instance> {
+    let schedule_start = *train.schedule.first_key_value().unwrap().0;
+    let first_day = schedule_start.seconds().div_euclid(DAY).max(0) * DAY;
+    let into_day = |start: Time| Time::new(start.seconds().rem_euclid(DAY) + first_day);
+    match &train.frequency {
+        TrainFrequency::Fixed(times) => {
+            let offsets: BTreeSet<Time> = times
+                .iter()
+                .map(|time| into_day(*time) - schedule_start)
+                .collect();
+            offsets.into_iter().map(Instance::Trip).collect()
+        }
+        TrainFrequency::Repeating(repetition) => {
+            let interval = repetition.interval.seconds().abs();
+            if interval == 0 {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::SimplifiedSchedule,
+                        format!(
+                            "Train '{}' repeats every 0 seconds and is written once",
+                            train.name
+                        ),
+                    )
+                    .train(&train.name),
+                );
+                return vec![Instance::Trip(into_day(schedule_start) - schedule_start)];
+            }
+            if interval > DAY {
+                warnings.push(Diagnostic::warning(ErrorCode::SimplifiedSchedule, format!(
+                    "Train '{}' repeats less often than daily and is written as a daily train",
+                    train.name
+                )).train(&train.name));
+            }
+            // unbounded repetitions run all day
+            let lower = repetition.first.map_or(first_day, |first| first.seconds());
+            let upper = repetition
+                .last
+                .map_or(first_day + DAY - 1, |last| last.seconds());
+            let first = lower + (schedule_start.seconds() - lower).rem_euclid(interval);
+            let starts: Vec<Time> = (first..=upper)
+                .step_by(interval as usize)
+                .map(Time::new)
+                .filter(|start| !repetition.except.contains(start))
+                .collect();
+            let (Some(&first), Some(&last)) = (starts.first(), starts.last()) else {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::SkippedTrain,
+                        format!("Train '{}' has no instances and is skipped", train.name),
+                    )
+                    .train(&train.name),
+                );
+                return Vec::new();
+            };
+            if starts.len() > 1 && !expand && repetition.except.is_empty() {
+                // keep the window together, with the first instance within the first day
+                let shift = into_day(first) - first;
+                return vec![Instance::Frequency {
+                    offset: first + shift - schedule_start,
+                    end: last + shift + Time::new(interval),
+                    headway: Time::new(interval),
+                }];
+            }
+            let offsets: BTreeSet<Time> = starts
+                .into_iter()
+                .map(|start| into_day(start) - schedule_start)
+                .collect();
+            offsets.into_iter().map(Instance::Trip).collect()
+        }
+    }
+}
+
+/// Read the coordinates of the stations. If `guess` is set, stations without coordinates are
+/// placed along the equator, using the lengths of the intervals from stations that are already
+/// placed; otherwise they are an error.
+fn locate_stations(
+    network: &Network,
+    station_ids: &[StationID],
+    guess: bool,
+    warnings: &mut Vec<Diagnostic>,
+) -> anyhow::Result<HashMap<StationID, (f64, f64)>> {
+    let mut coordinates: HashMap<StationID, (f64, f64)> = station_ids
+        .iter()
+        .filter_map(|id| {
+            let metadata = &network.stations.get(id)?.metadata;
+            Some((
+                *id,
+                (
+                    metadata_number(metadata, "lat")?,
+                    metadata_number(metadata, "lon")?,
+                ),
+            ))
+        })
+        .collect();
+    if coordinates.len() == station_ids.len() {
+        return Ok(coordinates);
+    }
+    if !guess {
+        let missing: Vec<String> = station_ids
+            .iter()
+            .filter(|id| !coordinates.contains_key(id))
+            .map(|id| {
+                network
+                    .stations
+                    .get(id)
+                    .map_or_else(|| id.to_string(), |station| station.name.clone())
+            })
+            .collect();
+        return Err(Diagnostic::error(
+            ErrorCode::MissingCoordinates,
+            format!(
+                "Stations {} do not have `lat` and `lon` metadata, set `guess_coordinates` to place them along the equator",
+                missing.join(", ")
+            ),
+        )
+        .into());
+    }
+    warnings.push(Diagnostic::warning(
+        ErrorCode::MissingCoordinates,
+        format!(
+            "{} stations do not have coordinates, they are placed along the equator",
+            station_ids.len() - coordinates.len()
+        ),
+    ));
+    let mut neighbours: HashMap<StationID, Vec<(StationID, u32)>> = HashMap::new();
+    for ((from, to), interval) in &network.intervals {
+        neighbours
+            .entry(*from)
+            .or_default()
+            .push((*to, interval.length.meters()));
+        neighbours
+            .entry(*to)
+            .or_default()
+            .push((*from, interval.length.meters()));
+    }
+    let mut queue: VecDeque<StationID> = station_ids
+        .iter()
+        .filter(|id| coordinates.contains_key(id))
+        .copied()
+        .collect();
+    // networks without any coordinates are spread one degree apart
+    let mut next_origin = 0.0;
+    for start in station_ids {
+        if queue.is_empty() && !coordinates.contains_key(start) {
+            coordinates.insert(*start, (0.0, next_origin));
+            next_origin += 1.0;
+            queue.push_back(*start);
+        }
+        while let Some(station) = queue.pop_front() {
+            let (lat, lon) = coordinates[&station];
+            for (neighbour, length) in neighbours.get(&station).map_or(&[][..], Vec::as_slice) {
+                if coordinates.contains_key(neighbour) {
+                    continue;
+                }
+                let lon = lon + *length as f64 / (METERS_PER_DEGREE * lat.to_radians().cos());
+                next_origin = f64::max(next_origin, lon + 1.0);
+                coordinates.insert(*neighbour, (lat, lon));
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    Ok(coordinates)
+}
+
+fn metadata_text<'a>(metadata: &'a Metadata, key: &str) -> Option<&'a str> {
+    metadata.get(key)?.as_text()
+}
+
+fn metadata_number(metadata: &Metadata, key: &str) -> Option<f64> {
+    match metadata.get(key)? {
+        ciborium::Value::Float(value) => Some(*value),
+        ciborium::Value::Integer(value) => i64::try_from(*value).ok().map(|value| value as f64),
+        ciborium::Value::Text(value) => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Make the ID unique by adding a number after it.
+fn unique_id(id: &str, seen: &mut HashSet<String>) -> String {
+    let mut unique = id.to_string();
+    let mut suffix = 2;
+    while !seen.insert(unique.clone()) {
+        unique = format!("{id}_{suffix}");
+        suffix += 1;
+    }
+    unique
+}
+
+/// Format the time as `HH:MM:SS`. Hours go beyond 24 for times after midnight.
+fn format_time(time: Time) -> String {
+    let seconds = time.seconds();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn format_date(date: Date) -> String {
+    let (year, month, day) = date.ymd();
+    format!("{year:04}{month:02}{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CalendarBuilder, NetworkBuilder, StationBuilder, TrainBuilder};
+    use crate::foreign::test_util::*;
+    use crate::foreign::{Imported, gtfs};
+
+    fn config(guess_coordinates: bool) -> GtfsExportConfig {
+        GtfsExportConfig {
+            agency_name: default_agency_name(),
+            agency_url: default_agency_url(),
+            agency_timezone: default_agency_timezone(),
+            date_range: None,
+            expand_frequencies: false,
+            guess_coordinates,
+        }
+    }
+
+    fn station(lat: f64, lon: f64) -> StationBuilder {
+        StationBuilder::new()
+            .metadata("lat", ciborium::Value::Float(lat))
+            .metadata("lon", ciborium::Value::Float(lon))
+    }
+
+    fn network(with_coordinates: bool) -> Network {
+        let first = Date::from_ymd(2026, 1, 1).unwrap();
+        let last = Date::from_ymd(2026, 12, 31).unwrap();
+        let gamma = if with_coordinates {
+            station(35.02, 139.0)
+        } else {
+            StationBuilder::new()
+        };
+        NetworkBuilder::new()
+            .station(
+                "Alpha",
+                station(35.0, 139.0).tracks([("1".to_string(), true), ("2".to_string(), false)]),
+            )
+            .station("Beta", station(35.01, 139.0))
+            .station("Gamma", gamma)
+            .interval("Alpha", "Beta", IntervalLength::new(1100), true)
+            .interval("Beta", "Gamma", IntervalLength::new(1200), true)
+            .train(
+                "Local",
+                TrainBuilder::new()
+                    .stop("Alpha", hm(8, 0), hm(8, 0))
+                    .track(1)
+                    .stop("Beta", hm(8, 5), hm(8, 6))
+                    .stop("Gamma", hm(8, 10), hm(8, 10))
+                    .frequency(TrainFrequency::Repeating(Repetition {
+                        interval: Time::new(1800),
+                        first: Some(hm(8, 0)),
+                        last: Some(hm(10, 0)),
+                        except: Vec::new(),
+                    }))
+                    .calendar(
+                        CalendarBuilder::new()
+                            .weekdays(
+                                [
+                                    Weekday::Monday,
+                                    Weekday::Tuesday,
+                                    Weekday::Wednesday,
+                                    Weekday::Thursday,
+                                    Weekday::Friday,
+                                ],
+                                false,
+                            )
+                            .date_range(first, last)
+                            .exclude(Date::from_ymd(2026, 1, 5).unwrap()),
+                    ),
+            )
+            .train(
+                "Night",
+                TrainBuilder::new()
+                    .stop("Gamma", hm(23, 50), hm(23, 50))
+                    .stop("Alpha", hm(24, 10), hm(24, 10)),
+            )
+            .build()
+            .unwrap()
+    }
+
+    fn reimport(feed: &[u8]) -> Imported {
+        let config = ciborium::Value::Map(Vec::new()).deserialized().unwrap();
+        gtfs::import(feed, config).unwrap()
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let exported = export(&network(true), config(false)).unwrap();
+        let imported = reimport(&exported.feed.0);
+        let network = &imported.network;
+
+        let mut stations: Vec<&str> = network
+            .stations
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        stations.sort_unstable();
+        assert_eq!(stations, ["Alpha", "Beta", "Gamma"]);
+        let (_, alpha) = network
+            .stations
+            .iter()
+            .find(|(name, _)| name == "Alpha")
+            .unwrap();
+        let tracks: Vec<&str> = match &alpha.tracks {
+            Some(TracksHelper::List(tracks)) => {
+                tracks.iter().map(|track| track.name.as_str()).collect()
+            }
+            _ => panic!("Alpha should have a list of tracks"),
+        };
+        assert_eq!(tracks, ["1", "2"]);
+
+        let names: Vec<&str> = network
+            .trains
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["Local", "Night"]);
+
+        // the repeating train is written to frequencies.txt, ending after its last instance
+        let local = &network.trains[0].1;
+        assert_eq!(local.schedule.len(), 3);
+        assert!(matches!(local.schedule[0].track, Some(TrackRef::Index(1))));
+        assert_eq!(local.schedule[1].arrival, hm(8, 5));
+        assert_eq!(local.schedule[1].departure, hm(8, 6));
+        match &local.frequency {
+            Some(TrainFrequency::Repeating(repetition)) => {
+                assert_eq!(repetition.interval, Time::new(1800));
+                assert_eq!(repetition.first, Some(hm(8, 0)));
+                assert!(repetition.last.unwrap() >= hm(10, 0));
+                assert!(repetition.last.unwrap() < hm(10, 30));
+            }
+            _ => panic!("Local should repeat"),
+        }
+        // the excluded date is written to calendar_dates.txt
+        let calendar = local.calendar.as_ref().unwrap();
+        assert_eq!(calendar.weekdays, Some(0b11111));
+        assert_eq!(calendar.exclude, [Date::from_ymd(2026, 1, 5).unwrap()]);
+
+        // times past midnight stay on the same trip
+        let night = &network.trains[1].1;
+        assert!(night.frequency.is_none());
+        assert_eq!(night.schedule[1].arrival, hm(24, 10));
+    }
+
+    #[test]
+    fn export_without_coordinates() {
+        let error = export(&network(false), config(false)).err().unwrap();
+        assert_eq!(Diagnostic::of(&error).code, ErrorCode::MissingCoordinates);
+
+        let exported = export(&network(false), config(true)).unwrap();
+        let codes: Vec<ErrorCode> = exported
+            .warnings
+            .iter()
+            .map(|warning| warning.code)
+            .collect();
+        assert_eq!(codes, [ErrorCode::MissingCoordinates]);
+        assert_eq!(reimport(&exported.feed.0).network.stations.len(), 3);
+    }
+}
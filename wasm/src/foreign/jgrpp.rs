@@ -0,0 +1,704 @@
+use super::*;
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Used by most games.
+const DEFAULT_TICKS_PER_MINUTE: i64 = 74;
+/// Used when one of the stations of an interval has no location.
+const DEFAULT_INTERVAL_LENGTH: u32 = 1000;
+
+/// An orders export of a vehicle.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct JgrppVehicle {
+    /// The name of the vehicle. The vehicles are numbered if `None`.
+    #[serde(alias = "vehicle-name", alias = "source")]
+    name: Option<String>,
+    game_properties: Option<GameProperties>,
+    #[serde(default)]
+    orders: Vec<JgrppOrder>,
+    /// Scheduled dispatch schedules.
+    #[serde(default)]
+    schedules: Vec<DispatchSchedule>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct GameProperties {
+    ticks_per_minute: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct JgrppOrder {
+    #[serde(rename = "type")]
+    order_type: String,
+    destination_id: Option<i64>,
+    destination_name: Option<String>,
+    destination_location: Option<Location>,
+    /// Ticks to travel to the destination.
+    travel_time: Option<i64>,
+    /// Ticks to wait at the destination.
+    wait_time: Option<i64>,
+    /// E.g. `go-via` for stations that are passed.
+    non_stop: Option<String>,
+    load: Option<String>,
+    depot_action: Option<String>,
+    /// The condition of a conditional order.
+    #[serde(alias = "condition-variable")]
+    condition: Option<String>,
+    /// The order a conditional order jumps to.
+    #[serde(alias = "jump-to-order")]
+    jump_to: Option<usize>,
+    /// The dispatch schedule used when departing from the destination.
+    #[serde(alias = "dispatch-index", alias = "scheduled-dispatch-index")]
+    schedule_index: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct Location {
+    #[serde(rename = "X")]
+    x: i64,
+    #[serde(rename = "Y")]
+    y: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DispatchSchedule {
+    name: Option<String>,
+    /// Departure offsets within the schedule, in ticks.
+    slots: Vec<Slot>,
+    /// Ticks before the schedule repeats. A day if `None`.
+    duration: Option<i64>,
+    /// Ticks a vehicle could depart late and still use a slot.
+    #[serde(default)]
+    max_delay: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Slot {
+    Offset(i64),
+    Detailed { offset: i64 },
+}
+
+/// How the length of an interval is found from the locations of its stations.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceFunction {
+    #[default]
+    Manhattan,
+    Euclidean,
+}
+
+/// Options of the JGRPP importer.
+#[derive(Deserialize)]
+pub struct JgrppConfig {
+    #[serde(default)]
+    distance: DistanceFunction,
+    /// Meters per tile.
+    #[serde(default = "default_tile_length")]
+    tile_length: f64,
+    /// Lengths in meters that are used instead of the distance function, e.g. for curvy lines.
+    #[serde(default)]
+    lengths: Vec<((String, String), u32)>,
+    /// Used instead of the ticks per minute of the exports.
+    ticks_per_minute: Option<i64>,
+}
+
+fn default_tile_length() -> f64 {
+    64.0
+}
+
+/// A station or waypoint visited by a vehicle, with the times in ticks.
+struct Visit<'a> {
+    order: &'a JgrppOrder,
+    name: String,
+    travel: i64,
+    wait: i64,
+    kind: EntryKind,
+}
+
+/// Read the orders exports of OpenTTD JGRPP vehicles. The orders of each vehicle are followed
+/// from the first order until they loop. Every order with a dispatch schedule starts a train,
+/// which runs until the next order with a dispatch schedule, and the slots of the schedule are
+/// turned into the instances of the train. Vehicles without dispatch schedules repeat after
+/// their whole timetable.
+pub fn import(vehicles: Vec<JgrppVehicle>, config: JgrppConfig) -> Result<Imported> {
+    let mut warnings = Vec::new();
+    let mut station_locations: Vec<(String, Option<Location>)> = Vec::new();
+    let mut seen_stations = HashSet::new();
+    let mut intervals: Vec<((String, String), IntervalHelper)> = Vec::new();
+    let mut interval_pairs = HashSet::new();
+    let mut missing_locations = 0;
+    let lengths: HashMap<(&str, &str), u32> = config
+        .lengths
+        .iter()
+        .flat_map(|((from, to), length)| {
+            [
+                ((from.as_str(), to.as_str()), *length),
+                ((to.as_str(), from.as_str()), *length),
+            ]
+        })
+        .collect();
+    let mut seen_trains = HashSet::new();
+    let mut trains = Vec::new();
+
+    for (vehicle_idx, vehicle) in vehicles.iter().enumerate() {
+        let vehicle_name = vehicle
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Vehicle {}", vehicle_idx + 1));
+        let ticks_per_minute = config
+            .ticks_per_minute
+            .or(vehicle
+                .game_properties
+                .as_ref()
+                .and_then(|properties| properties.ticks_per_minute))
+            .unwrap_or(DEFAULT_TICKS_PER_MINUTE);
+        if ticks_per_minute <= 0 {
+            return Err(anyhow!(
+                "Vehicle '{}' has an invalid number of ticks per minute {}",
+                vehicle_name,
+                ticks_per_minute
+            ));
+        }
+        let to_time = |ticks: i64| Time::new((ticks * 60 / ticks_per_minute) as i32);
+
+        let visits = follow_orders(&vehicle_name, &vehicle.orders, &mut warnings);
+        if visits.len() < 2 {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!(
+                        "Vehicle '{vehicle_name}' visits fewer than two stations and is skipped"
+                    ),
+                )
+                .train(&vehicle_name),
+            );
+            continue;
+        }
+
+        // stations and intervals
+        for (idx, visit) in visits.iter().enumerate() {
+            if seen_stations.insert(visit.name.clone()) {
+                station_locations.push((visit.name.clone(), visit.order.destination_location));
+            }
+            let previous = &visits[(idx + visits.len() - 1) % visits.len()];
+            if previous.name == visit.name {
+                continue;
+            }
+            let pair = if previous.name < visit.name {
+                (previous.name.clone(), visit.name.clone())
+            } else {
+                (visit.name.clone(), previous.name.clone())
+            };
+            if !interval_pairs.insert(pair) {
+                continue;
+            }
+            let length = match (
+                lengths.get(&(previous.name.as_str(), visit.name.as_str())),
+                previous.order.destination_location,
+                visit.order.destination_location,
+            ) {
+                (Some(length), ..) => *length,
+                (None, Some(from), Some(to)) => {
+                    (distance(config.distance, from, to) * config.tile_length).round() as u32
+                }
+                _ => {
+                    missing_locations += 1;
+                    DEFAULT_INTERVAL_LENGTH
+                }
+            };
+            intervals.push((
+                (previous.name.clone(), visit.name.clone()),
+                IntervalHelper {
+                    length: IntervalLength::new(length),
+                    bidirectional: Some(true),
+                },
+            ));
+        }
+
+        // each dispatching visit starts a train
+        let mut dispatches: Vec<usize> = visits
+            .iter()
+            .enumerate()
+            .filter(|(_, visit)| visit.order.schedule_index.is_some())
+            .map(|(idx, _)| idx)
+            .collect();
+        let mut default_schedule = None;
+        if dispatches.is_empty() && vehicle.schedules.len() == 1 {
+            // older exports do not say which order dispatches
+            dispatches.push(0);
+            default_schedule = Some(0);
+        }
+        if dispatches.is_empty() {
+            let schedule = make_schedule(&visits, 0, visits.len(), to_time);
+            let cycle = schedule
+                .last()
+                .map_or(Time::new(0), |entry| entry.departure);
+            if cycle <= Time::new(0) {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::ApproximateTimes,
+                        format!(
+                            "Vehicle '{vehicle_name}' does not have a timetable and is drawn once"
+                        ),
+                    )
+                    .train(&vehicle_name),
+                );
+            }
+            trains.push((
+                unique_name(&vehicle_name, &mut seen_trains, &mut warnings),
+                TrainHelper {
+                    frequency: (cycle > Time::new(0))
+                        .then(|| TrainFrequency::Repeating(Repetition::from(cycle))),
+                    calendar: None,
+                    label_size: Default::default(),
+                    priority: None,
+                    schedule,
+                    metadata: Metadata::new(),
+                },
+            ));
+            continue;
+        }
+        for (dispatch_idx, &start) in dispatches.iter().enumerate() {
+            let end = dispatches
+                .get(dispatch_idx + 1)
+                .copied()
+                .unwrap_or(dispatches[0] + visits.len());
+            let schedule_idx = visits[start]
+                .order
+                .schedule_index
+                .or(default_schedule)
+                .unwrap_or_default();
+            let Some(dispatch) = vehicle.schedules.get(schedule_idx) else {
+                warnings.push(Diagnostic::warning(ErrorCode::UnknownReference, format!(
+                    "Vehicle '{vehicle_name}' refers to the unknown dispatch schedule {schedule_idx}, \
+                     the departures from '{}' are skipped",
+                    visits[start].name
+                )).train(&vehicle_name));
+                continue;
+            };
+            let name = if dispatches.len() == 1 {
+                vehicle_name.clone()
+            } else {
+                format!("{vehicle_name} from {}", visits[start].name)
+            };
+            let Some(frequency) = dispatch_frequency(dispatch, ticks_per_minute, to_time) else {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::SkippedTrain,
+                        format!(
+                            "Train '{name}' has a dispatch schedule without slots and is skipped"
+                        ),
+                    )
+                    .train(&name),
+                );
+                continue;
+            };
+            let mut schedule = make_schedule(&visits, start, end, to_time);
+            // repeating instances are identified by their first departure
+            if let TrainFrequency::Repeating(_) = frequency {
+                let first_slot = dispatch.slots.iter().map(Slot::offset).min().unwrap_or(0);
+                for entry in &mut schedule {
+                    entry.arrival += to_time(first_slot);
+                    entry.departure += to_time(first_slot);
+                }
+            }
+            let mut metadata = Metadata::new();
+            metadata.insert("vehicle".to_string(), text(vehicle_name.clone()));
+            if let Some(schedule_name) = &dispatch.name {
+                metadata.insert("dispatch_schedule".to_string(), text(schedule_name));
+            }
+            if dispatch.max_delay > 0 {
+                metadata.insert(
+                    "max_delay".to_string(),
+                    ciborium::Value::Integer(to_time(dispatch.max_delay).seconds().into()),
+                );
+            }
+            trains.push((
+                unique_name(&name, &mut seen_trains, &mut warnings),
+                TrainHelper {
+                    frequency: Some(frequency),
+                    calendar: None,
+                    label_size: Default::default(),
+                    priority: None,
+                    schedule,
+                    metadata,
+                },
+            ));
+        }
+    }
+    if missing_locations > 0 {
+        warnings.push(Diagnostic::warning(
+            ErrorCode::UnmeasuredInterval,
+            format!(
+                "{missing_locations} intervals have a station without location, they are \
+             {DEFAULT_INTERVAL_LENGTH} m long"
+            ),
+        ));
+    }
+
+    let stations = station_locations
+        .into_iter()
+        .map(|(name, location)| {
+            let mut metadata = Metadata::new();
+            if let Some(location) = location {
+                metadata.insert("x".to_string(), ciborium::Value::Integer(location.x.into()));
+                metadata.insert("y".to_string(), ciborium::Value::Integer(location.y.into()));
+            }
+            (
+                name,
+                StationHelper {
+                    label_size: Default::default(),
+                    tracks: None,
+                    metadata,
+                },
+            )
+        })
+        .collect();
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Follow the orders from the first order until they loop, and return the loop. Depots are
+/// skipped, but the time spent going to them is kept. Conditional orders are only followed if
+/// they always jump, as the other conditions depend on the state of the game.
+fn follow_orders<'a>(
+    vehicle_name: &str,
+    orders: &'a [JgrppOrder],
+    warnings: &mut Vec<Diagnostic>,
+) -> Vec<Visit<'a>> {
+    let mut visits = Vec::new();
+    // the index of the first visit after each order, to find where the loop starts
+    let mut visited: HashMap<usize, usize> = HashMap::new();
+    let mut pending_travel = 0;
+    let mut order_idx = 0;
+    let mut missing_times = false;
+    let mut approximate = false;
+    let mut skipped_conditions = false;
+    // each order is visited at most once before the loop closes
+    while order_idx < orders.len() {
+        if let Some(&visit_idx) = visited.get(&order_idx) {
+            visits.drain(..visit_idx);
+            break;
+        }
+        visited.insert(order_idx, visits.len());
+        let order = &orders[order_idx];
+        order_idx += 1;
+        match order.order_type.as_str() {
+            "go-to-station" | "go-to-waypoint" => {
+                let is_waypoint = order.order_type == "go-to-waypoint";
+                let passes = is_waypoint
+                    || order
+                        .non_stop
+                        .as_deref()
+                        .is_some_and(|non_stop| non_stop.contains("via"));
+                approximate |= order
+                    .load
+                    .as_deref()
+                    .is_some_and(|load| load.starts_with("full-load"));
+                let wait = if passes {
+                    0
+                } else {
+                    timetabled(order.wait_time, &mut missing_times)
+                };
+                let name = order
+                    .destination_name
+                    .clone()
+                    .or_else(|| order.destination_id.map(|id| format!("#{id}")))
+                    .unwrap_or_else(|| format!("Order {order_idx}"));
+                visits.push(Visit {
+                    order,
+                    name,
+                    travel: pending_travel + timetabled(order.travel_time, &mut missing_times),
+                    wait,
+                    kind: if passes {
+                        EntryKind::Pass
+                    } else {
+                        EntryKind::Stop
+                    },
+                });
+                pending_travel = 0;
+            }
+            "go-to-depot" => {
+                pending_travel += timetabled(order.travel_time, &mut missing_times)
+                    + order.wait_time.unwrap_or_default();
+                approximate |= order
+                    .depot_action
+                    .as_deref()
+                    .is_some_and(|action| action.contains("unbunch"));
+            }
+            "conditional" => {
+                let always = order
+                    .condition
+                    .as_deref()
+                    .is_some_and(|condition| condition == "always");
+                match order.jump_to {
+                    Some(target) if always && target < orders.len() => order_idx = target,
+                    Some(_) if !always => skipped_conditions = true,
+                    _ => {}
+                }
+            }
+            // labels and implicit orders do not move the vehicle
+            _ => {}
+        }
+        if order_idx == orders.len() {
+            order_idx = 0;
+        }
+    }
+    if missing_times {
+        warnings.push(Diagnostic::warning(ErrorCode::ApproximateTimes, format!(
+            "Some orders of vehicle '{vehicle_name}' are not timetabled, their times are taken \
+             as 0"
+        )).train(vehicle_name));
+    }
+    if approximate {
+        warnings.push(
+            Diagnostic::warning(
+                ErrorCode::ApproximateTimes,
+                format!(
+                    "Vehicle '{vehicle_name}' full loads or unbunches, its times are approximate"
+                ),
+            )
+            .train(vehicle_name),
+        );
+    }
+    if skipped_conditions {
+        warnings.push(Diagnostic::warning(ErrorCode::UnsupportedFeature, format!(
+            "Vehicle '{vehicle_name}' has conditional orders, they are assumed to never jump"
+        )).train(vehicle_name));
+    }
+    visits
+}
+
+/// Get a timetabled time, or 0 if the time is not timetabled.
+fn timetabled(ticks: Option<i64>, missing: &mut bool) -> i64 {
+    ticks.unwrap_or_else(|| {
+        *missing = true;
+        0
+    })
+}
+
+/// Make the schedule from the visit `start` to the visit `end`, both included. Indices wrap
+/// around the loop. The vehicle departs from the first visit at 0.
+fn make_schedule(
+    visits: &[Visit],
+    start: usize,
+    end: usize,
+    to_time: impl Fn(i64) -> Time,
+) -> Vec<ScheduleEntryHelper> {
+    let mut ticks = 0;
+    (start..=end)
+        .map(|idx| {
+            let visit = &visits[idx % visits.len()];
+            let (arrival, departure) = if idx == start {
+                (ticks, ticks)
+            } else if idx == end {
+                ticks += visit.travel;
+                (ticks, ticks)
+            } else {
+                ticks += visit.travel;
+                let arrival = ticks;
+                ticks += visit.wait;
+                (arrival, ticks)
+            };
+            let kind = if idx == start || idx == end {
+                EntryKind::Stop
+            } else {
+                visit.kind
+            };
+            ScheduleEntryHelper {
+                arrival: to_time(arrival),
+                departure: to_time(departure),
+                station: visit.name.clone(),
+                track: None,
+                kind: Some(kind),
+            }
+        })
+        .collect()
+}
+
+/// Turn the slots of a dispatch schedule into instances. Evenly spaced slots repeat, and other
+/// slots are listed for one day.
+fn dispatch_frequency(
+    dispatch: &DispatchSchedule,
+    ticks_per_minute: i64,
+    to_time: impl Fn(i64) -> Time,
+) -> Option<TrainFrequency> {
+    let day = ticks_per_minute * 60 * 24;
+    let duration = dispatch
+        .duration
+        .filter(|duration| *duration > 0)
+        .unwrap_or(day);
+    let mut offsets: Vec<i64> = dispatch
+        .slots
+        .iter()
+        .map(|slot| slot.offset().rem_euclid(duration))
+        .collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let first = *offsets.first()?;
+    let count = offsets.len() as i64;
+    let evenly_spaced = duration % count == 0
+        && offsets
+            .iter()
+            .enumerate()
+            .all(|(idx, offset)| *offset == first + idx as i64 * duration / count);
+    if evenly_spaced {
+        return Some(TrainFrequency::Repeating(Repetition::from(to_time(
+            duration / count,
+        ))));
+    }
+    let mut times = Vec::new();
+    let mut cycle = 0;
+    while cycle < day {
+        times.extend(
+            offsets
+                .iter()
+                .map(|offset| cycle + offset)
+                .filter(|ticks| *ticks < day)
+                .map(&to_time),
+        );
+        cycle += duration;
+    }
+    Some(TrainFrequency::Fixed(times))
+}
+
+impl Slot {
+    fn offset(&self) -> i64 {
+        match self {
+            Slot::Offset(offset) | Slot::Detailed { offset } => *offset,
+        }
+    }
+}
+
+/// The distance between two locations, in tiles.
+fn distance(function: DistanceFunction, from: Location, to: Location) -> f64 {
+    let (dx, dy) = ((from.x - to.x) as f64, (from.y - to.y) as f64);
+    match function {
+        DistanceFunction::Manhattan => dx.abs() + dy.abs(),
+        DistanceFunction::Euclidean => (dx * dx + dy * dy).sqrt(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const VEHICLES: &str = r#"[
+        {
+            "name": "Train 1",
+            "game-properties": {"ticks-per-minute": 74},
+            "orders": [
+                {"type": "go-to-station", "destination-name": "A", "destination-location": {"X": 0, "Y": 0}, "travel-time": 740, "wait-time": 148, "schedule-index": 0},
+                {"type": "label", "text": "hello"},
+                {"type": "go-to-waypoint", "destination-name": "W", "destination-location": {"X": 10, "Y": 0}, "travel-time": 370},
+                {"type": "go-to-depot", "destination-name": "Depot", "travel-time": 74, "depot-action": "unbunch"},
+                {"type": "go-to-station", "destination-name": "B", "destination-location": {"X": 10, "Y": 20}, "travel-time": 740, "wait-time": 148, "load": "full-load-any"},
+                {"type": "conditional", "condition": "load-percentage", "jump-to": 0},
+                {"type": "go-to-station", "destination-name": "C", "travel-time": 740, "wait-time": 148, "schedule-index": 1}
+            ],
+            "schedules": [
+                {"slots": [0, {"offset": 2220}, 4440], "duration": 6660, "max-delay": 74, "name": "Morning"},
+                {"slots": [100, 3000], "duration": 4440}
+            ]
+        },
+        {
+            "orders": [
+                {"type": "go-to-station", "destination-name": "B", "destination-location": {"X": 10, "Y": 20}, "travel-time": 100, "wait-time": 100},
+                {"type": "conditional", "condition": "always", "jump-to": 3},
+                {"type": "go-to-station", "destination-name": "Never"},
+                {"type": "go-to-station", "destination-name": "A", "destination-location": {"X": 0, "Y": 0}}
+            ]
+        },
+        {"orders": []}
+    ]"#;
+
+    fn config() -> JgrppConfig {
+        JgrppConfig {
+            distance: DistanceFunction::Euclidean,
+            tile_length: default_tile_length(),
+            lengths: vec![(("A".to_string(), "W".to_string()), 5000)],
+            ticks_per_minute: None,
+        }
+    }
+
+    #[test]
+    fn import_dispatch_schedules() {
+        let vehicles: Vec<JgrppVehicle> = serde_json::from_str(VEHICLES).unwrap();
+        let imported = import(vehicles, config()).unwrap();
+        assert_eq!(
+            codes(&imported),
+            [
+                ErrorCode::ApproximateTimes,
+                ErrorCode::UnsupportedFeature,
+                ErrorCode::ApproximateTimes,
+                ErrorCode::SkippedTrain,
+                ErrorCode::UnmeasuredInterval,
+            ]
+        );
+        assert_eq!(station_names(&imported), ["A", "W", "B", "C"]);
+        // given lengths are preferred over the distance between the locations
+        assert_eq!(interval_length(&imported, "A", "W"), 5000);
+        assert_eq!(interval_length(&imported, "W", "B"), 20 * 64);
+
+        // evenly spaced slots repeat, and the depot is not a station
+        let morning = train(&imported, "Train 1 from A");
+        let schedule: Vec<(&str, Time, Time)> = morning
+            .schedule
+            .iter()
+            .map(|entry| (entry.station.as_str(), entry.arrival, entry.departure))
+            .collect();
+        assert_eq!(
+            schedule,
+            [
+                ("A", Time::new(0), Time::new(0)),
+                ("W", Time::new(300), Time::new(300)),
+                ("B", Time::new(960), Time::new(1080)),
+                ("C", Time::new(1680), Time::new(1680)),
+            ]
+        );
+        assert!(matches!(morning.schedule[1].kind, Some(EntryKind::Pass)));
+        assert!(matches!(
+            &morning.frequency,
+            Some(TrainFrequency::Repeating(repetition)) if repetition.interval == Time::new(1800)
+        ));
+        assert_eq!(
+            morning
+                .metadata
+                .get("dispatch_schedule")
+                .and_then(|name| name.as_text()),
+            Some("Morning")
+        );
+
+        // other slots are listed for a day
+        let Some(TrainFrequency::Fixed(times)) = &train(&imported, "Train 1 from C").frequency
+        else {
+            panic!("the second schedule should have fixed times");
+        };
+        assert_eq!(times[..2], [Time::new(81), Time::new(2432)]);
+        assert!(times.iter().all(|time| *time < Time::new(86400)));
+
+        // conditional orders that always jump are followed
+        let stations: Vec<&str> = train(&imported, "Vehicle 2")
+            .schedule
+            .iter()
+            .map(|entry| entry.station.as_str())
+            .collect();
+        assert_eq!(stations, ["B", "A", "B"]);
+    }
+}
@@ -0,0 +1,144 @@
+pub mod csv;
+pub mod etrc;
+pub mod gtfs;
+pub mod gtfs_export;
+pub mod jgrpp;
+pub mod oudia;
+pub mod qetrc;
+pub mod railml;
+pub mod spreadsheet;
+
+use crate::diagnostic::*;
+use crate::input::*;
+use crate::types::*;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// A network read from a file of another program. It has the same shape as the network passed to
+/// `process`, plus the problems found while reading the file.
+#[derive(Serialize)]
+pub struct Imported {
+    #[serde(flatten)]
+    pub(crate) network: NetworkHelper,
+    /// Parts of the file that could not be imported and were skipped or changed.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Shorthand for a text metadata value.
+fn text(value: impl Into<String>) -> ciborium::Value {
+    ciborium::Value::Text(value.into())
+}
+
+/// Make the name unique by adding a number after it, and warn about the renaming.
+fn unique_name(name: &str, seen: &mut HashSet<String>, warnings: &mut Vec<Diagnostic>) -> String {
+    let mut unique = name.to_string();
+    let mut suffix = 2;
+    while seen.contains(&unique) {
+        unique = format!("{name} ({suffix})");
+        suffix += 1;
+    }
+    if unique != name {
+        warnings.push(Diagnostic::warning(
+            ErrorCode::RenamedDuplicate,
+            format!("The name '{name}' is used more than once, renamed to '{unique}'"),
+        ));
+    }
+    seen.insert(unique.clone());
+    unique
+}
+
+/// Turns times of day into times that never go backwards, by adding a day whenever a time is
+/// before the previous one.
+#[derive(Default)]
+struct DayUnwrapper {
+    offset: Time,
+    previous: Option<Time>,
+}
+
+impl DayUnwrapper {
+    fn unwrap(&mut self, time: Time) -> Time {
+        let mut time = time + self.offset;
+        if self.previous.is_some_and(|previous| time < previous) {
+            self.offset += Time::new(86400);
+            time += Time::new(86400);
+        }
+        self.previous = Some(time);
+        time
+    }
+}
+
+#[cfg(test)]
+mod test_util {
+    use super::*;
+
+    /// A time in hours and minutes, which is on the next day from 24:00 on.
+    pub fn hm(hours: i32, minutes: i32) -> Time {
+        Time::new(hours * 3600 + minutes * 60)
+    }
+
+    /// The codes of the warnings, in the order they were raised.
+    pub fn codes(imported: &Imported) -> Vec<ErrorCode> {
+        imported
+            .warnings
+            .iter()
+            .map(|warning| warning.code)
+            .collect()
+    }
+
+    /// The names of the stations, in the order they were imported.
+    pub fn station_names(imported: &Imported) -> Vec<&str> {
+        imported
+            .network
+            .stations
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// The train of the given name.
+    pub fn train<'a>(imported: &'a Imported, name: &str) -> &'a TrainHelper {
+        imported
+            .network
+            .trains
+            .iter()
+            .find(|(train_name, _)| train_name == name)
+            .map(|(_, train)| train)
+            .unwrap_or_else(|| panic!("train {name} should be imported"))
+    }
+
+    /// The length of the interval in meters.
+    pub fn interval_length(imported: &Imported, from: &str, to: &str) -> u32 {
+        imported
+            .network
+            .intervals
+            .iter()
+            .find(|((interval_from, interval_to), _)| interval_from == from && interval_to == to)
+            .map(|(_, interval)| interval.length.meters())
+            .unwrap_or_else(|| panic!("the interval from {from} to {to} should be imported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_util::hm;
+    use super::*;
+
+    #[test]
+    fn unwrap_days() {
+        let mut unwrapper = DayUnwrapper::default();
+        let times: Vec<Time> = [hm(23, 50), hm(23, 50), hm(0, 5), hm(0, 4), hm(1, 0)]
+            .into_iter()
+            .map(|time| unwrapper.unwrap(time))
+            .collect();
+        // equal times stay on the same day, and every step back adds a day
+        assert_eq!(
+            times,
+            [hm(23, 50), hm(23, 50), hm(24, 5), hm(48, 4), hm(49, 0)]
+        );
+
+        // times that are already past 24:00 are kept
+        let mut unwrapper = DayUnwrapper::default();
+        assert_eq!(unwrapper.unwrap(hm(24, 20)), hm(24, 20));
+        assert_eq!(unwrapper.unwrap(hm(25, 5)), hm(25, 5));
+    }
+}
@@ -0,0 +1,736 @@
+use super::*;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// OuDia files do not have distances, so all intervals have the same length.
+const INTERVAL_LENGTH: u32 = 1000;
+
+/// A block of an OuDia file. Blocks start with a `Name.` line and end with a `.` line. Other
+/// lines are `key=value` fields.
+#[derive(Default)]
+struct Block {
+    name: String,
+    fields: Vec<(String, String)>,
+    children: Vec<Block>,
+}
+
+impl Block {
+    fn parse(text: &str) -> Result<Self> {
+        let mut stack = vec![Block::default()];
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if let Some((key, value)) = line.split_once('=') {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .fields
+                    .push((key.to_string(), value.to_string()));
+            } else if line == "." {
+                let block = stack.pop().unwrap();
+                let Some(parent) = stack.last_mut() else {
+                    return Err(anyhow!(
+                        "Line {} closes a block that is not open",
+                        line_idx + 1
+                    ));
+                };
+                parent.children.push(block);
+            } else if let Some(name) = line.strip_suffix('.') {
+                stack.push(Block {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+        if stack.len() != 1 {
+            return Err(anyhow!(
+                "Block '{}' is not closed",
+                stack.last().unwrap().name
+            ));
+        }
+        Ok(stack.pop().unwrap())
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field_key, _)| field_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Block> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Block> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Options of the OuDia importer.
+#[derive(Deserialize)]
+pub struct OudiaConfig {
+    /// The name of the diagram (`DiaName`) to read. The first diagram is read if `None`.
+    diagram: Option<String>,
+}
+
+/// A station of the line.
+struct OudiaStation {
+    name: String,
+    track_count: usize,
+}
+
+/// Read an OuDia (`.oud`) or OuDiaSecond (`.oud2`) file. Files that are not UTF-8 are read as
+/// Shift_JIS, which older versions of OuDia use.
+pub fn import(data: &[u8], config: OudiaConfig) -> Result<Imported> {
+    let content = match std::str::from_utf8(data) {
+        Ok(content) => std::borrow::Cow::Borrowed(content),
+        Err(_) => {
+            let (content, _, had_errors) = encoding_rs::SHIFT_JIS.decode(data);
+            if had_errors {
+                return Err(anyhow!("The file is neither UTF-8 nor Shift_JIS"));
+            }
+            content
+        }
+    };
+    let root = Block::parse(&content).context("Failed to parse OuDia file")?;
+    let rosen = root
+        .child("Rosen")
+        .ok_or_else(|| anyhow!("The file does not have a line (Rosen)"))?;
+    let mut warnings = Vec::new();
+
+    // stations
+    let mut line_stations: Vec<OudiaStation> = Vec::new();
+    let mut stations: Vec<(String, StationHelper)> = Vec::new();
+    let mut seen_stations = HashSet::new();
+    for eki in rosen.children("Eki") {
+        let name = unique_name(
+            eki.get("Ekimei").unwrap_or_default(),
+            &mut seen_stations,
+            &mut warnings,
+        );
+        let main_tracks: Vec<usize> = ["DownMain", "UpMain"]
+            .iter()
+            .filter_map(|key| eki.get(key)?.parse().ok())
+            .collect();
+        let tracks: Vec<TrackHelper> = eki
+            .child("EkiTrack2Cont")
+            .map(|container| {
+                container
+                    .children("EkiTrack2")
+                    .enumerate()
+                    .map(|(track_idx, track)| TrackHelper {
+                        name: track
+                            .get("TrackName")
+                            .map_or_else(|| (track_idx + 1).to_string(), str::to_string),
+                        main: (!main_tracks.is_empty()).then(|| main_tracks.contains(&track_idx)),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut metadata = Metadata::new();
+        if eki.get("Ekikibo") == Some("Ekikibo_Syuyou") {
+            metadata.insert("major".to_string(), ciborium::Value::Bool(true));
+        }
+        line_stations.push(OudiaStation {
+            name: name.clone(),
+            track_count: tracks.len(),
+        });
+        stations.push((
+            name,
+            StationHelper {
+                label_size: Default::default(),
+                tracks: (!tracks.is_empty()).then_some(TracksHelper::List(tracks)),
+                metadata,
+            },
+        ));
+    }
+    if line_stations.is_empty() {
+        return Err(anyhow!("The line does not have any stations"));
+    }
+
+    let intervals = line_stations
+        .windows(2)
+        .map(|pair| {
+            (
+                (pair[0].name.clone(), pair[1].name.clone()),
+                IntervalHelper {
+                    length: IntervalLength::new(INTERVAL_LENGTH),
+                    bidirectional: Some(true),
+                },
+            )
+        })
+        .collect();
+
+    // train types
+    let train_types: Vec<Metadata> = rosen
+        .children("Ressyasyubetsu")
+        .map(train_type_metadata)
+        .collect();
+
+    // trains of the chosen diagram
+    let diagram_names = || {
+        rosen
+            .children("Dia")
+            .map(|dia| format!("'{}'", dia.get("DiaName").unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let dia = match &config.diagram {
+        Some(name) => rosen
+            .children("Dia")
+            .find(|dia| dia.get("DiaName") == Some(name.as_str()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Diagram '{}' not found, available diagrams are {}",
+                    name,
+                    diagram_names()
+                )
+            })?,
+        None => rosen
+            .child("Dia")
+            .ok_or_else(|| anyhow!("The file does not have any diagrams (Dia)"))?,
+    };
+
+    let mut trains: Vec<(String, TrainHelper)> = Vec::new();
+    let mut seen_trains = HashSet::new();
+    let mut nameless_count = 0;
+    // trains by their numbers, and the operations that could refer to them
+    let mut train_numbers: HashMap<String, usize> = HashMap::new();
+    let mut links: Vec<(usize, Operation)> = Vec::new();
+    for (direction, reversed) in [("Kudari", false), ("Nobori", true)] {
+        let Some(direction_block) = dia.child(direction) else {
+            continue;
+        };
+        let ordered_stations: Vec<&OudiaStation> = if reversed {
+            line_stations.iter().rev().collect()
+        } else {
+            line_stations.iter().collect()
+        };
+        for ressya in direction_block.children("Ressya") {
+            let name = match train_name(ressya) {
+                Some(name) => name,
+                None => {
+                    nameless_count += 1;
+                    format!("?{nameless_count}")
+                }
+            };
+            let name = unique_name(&name, &mut seen_trains, &mut warnings);
+            let schedule = make_schedule(
+                &name,
+                ressya.get("EkiJikoku").unwrap_or_default(),
+                &ordered_stations,
+                &mut warnings,
+            )
+            .with_context(|| format!("Failed to read the timetable of train '{name}'"))?;
+            if schedule.is_empty() {
+                continue;
+            }
+
+            let mut metadata = ressya
+                .get("Syubetsu")
+                .and_then(|type_idx| train_types.get(type_idx.parse::<usize>().ok()?))
+                .cloned()
+                .unwrap_or_default();
+            metadata.insert("direction".to_string(), text(direction.to_lowercase()));
+            if let Some(remarks) = ressya.get("Bikou").filter(|remarks| !remarks.is_empty()) {
+                metadata.insert("remarks".to_string(), text(remarks));
+            }
+            let operations = read_operations(&name, ressya, &ordered_stations, &mut warnings);
+            if !operations.is_empty() {
+                metadata.insert(
+                    "operations".to_string(),
+                    ciborium::Value::Array(operations.iter().map(Operation::to_value).collect()),
+                );
+            }
+            if let Some(number) = ressya.get("Ressyabangou").filter(|it| !it.is_empty()) {
+                train_numbers
+                    .entry(number.to_string())
+                    .or_insert(trains.len());
+            }
+            links.extend(
+                operations
+                    .into_iter()
+                    .map(|operation| (trains.len(), operation)),
+            );
+
+            trains.push((
+                name,
+                TrainHelper {
+                    frequency: None,
+                    calendar: None,
+                    label_size: Default::default(),
+                    priority: None,
+                    schedule,
+                    metadata,
+                },
+            ));
+        }
+    }
+
+    // operations that name another train link the two trains, which is recorded as the previous
+    // and the next train of each of them
+    for (train_idx, operation) in links {
+        // other operations, e.g. going to or from the depot, are only kept in the metadata
+        let Some(&other_idx) = train_numbers.get(&operation.detail) else {
+            continue;
+        };
+        if other_idx == train_idx {
+            continue;
+        }
+        let (previous, next) = if operation.after {
+            (train_idx, other_idx)
+        } else {
+            (other_idx, train_idx)
+        };
+        let (previous_name, next_name) = (trains[previous].0.clone(), trains[next].0.clone());
+        trains[previous]
+            .1
+            .metadata
+            .insert("next".to_string(), text(next_name));
+        trains[next]
+            .1
+            .metadata
+            .insert("previous".to_string(), text(previous_name));
+    }
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// An operation (運用) of a train, written as `Operation{station}{side}={kind}/{detail}`. The
+/// station is the index of the station in the direction of the train, like in `EkiJikoku`. The
+/// side is `B` for the work before the train departs from the station, and `A` for the work
+/// after it arrives.
+struct Operation {
+    station: String,
+    after: bool,
+    kind: Option<i64>,
+    /// E.g. the number of the train this one continues from or as.
+    detail: String,
+}
+
+impl Operation {
+    fn to_value(&self) -> ciborium::Value {
+        let mut fields = vec![
+            (text("station"), text(&self.station)),
+            (
+                text("side"),
+                text(if self.after { "after" } else { "before" }),
+            ),
+            (text("detail"), text(&self.detail)),
+        ];
+        if let Some(kind) = self.kind {
+            fields.push((text("kind"), ciborium::Value::Integer(kind.into())));
+        }
+        ciborium::Value::Map(fields)
+    }
+}
+
+/// Read the `Operation*` fields of a train.
+fn read_operations(
+    train_name: &str,
+    ressya: &Block,
+    stations: &[&OudiaStation],
+    warnings: &mut Vec<Diagnostic>,
+) -> Vec<Operation> {
+    let mut operations = Vec::new();
+    for (key, value) in &ressya.fields {
+        let Some(rest) = key.strip_prefix("Operation") else {
+            continue;
+        };
+        let (station_idx, after) = match rest.split_at(rest.len().saturating_sub(1)) {
+            (station_idx, "A") => (station_idx, true),
+            (station_idx, "B") => (station_idx, false),
+            _ => (rest, false),
+        };
+        let Some(station) = station_idx
+            .parse::<usize>()
+            .ok()
+            .and_then(|station_idx| stations.get(station_idx))
+        else {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::UnsupportedFeature,
+                    format!("Train '{train_name}' has an unknown operation '{key}', it is skipped"),
+                )
+                .train(train_name),
+            );
+            continue;
+        };
+        let (kind, detail) = value.split_once('/').unwrap_or((value, ""));
+        operations.push(Operation {
+            station: station.name.clone(),
+            after,
+            kind: kind.parse().ok(),
+            detail: detail.to_string(),
+        });
+    }
+    operations
+}
+
+/// The train number, followed by the train name and its number if there is one.
+fn train_name(ressya: &Block) -> Option<String> {
+    let number = ressya.get("Ressyabangou").filter(|it| !it.is_empty());
+    let title = ressya
+        .get("Ressyamei")
+        .filter(|it| !it.is_empty())
+        .map(
+            |title| match ressya.get("Gousuu").filter(|it| !it.is_empty()) {
+                Some(gousuu) => format!("{title}{gousuu}号"),
+                None => title.to_string(),
+            },
+        );
+    match (number, title) {
+        (Some(number), Some(title)) => Some(format!("{number} {title}")),
+        (Some(number), None) => Some(number.to_string()),
+        (None, Some(title)) => Some(title),
+        (None, None) => None,
+    }
+}
+
+/// Turns a train type into metadata, with the same keys as the ones the qETRC importer uses.
+fn train_type_metadata(syubetsu: &Block) -> Metadata {
+    let mut metadata = Metadata::new();
+    if let Some(type_name) = syubetsu.get("Syubetsumei") {
+        metadata.insert("type".to_string(), text(type_name));
+    }
+    // colours are written as AABBGGRR
+    if let Some(color) = syubetsu.get("DiagramSenColor")
+        && color.len() == 8
+        && color.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        let color = format!("#{}{}{}", &color[6..8], &color[4..6], &color[2..4]);
+        metadata.insert("color".to_string(), text(color));
+    }
+    let line_style = match syubetsu.get("DiagramSenStyle") {
+        Some("SenStyle_Jissen") => Some(1),
+        Some("SenStyle_Hasen") => Some(2),
+        Some("SenStyle_Tensen") => Some(3),
+        Some("SenStyle_Ittensasen") => Some(4),
+        _ => None,
+    };
+    if let Some(line_style) = line_style {
+        metadata.insert(
+            "line_style".to_string(),
+            ciborium::Value::Integer(line_style.into()),
+        );
+    }
+    if syubetsu.get("DiagramSenIsBold") == Some("1") {
+        metadata.insert("line_width".to_string(), ciborium::Value::Float(2.0));
+    }
+    metadata
+}
+
+/// Parse a time written as `HMM`, `HHMM`, `HMMSS`, or `HHMMSS`.
+fn parse_time(time: &str) -> Result<Time> {
+    if !time.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!("Invalid time '{}'", time));
+    }
+    let (hours, minutes, seconds) = match time.len() {
+        3 | 4 => (&time[..time.len() - 2], &time[time.len() - 2..], "0"),
+        5 | 6 => (
+            &time[..time.len() - 4],
+            &time[time.len() - 4..time.len() - 2],
+            &time[time.len() - 2..],
+        ),
+        _ => return Err(anyhow!("Invalid time '{}'", time)),
+    };
+    let (hours, minutes, seconds): (i32, i32, i32) =
+        (hours.parse()?, minutes.parse()?, seconds.parse()?);
+    if minutes >= 60 || seconds >= 60 {
+        return Err(anyhow!("Invalid time '{}'", time));
+    }
+    Ok(Time::new(hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Read the `EkiJikoku` field of a train. Each station has an entry written as
+/// `kind;arrival/departure$track`, where any part except the kind could be left out.
+/// Kind 1 is a stop and kind 2 is a pass. Entries without times are left to be interpolated, with
+/// one warning per train for the stops and tracked passes among them.
+fn make_schedule(
+    train_name: &str,
+    eki_jikoku: &str,
+    stations: &[&OudiaStation],
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Vec<ScheduleEntryHelper>> {
+    let mut schedule = Vec::new();
+    let mut unwrapper = DayUnwrapper::default();
+    let mut untimed = 0;
+    for (station_idx, entry) in eki_jikoku.split(',').enumerate() {
+        if entry.is_empty() {
+            continue;
+        }
+        let Some(station) = stations.get(station_idx) else {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedEntry,
+                    format!("Train '{train_name}' has more entries than the line has stations"),
+                )
+                .train(train_name),
+            );
+            break;
+        };
+        let (kind, rest) = entry.split_once(';').unwrap_or((entry, ""));
+        let kind = match kind {
+            "1" => EntryKind::Stop,
+            "2" => EntryKind::Pass,
+            // not served, or not via this station
+            _ => continue,
+        };
+        let (times, track) = match rest.split_once('$') {
+            Some((times, track)) => (times, Some(track)),
+            None => (rest, None),
+        };
+        let (arrival, departure) = times.split_once('/').unwrap_or(("", times));
+        let arrival = (!arrival.is_empty())
+            .then(|| parse_time(arrival))
+            .transpose()?;
+        let departure = (!departure.is_empty())
+            .then(|| parse_time(departure))
+            .transpose()?;
+        let (arrival, departure) = match (arrival, departure) {
+            (Some(arrival), Some(departure)) => (arrival, departure),
+            (Some(time), None) | (None, Some(time)) => (time, time),
+            (None, None) => {
+                // passes without times are common, and only worth a warning if they had a track
+                if kind == EntryKind::Stop || track.is_some() {
+                    untimed += 1;
+                }
+                continue;
+            }
+        };
+        let arrival = unwrapper.unwrap(arrival);
+        let departure = unwrapper.unwrap(departure);
+
+        // tracks are the indices to the station's tracks
+        let track =
+            match track.map(|track| track.parse::<u16>()) {
+                None => None,
+                Some(Ok(track)) if usize::from(track) < station.track_count => {
+                    Some(TrackRef::Index(track))
+                }
+                Some(_) => {
+                    warnings.push(Diagnostic::warning(ErrorCode::UnknownTrack, format!(
+                    "Train '{}' uses the unknown track '{}' at '{}', the track is ignored",
+                    train_name,
+                    track.unwrap_or_default(),
+                    station.name
+                )).train(train_name));
+                    None
+                }
+            };
+
+        schedule.push(ScheduleEntryHelper {
+            arrival,
+            departure,
+            station: station.name.clone(),
+            track,
+            kind: Some(kind),
+        });
+    }
+    if untimed > 0 {
+        warnings.push(
+            Diagnostic::warning(
+                ErrorCode::SkippedEntry,
+                format!(
+                    "Train '{train_name}' has {untimed} stops or tracked passes without times, they \
+                     are left to be interpolated without their tracks"
+                ),
+            )
+            .train(train_name),
+        );
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const FILE: &str = "FileType=OuDiaSecond.1.10
+Rosen.
+Rosenmei=テスト線
+Eki.
+Ekimei=東京
+Ekikibo=Ekikibo_Syuyou
+DownMain=0
+UpMain=1
+EkiTrack2Cont.
+EkiTrack2.
+TrackName=1番線
+.
+EkiTrack2.
+TrackName=2番線
+.
+EkiTrack2.
+TrackName=3番線
+.
+.
+.
+Eki.
+Ekimei=神田
+.
+Eki.
+Ekimei=上野
+.
+Ressyasyubetsu.
+Syubetsumei=普通
+DiagramSenColor=00FF8000
+.
+Dia.
+DiaName=平日
+Kudari.
+Ressya.
+Houkou=Kudari
+Syubetsu=0
+Ressyabangou=101M
+EkiJikoku=1;2350/2355$2,2;$1,1;002/
+Operation0B=1/
+Operation2A=0/102M
+.
+Ressya.
+Houkou=Kudari
+Syubetsu=0
+Ressyabangou=103M
+EkiJikoku=1;2300/,2,1;2310/
+.
+.
+Nobori.
+Ressya.
+Houkou=Nobori
+Syubetsu=0
+Ressyabangou=102M
+EkiJikoku=1;600,1;60530/606$1
+Operation1A=1/
+.
+.
+.
+.
+";
+
+    #[test]
+    fn import_times_and_tracks() {
+        let imported = import(FILE.as_bytes(), OudiaConfig { diagram: None }).unwrap();
+        // the pass through 神田 has a track but no time, and 神田 does not have track 1. The plain
+        // pass of 103M is not worth a warning.
+        assert_eq!(
+            codes(&imported),
+            [ErrorCode::SkippedEntry, ErrorCode::UnknownTrack]
+        );
+        assert_eq!(imported.warnings[0].train.as_deref(), Some("101M"));
+        let network = &imported.network;
+        let main: Vec<Option<bool>> = match &network.stations[0].1.tracks {
+            Some(TracksHelper::List(tracks)) => tracks.iter().map(|track| track.main).collect(),
+            _ => panic!("東京 should have a list of tracks"),
+        };
+        assert_eq!(main, [Some(true), Some(true), Some(false)]);
+
+        let (name, down) = &network.trains[0];
+        assert_eq!(name, "101M");
+        let stations: Vec<&str> = down
+            .schedule
+            .iter()
+            .map(|entry| entry.station.as_str())
+            .collect();
+        assert_eq!(stations, ["東京", "上野"]);
+        assert_eq!(down.schedule[0].arrival, hm(23, 50));
+        assert_eq!(down.schedule[0].departure, hm(23, 55));
+        assert!(matches!(down.schedule[0].track, Some(TrackRef::Index(2))));
+        // times are written without a colon, and `002/` is an arrival without a departure
+        assert_eq!(down.schedule[1].arrival, hm(24, 2));
+        assert_eq!(down.schedule[1].departure, down.schedule[1].arrival);
+        assert_eq!(
+            down.metadata.get("color").and_then(|color| color.as_text()),
+            Some("#0080FF")
+        );
+
+        // up trains run through the stations in reverse
+        let up = &network.trains[2].1;
+        assert_eq!(up.schedule[0].station, "上野");
+        assert_eq!(up.schedule[1].station, "神田");
+        // times could have seconds
+        assert_eq!(up.schedule[1].arrival, hm(6, 5) + Time::new(30));
+        assert!(up.schedule[1].track.is_none());
+    }
+
+    #[test]
+    fn import_operations() {
+        let imported = import(FILE.as_bytes(), OudiaConfig { diagram: None }).unwrap();
+        let trains = &imported.network.trains;
+        let metadata_text = |train_idx: usize, key: &str| {
+            trains[train_idx]
+                .1
+                .metadata
+                .get(key)
+                .and_then(|value| value.as_text())
+        };
+        // 101M continues as 102M after arriving at 上野
+        assert_eq!(trains[0].0, "101M");
+        assert_eq!(metadata_text(0, "next"), Some("102M"));
+        assert_eq!(metadata_text(0, "previous"), None);
+        assert_eq!(trains[2].0, "102M");
+        assert_eq!(metadata_text(2, "previous"), Some("101M"));
+        assert_eq!(metadata_text(2, "next"), None);
+        assert_eq!(metadata_text(1, "previous"), None);
+
+        // every operation is kept, with the station counted in the direction of the train
+        let operations = |train_idx: usize| -> Vec<(String, String, String)> {
+            let Some(ciborium::Value::Array(operations)) =
+                trains[train_idx].1.metadata.get("operations")
+            else {
+                return Vec::new();
+            };
+            operations
+                .iter()
+                .map(|operation| {
+                    let field = |key: &str| {
+                        operation
+                            .as_map()
+                            .unwrap()
+                            .iter()
+                            .find(|(field_key, _)| field_key.as_text() == Some(key))
+                            .and_then(|(_, value)| value.as_text())
+                            .unwrap()
+                            .to_string()
+                    };
+                    (field("station"), field("side"), field("detail"))
+                })
+                .collect()
+        };
+        assert_eq!(
+            operations(0),
+            [
+                ("東京".to_string(), "before".to_string(), String::new()),
+                ("上野".to_string(), "after".to_string(), "102M".to_string()),
+            ]
+        );
+        assert_eq!(
+            operations(2),
+            [("神田".to_string(), "after".to_string(), String::new())]
+        );
+    }
+
+    #[test]
+    fn import_shift_jis() {
+        let (data, _, _) = encoding_rs::SHIFT_JIS.encode(FILE);
+        let imported = import(
+            &data,
+            OudiaConfig {
+                diagram: Some("平日".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(imported.network.stations[0].0, "東京");
+        assert_eq!(imported.network.trains.len(), 3);
+    }
+}
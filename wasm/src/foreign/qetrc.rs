@@ -0,0 +1,502 @@
+use super::*;
+use crate::chinese_railway_type::match_type;
+use crate::types::*;
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Bits of `QetrcStation::direction`.
+const DOWN_BIT: u8 = 1;
+const UP_BIT: u8 = 2;
+
+#[derive(Deserialize)]
+struct QetrcFile {
+    /// pyETRC files and older qETRC files have one line.
+    line: Option<QetrcLine>,
+    /// Newer qETRC files could have many lines.
+    #[serde(default)]
+    lines: Vec<QetrcLine>,
+    #[serde(default)]
+    trains: Vec<QetrcTrain>,
+    #[serde(default)]
+    circuits: Vec<QetrcCircuit>,
+}
+
+#[derive(Deserialize)]
+struct QetrcLine {
+    #[serde(default)]
+    name: String,
+    stations: Vec<QetrcStation>,
+}
+
+#[derive(Deserialize)]
+struct QetrcStation {
+    zhanming: String,
+    /// Mileage in kilometers.
+    licheng: f64,
+    /// Mileage in kilometers for the up direction, if it differs.
+    counter: Option<f64>,
+    /// The level of the station.
+    dengji: Option<i64>,
+    /// Which directions pass the station. See `DOWN_BIT` and `UP_BIT`.
+    #[serde(default = "both_directions")]
+    direction: u8,
+    #[serde(default)]
+    tracks: Vec<String>,
+}
+
+fn both_directions() -> u8 {
+    DOWN_BIT | UP_BIT
+}
+
+#[derive(Deserialize)]
+struct QetrcTrain {
+    /// The full, down and up train numbers.
+    checi: Vec<String>,
+    #[serde(default, rename = "type")]
+    train_type: String,
+    #[serde(default, rename = "UI")]
+    ui: Option<QetrcTrainUi>,
+    timetable: Vec<QetrcEntry>,
+}
+
+#[derive(Deserialize)]
+struct QetrcTrainUi {
+    #[serde(rename = "Color")]
+    color: Option<String>,
+    #[serde(rename = "LineWidth")]
+    line_width: Option<f64>,
+    #[serde(rename = "LineStyle")]
+    line_style: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct QetrcEntry {
+    zhanming: String,
+    ddsj: String,
+    cfsj: String,
+    /// Whether the train stops for passengers or goods.
+    business: Option<bool>,
+    track: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct QetrcCircuit {
+    name: String,
+    order: Vec<QetrcCircuitNode>,
+}
+
+#[derive(Deserialize)]
+struct QetrcCircuitNode {
+    checi: String,
+    /// Whether the train is connected to the previous train of the circuit.
+    #[serde(default)]
+    link: bool,
+    /// Virtual trains are not in the file.
+    #[serde(default, rename = "virtual")]
+    is_virtual: bool,
+}
+
+/// Options of the qETRC importer.
+#[derive(Deserialize)]
+pub struct QetrcConfig {
+    /// The name of the line to read. Could be omitted if the file has only one line.
+    line: Option<String>,
+}
+
+/// Read a qETRC or pyETRC diagram file.
+pub fn import(data: &[u8], config: QetrcConfig) -> Result<Imported> {
+    let file: QetrcFile = serde_json::from_slice(data).context("Failed to parse qETRC file")?;
+    let mut warnings = Vec::new();
+
+    let QetrcFile {
+        line,
+        lines,
+        trains,
+        circuits,
+    } = file;
+    let mut all_lines: Vec<QetrcLine> = line.into_iter().chain(lines).collect();
+    let line_names = || {
+        all_lines
+            .iter()
+            .map(|line| format!("'{}'", line.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let line_idx = match &config.line {
+        Some(name) => all_lines
+            .iter()
+            .position(|line| line.name == *name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Line '{}' not found, available lines are {}",
+                    name,
+                    line_names()
+                )
+            })?,
+        None if all_lines.len() == 1 => 0,
+        None if all_lines.is_empty() => return Err(anyhow!("The file does not have any lines")),
+        None => {
+            return Err(anyhow!(
+                "The file has more than one line, please specify one of {}",
+                line_names()
+            ));
+        }
+    };
+    let mut line = all_lines.swap_remove(line_idx);
+    line.stations
+        .sort_by(|a, b| a.licheng.total_cmp(&b.licheng));
+
+    let mut stations: Vec<(String, StationHelper)> = Vec::with_capacity(line.stations.len());
+    let mut station_tracks: HashMap<&str, &[String]> = HashMap::new();
+    for station in &line.stations {
+        if station_tracks.contains_key(station.zhanming.as_str()) {
+            warnings.push(Diagnostic::warning(ErrorCode::DuplicateStation, format!(
+                "Station '{}' appears more than once on the line, only the first one is kept",
+                station.zhanming
+            )).station(&station.zhanming));
+            continue;
+        }
+        station_tracks.insert(&station.zhanming, &station.tracks);
+        let mut metadata = Metadata::new();
+        if let Some(level) = station.dengji {
+            metadata.insert("level".to_string(), ciborium::Value::Integer(level.into()));
+        }
+        stations.push((
+            station.zhanming.clone(),
+            StationHelper {
+                label_size: Default::default(),
+                tracks: (!station.tracks.is_empty()).then(|| {
+                    TracksHelper::List(
+                        station
+                            .tracks
+                            .iter()
+                            .map(|name| TrackHelper {
+                                name: name.clone(),
+                                main: None,
+                            })
+                            .collect(),
+                    )
+                }),
+                metadata,
+            },
+        ));
+    }
+
+    let intervals = make_intervals(&line.stations);
+
+    let mut seen_names: HashSet<String> = HashSet::new();
+    let mut imported_trains: Vec<(String, TrainHelper)> = Vec::with_capacity(trains.len());
+    // the first train with each train number, to resolve circuits
+    let mut train_indices: HashMap<String, usize> = HashMap::new();
+    for train in trains {
+        let Some(number) = train.checi.iter().find(|number| !number.is_empty()) else {
+            warnings.push(Diagnostic::warning(
+                ErrorCode::SkippedTrain,
+                "A train without a train number was skipped",
+            ));
+            continue;
+        };
+        let name = unique_name(number, &mut seen_names, &mut warnings);
+        train_indices
+            .entry(number.clone())
+            .or_insert(imported_trains.len());
+
+        let schedule = make_schedule(&name, &train.timetable, &station_tracks, &mut warnings)
+            .with_context(|| format!("Failed to read the timetable of train '{name}'"))?;
+
+        let mut metadata = Metadata::new();
+        let train_type = if train.train_type.is_empty() {
+            match_type(number)
+        } else {
+            &train.train_type
+        };
+        metadata.insert("type".to_string(), text(train_type));
+        if let Some(ui) = train.ui {
+            if let Some(color) = ui.color {
+                metadata.insert("color".to_string(), text(color));
+            }
+            if let Some(line_width) = ui.line_width {
+                metadata.insert("line_width".to_string(), ciborium::Value::Float(line_width));
+            }
+            if let Some(line_style) = ui.line_style {
+                metadata.insert(
+                    "line_style".to_string(),
+                    ciborium::Value::Integer(line_style.into()),
+                );
+            }
+        }
+        imported_trains.push((
+            name,
+            TrainHelper {
+                frequency: None,
+                calendar: None,
+                label_size: Default::default(),
+                priority: None,
+                schedule,
+                metadata,
+            },
+        ));
+    }
+
+    // circuits are recorded as the previous and the next train of each train
+    for circuit in circuits {
+        let mut previous: Option<usize> = None;
+        for node in &circuit.order {
+            let current = train_indices.get(&node.checi).copied();
+            if current.is_none() && !node.is_virtual {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::UnknownReference,
+                        format!(
+                            "Train '{}' of circuit '{}' not found",
+                            node.checi, circuit.name
+                        ),
+                    )
+                    .train(&node.checi),
+                );
+            }
+            if let Some(current) = current {
+                imported_trains[current]
+                    .1
+                    .metadata
+                    .insert("circuit".to_string(), text(&circuit.name));
+                if node.link
+                    && let Some(previous) = previous
+                {
+                    let previous_name = imported_trains[previous].0.clone();
+                    let current_name = imported_trains[current].0.clone();
+                    imported_trains[previous]
+                        .1
+                        .metadata
+                        .insert("next".to_string(), text(current_name));
+                    imported_trains[current]
+                        .1
+                        .metadata
+                        .insert("previous".to_string(), text(previous_name));
+                }
+            }
+            previous = current;
+        }
+    }
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains: imported_trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Make the intervals between neighbouring stations. Down trains use the mileage, and up trains
+/// use the counter mileage if there is one. Stations that are only passed in one direction are
+/// skipped in the other direction.
+fn make_intervals(stations: &[QetrcStation]) -> Vec<((String, String), IntervalHelper)> {
+    let to_meters =
+        |kilometers: f64| IntervalLength::new((kilometers.abs() * 1000.0).round() as u32);
+    let chain = |bit: u8| -> Vec<&QetrcStation> {
+        let mut seen = HashSet::new();
+        stations
+            .iter()
+            .filter(|station| station.direction & bit != 0 && seen.insert(&station.zhanming))
+            .collect()
+    };
+
+    let mut down_lengths: Vec<(&str, &str, IntervalLength)> = Vec::new();
+    for pair in chain(DOWN_BIT).windows(2) {
+        let [from, to] = pair else { continue };
+        down_lengths.push((
+            &from.zhanming,
+            &to.zhanming,
+            to_meters(to.licheng - from.licheng),
+        ));
+    }
+    let mut up_lengths: HashMap<(&str, &str), IntervalLength> = HashMap::new();
+    let mut up_order: Vec<(&str, &str)> = Vec::new();
+    for pair in chain(UP_BIT).windows(2) {
+        let [to, from] = pair else { continue };
+        let length = match (from.counter, to.counter) {
+            (Some(from_counter), Some(to_counter)) => to_meters(to_counter - from_counter),
+            _ => to_meters(from.licheng - to.licheng),
+        };
+        up_lengths.insert((&from.zhanming, &to.zhanming), length);
+        up_order.push((&from.zhanming, &to.zhanming));
+    }
+
+    let mut intervals = Vec::with_capacity(down_lengths.len() + up_order.len());
+    for (from, to, length) in down_lengths {
+        let bidirectional = up_lengths.get(&(to, from)) == Some(&length);
+        if bidirectional {
+            up_lengths.remove(&(to, from));
+        }
+        intervals.push((
+            (from.to_string(), to.to_string()),
+            IntervalHelper {
+                length,
+                bidirectional: Some(bidirectional),
+            },
+        ));
+    }
+    for key in up_order {
+        let Some(length) = up_lengths.remove(&key) else {
+            continue;
+        };
+        intervals.push((
+            (key.0.to_string(), key.1.to_string()),
+            IntervalHelper {
+                length,
+                bidirectional: Some(false),
+            },
+        ));
+    }
+    intervals
+}
+
+/// Read the timetable of a train. Times only have the time of day, so a day is added whenever a
+/// time goes backwards.
+fn make_schedule(
+    train_name: &str,
+    timetable: &[QetrcEntry],
+    station_tracks: &HashMap<&str, &[String]>,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<Vec<ScheduleEntryHelper>> {
+    let mut schedule = Vec::with_capacity(timetable.len());
+    let mut unwrapper = DayUnwrapper::default();
+    for (entry_idx, entry) in timetable.iter().enumerate() {
+        let arrival = unwrapper.unwrap(entry.ddsj.parse()?);
+        let departure = unwrapper.unwrap(entry.cfsj.parse()?);
+
+        // the first and the last stations are always stops
+        let kind = if entry_idx == 0 || entry_idx + 1 == timetable.len() {
+            EntryKind::Stop
+        } else if arrival == departure {
+            EntryKind::Pass
+        } else if entry.business == Some(false) {
+            EntryKind::TechnicalStop
+        } else {
+            EntryKind::Stop
+        };
+
+        let track = match entry.track.as_deref() {
+            None | Some("") => None,
+            Some(track) => {
+                let known = station_tracks
+                    .get(entry.zhanming.as_str())
+                    .is_some_and(|tracks| tracks.iter().any(|name| name == track));
+                if !known {
+                    warnings.push(Diagnostic::warning(ErrorCode::UnknownTrack, format!(
+                        "Train '{}' uses the unknown track '{}' at '{}', the track is ignored",
+                        train_name, track, entry.zhanming
+                    )).train(train_name));
+                }
+                known.then(|| TrackRef::Name(track.to_string()))
+            }
+        };
+
+        schedule.push(ScheduleEntryHelper {
+            arrival,
+            departure,
+            station: entry.zhanming.clone(),
+            track,
+            kind: Some(kind),
+        });
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const FILE: &str = r#"{
+        "line": {
+            "name": "Test",
+            "stations": [
+                {"zhanming": "C", "licheng": 25.0, "counter": 24.0},
+                {"zhanming": "A", "licheng": 0.0, "counter": 0.0, "tracks": ["1", "2"]},
+                {"zhanming": "B", "licheng": 10.0, "direction": 1}
+            ]
+        },
+        "trains": [
+            {
+                "checi": ["G1", "G1", ""],
+                "timetable": [
+                    {"zhanming": "A", "ddsj": "08:00:00", "cfsj": "08:00:00", "track": "2"},
+                    {"zhanming": "B", "ddsj": "08:10:00", "cfsj": "08:10:00"},
+                    {"zhanming": "C", "ddsj": "08:30:00", "cfsj": "08:30:00"}
+                ]
+            },
+            {
+                "checi": ["", "", "G2"],
+                "timetable": [
+                    {"zhanming": "C", "ddsj": "23:50:00", "cfsj": "23:50:00"},
+                    {"zhanming": "A", "ddsj": "00:20:00", "cfsj": "00:20:00"}
+                ]
+            }
+        ],
+        "circuits": [
+            {
+                "name": "C1",
+                "order": [
+                    {"checi": "G1"},
+                    {"checi": "G2", "link": true},
+                    {"checi": "G9", "link": true, "virtual": true}
+                ]
+            }
+        ]
+    }"#;
+
+    fn metadata_text<'a>(train: &'a TrainHelper, key: &str) -> Option<&'a str> {
+        train.metadata.get(key)?.as_text()
+    }
+
+    #[test]
+    fn import_directions_and_circuits() {
+        let imported = import(FILE.as_bytes(), QetrcConfig { line: None }).unwrap();
+        assert!(imported.warnings.is_empty());
+        assert_eq!(station_names(&imported), ["A", "B", "C"]);
+        let network = imported.network;
+        // B is only passed by down trains, and up trains use the counter mileage
+        let intervals: Vec<(&str, &str, u32, Option<bool>)> = network
+            .intervals
+            .iter()
+            .map(|((from, to), interval)| {
+                (
+                    from.as_str(),
+                    to.as_str(),
+                    interval.length.meters(),
+                    interval.bidirectional,
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                ("A", "B", 10000, Some(false)),
+                ("B", "C", 15000, Some(false)),
+                ("C", "A", 24000, Some(false)),
+            ]
+        );
+
+        // trains without a full number are named after their down or up number
+        let (g1, g2) = (&network.trains[0], &network.trains[1]);
+        assert_eq!((g1.0.as_str(), g2.0.as_str()), ("G1", "G2"));
+        assert!(matches!(
+            &g1.1.schedule[0].track,
+            Some(TrackRef::Name(track)) if track == "2"
+        ));
+        assert!(g1.1.schedule[1].kind == Some(EntryKind::Pass));
+        // the arrival after midnight is on the next day
+        assert_eq!(g2.1.schedule[1].arrival, hm(24, 20));
+
+        assert_eq!(metadata_text(&g1.1, "circuit"), Some("C1"));
+        assert_eq!(metadata_text(&g1.1, "next"), Some("G2"));
+        assert_eq!(metadata_text(&g2.1, "previous"), Some("G1"));
+        assert_eq!(metadata_text(&g2.1, "next"), None);
+    }
+}
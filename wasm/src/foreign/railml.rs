@@ -0,0 +1,1213 @@
+use super::*;
+use anyhow::{Context, Result, anyhow};
+use roxmltree::Node;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Used when one of the stations of an interval has no mileage.
+const DEFAULT_INTERVAL_LENGTH: u32 = 1000;
+/// Elements of railML 3 itinerary points that refer to the track the train uses.
+const TRACK_REFERENCES: &[&str] = &["platformEdgeRef", "trackRef", "stopPostRef"];
+/// Elements of railML 3 trains that refer to the days they run on.
+const OPERATING_DAY_REFERENCES: &[&str] = &["operatingDayRef", "validityRef"];
+
+/// Options of the railML importer.
+#[derive(Deserialize)]
+pub struct RailmlConfig {
+    /// The scope of the times to read in railML 2, e.g. `scheduled` or `published`. The
+    /// scheduled times are read if `None`, or the first times if there are none.
+    time_scope: Option<String>,
+}
+
+/// An operation control point of railML 2, or an operational point of railML 3.
+struct Ocp {
+    id: String,
+    name: String,
+    /// Mileage in meters.
+    position: Option<f64>,
+    metadata: Metadata,
+}
+
+/// A stop of a train part, with the OCP still given by its ID.
+struct OcpTime {
+    ocp: String,
+    arrival: Option<Time>,
+    departure: Option<Time>,
+    kind: EntryKind,
+    track: Option<String>,
+}
+
+/// A train part of railML 2, or a base itinerary of railML 3.
+struct TrainPart {
+    schedule: Vec<OcpTime>,
+    calendar: Option<CalendarHelper>,
+    metadata: Metadata,
+}
+
+/// A train made of train parts, which are run one after another.
+struct RailmlTrain<'a> {
+    name: String,
+    parts: Vec<&'a str>,
+    metadata: Metadata,
+}
+
+/// Read the infrastructure and timetable of a railML 2 or railML 3 file.
+pub fn import(data: &[u8], config: RailmlConfig) -> Result<Imported> {
+    let content = std::str::from_utf8(data).context("The file is not valid UTF-8")?;
+    let document = roxmltree::Document::parse_with_options(
+        content,
+        roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )
+    .context("Failed to parse railML file")?;
+    let root = document.root_element();
+    let is_railml3 = match root.tag_name().name() {
+        "railml" => root
+            .attribute("version")
+            .is_some_and(|v| v.starts_with('3')),
+        "railML" => true,
+        other => return Err(anyhow!("The root element '{}' is not a railML root", other)),
+    };
+    let mut warnings = Vec::new();
+
+    let infrastructure = child(root, "infrastructure")
+        .ok_or_else(|| anyhow!("The file does not have any infrastructure"))?;
+    let timetable = child(root, "timetable");
+    for node in elements(root) {
+        if !matches!(
+            node.tag_name().name(),
+            "infrastructure" | "timetable" | "metadata"
+        ) {
+            unsupported(node, &mut warnings);
+        }
+    }
+
+    let (ocps, neighbours, parts, trains) = if is_railml3 {
+        let ocps = read_operational_points(infrastructure, &mut warnings);
+        let (parts, trains) = match timetable {
+            Some(timetable) => read_itineraries(timetable, infrastructure, &mut warnings)?,
+            None => Default::default(),
+        };
+        (ocps, Vec::new(), parts, trains)
+    } else {
+        let (ocps, neighbours) = read_ocps(infrastructure, &mut warnings);
+        let (parts, trains) = match timetable {
+            Some(timetable) => {
+                read_train_parts(timetable, config.time_scope.as_deref(), &mut warnings)?
+            }
+            None => Default::default(),
+        };
+        (ocps, neighbours, parts, trains)
+    };
+    if ocps.is_empty() {
+        return Err(anyhow!(
+            "The file does not have any operation control points"
+        ));
+    }
+
+    // stations
+    let mut seen_names = HashSet::new();
+    let mut station_names: HashMap<&str, String> = HashMap::new();
+    let mut station_tracks: HashMap<&str, Vec<String>> = HashMap::new();
+    for ocp in &ocps {
+        station_names.insert(
+            &ocp.id,
+            unique_name(&ocp.name, &mut seen_names, &mut warnings),
+        );
+    }
+    let positions: HashMap<&str, Option<f64>> = ocps
+        .iter()
+        .map(|ocp| (ocp.id.as_str(), ocp.position))
+        .collect();
+
+    // intervals come from OCPs next to each other on a track, and then from the OCPs trains run
+    // through one after another
+    let mut intervals: Vec<((String, String), IntervalHelper)> = Vec::new();
+    let mut interval_pairs: HashSet<(String, String)> = HashSet::new();
+    let mut missing_mileage = 0;
+    let mut add_interval = |from: &str, to: &str| {
+        let (Some(from_name), Some(to_name)) = (station_names.get(from), station_names.get(to))
+        else {
+            return;
+        };
+        let pair = if from < to { (from, to) } else { (to, from) };
+        if from == to || !interval_pairs.insert((pair.0.to_string(), pair.1.to_string())) {
+            return;
+        }
+        let length = match (positions[from], positions[to]) {
+            (Some(from), Some(to)) => (from - to).abs().round() as u32,
+            _ => {
+                missing_mileage += 1;
+                DEFAULT_INTERVAL_LENGTH
+            }
+        };
+        intervals.push((
+            (from_name.clone(), to_name.clone()),
+            IntervalHelper {
+                length: IntervalLength::new(length),
+                bidirectional: Some(true),
+            },
+        ));
+    };
+    for (from, to) in &neighbours {
+        add_interval(from, to);
+    }
+
+    // trains
+    let mut seen_trains = HashSet::new();
+    let mut imported_trains = Vec::new();
+    for train in trains {
+        let mut schedule: Vec<ScheduleEntryHelper> = Vec::new();
+        let mut previous_ocp: Option<&str> = None;
+        let mut unwrapper = DayUnwrapper::default();
+        let mut calendar = None;
+        let mut metadata = Metadata::new();
+        for part_id in &train.parts {
+            let Some(part) = parts.get(part_id) else {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::UnknownReference,
+                        format!(
+                            "Train '{}' refers to the unknown train part '{}', the part is skipped",
+                            train.name, part_id
+                        ),
+                    )
+                    .train(&train.name),
+                );
+                continue;
+            };
+            if calendar.is_none() {
+                calendar = part.calendar.clone();
+            }
+            for (key, value) in &part.metadata {
+                metadata.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            for entry in &part.schedule {
+                let Some(station) = station_names.get(entry.ocp.as_str()) else {
+                    warnings.push(Diagnostic::warning(ErrorCode::UnknownStation, format!(
+                        "Train '{}' refers to the unknown operation control point '{}', the \
+                         stop is skipped",
+                        train.name, entry.ocp
+                    )).train(&train.name));
+                    continue;
+                };
+                // points without times are still neighbours of the points around them
+                let same_point = previous_ocp == Some(entry.ocp.as_str());
+                if let Some(previous_ocp) = previous_ocp {
+                    add_interval(previous_ocp, &entry.ocp);
+                }
+                previous_ocp = Some(entry.ocp.as_str());
+                // passes without times could not be drawn
+                let (arrival, departure) = match (entry.arrival, entry.departure) {
+                    (Some(arrival), Some(departure)) => (arrival, departure),
+                    (Some(time), None) | (None, Some(time)) => (time, time),
+                    (None, None) => continue,
+                };
+                let arrival = unwrapper.unwrap(arrival);
+                let departure = unwrapper.unwrap(departure);
+                let track = entry.track.as_ref().map(|track| {
+                    let tracks = station_tracks.entry(entry.ocp.as_str()).or_default();
+                    if !tracks.contains(track) {
+                        tracks.push(track.clone());
+                    }
+                    TrackRef::Name(track.clone())
+                });
+                // parts of a train meet at the same point
+                if same_point
+                    && let Some(last) = schedule.last_mut()
+                    && last.station == *station
+                {
+                    last.departure = departure;
+                    last.kind = Some(entry.kind);
+                    continue;
+                }
+                schedule.push(ScheduleEntryHelper {
+                    arrival,
+                    departure,
+                    station: station.clone(),
+                    track,
+                    kind: Some(entry.kind),
+                });
+            }
+        }
+        if schedule.is_empty() {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!(
+                        "Train '{}' does not have any timed stops and is skipped",
+                        train.name
+                    ),
+                )
+                .train(&train.name),
+            );
+            continue;
+        }
+        for (key, value) in train.metadata {
+            metadata.insert(key, value);
+        }
+        imported_trains.push((
+            unique_name(&train.name, &mut seen_trains, &mut warnings),
+            TrainHelper {
+                frequency: None,
+                calendar,
+                label_size: Default::default(),
+                priority: None,
+                schedule,
+                metadata,
+            },
+        ));
+    }
+    if missing_mileage > 0 {
+        warnings.push(Diagnostic::warning(
+            ErrorCode::UnmeasuredInterval,
+            format!(
+                "{missing_mileage} intervals have a station without mileage, they are \
+             {DEFAULT_INTERVAL_LENGTH} m long"
+            ),
+        ));
+    }
+
+    let stations = ocps
+        .iter()
+        .map(|ocp| {
+            let tracks: Vec<TrackHelper> = station_tracks
+                .remove(ocp.id.as_str())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|name| TrackHelper { name, main: None })
+                .collect();
+            (
+                station_names[ocp.id.as_str()].clone(),
+                StationHelper {
+                    label_size: Default::default(),
+                    tracks: (!tracks.is_empty()).then_some(TracksHelper::List(tracks)),
+                    metadata: ocp.metadata.clone(),
+                },
+            )
+        })
+        .collect();
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations,
+            trains: imported_trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Read the OCPs of railML 2, with their mileage from the track topology, and the pairs of OCPs
+/// that are next to each other on a track.
+fn read_ocps(
+    infrastructure: Node,
+    warnings: &mut Vec<Diagnostic>,
+) -> (Vec<Ocp>, Vec<(String, String)>) {
+    for node in elements(infrastructure) {
+        if !matches!(
+            node.tag_name().name(),
+            "tracks" | "operationControlPoints" | "metadata"
+        ) {
+            unsupported(node, warnings);
+        }
+    }
+
+    // the first mileage found for each OCP
+    let mut positions: HashMap<&str, f64> = HashMap::new();
+    let mut neighbours: Vec<(String, String)> = Vec::new();
+    for topology in infrastructure
+        .descendants()
+        .filter(|node| node.has_tag_name("trackTopology"))
+    {
+        let begin_position = child(topology, "trackBegin")
+            .and_then(|begin| begin.attribute("absPos"))
+            .and_then(|position| position.parse::<f64>().ok());
+        let mut track_ocps: Vec<(f64, &str)> = Vec::new();
+        for node in topology.descendants() {
+            let ocp_ref = match node.tag_name().name() {
+                "macroscopicNode" | "crossSection" => node.attribute("ocpRef"),
+                _ => None,
+            };
+            let Some(ocp_ref) = ocp_ref else { continue };
+            // macroscopic nodes are placed at the ends of the track
+            let position_node = if node.has_tag_name("macroscopicNode") {
+                node.parent_element().unwrap_or(node)
+            } else {
+                node
+            };
+            let position = position_node
+                .attribute("absPos")
+                .and_then(|position| position.parse::<f64>().ok())
+                .or_else(|| {
+                    let relative: f64 = position_node.attribute("pos")?.parse().ok()?;
+                    Some(begin_position? + relative)
+                });
+            if let Some(position) = position {
+                positions.entry(ocp_ref).or_insert(position);
+                track_ocps.push((position, ocp_ref));
+            }
+        }
+        track_ocps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        track_ocps.dedup_by_key(|(_, ocp_ref)| *ocp_ref);
+        neighbours.extend(
+            track_ocps
+                .windows(2)
+                .map(|pair| (pair[0].1.to_string(), pair[1].1.to_string())),
+        );
+    }
+
+    let ocps = infrastructure
+        .descendants()
+        .filter(|node| node.has_tag_name("ocp"))
+        .filter_map(|node| {
+            let id = node.attribute("id")?;
+            let name = node
+                .attribute("name")
+                .or_else(|| child(node, "name").and_then(|name| name.attribute("name")))
+                .or(node.attribute("code"))
+                .unwrap_or(id);
+            let mut metadata = Metadata::new();
+            metadata.insert("railml_id".to_string(), text(id));
+            if let Some(code) = node.attribute("code") {
+                metadata.insert("code".to_string(), text(code));
+            }
+            if let Some(operational_type) =
+                child(node, "propOperational").and_then(|prop| prop.attribute("operationalType"))
+            {
+                metadata.insert("type".to_string(), text(operational_type));
+            }
+            if let Some((lat, lon)) = child(node, "geoCoord")
+                .and_then(|coord| coord.attribute("coord"))
+                .and_then(|coord| coord.split_once(' '))
+                .and_then(|(lat, lon)| Some((lat.parse().ok()?, lon.trim().parse().ok()?)))
+            {
+                metadata.insert("lat".to_string(), ciborium::Value::Float(lat));
+                metadata.insert("lon".to_string(), ciborium::Value::Float(lon));
+            }
+            Some(Ocp {
+                id: id.to_string(),
+                name: name.to_string(),
+                position: positions.get(id).copied(),
+                metadata,
+            })
+        })
+        .collect();
+    (ocps, neighbours)
+}
+
+/// Read the train parts and trains of railML 2. Operational trains are preferred over
+/// commercial trains, which usually describe the same runs. Train parts that are not used by
+/// any train are read as trains of their own.
+fn read_train_parts<'a>(
+    timetable: Node<'a, '_>,
+    time_scope: Option<&str>,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(HashMap<&'a str, TrainPart>, Vec<RailmlTrain<'a>>)> {
+    for node in elements(timetable) {
+        if !matches!(
+            node.tag_name().name(),
+            "timetablePeriods"
+                | "operatingPeriods"
+                | "categories"
+                | "trainParts"
+                | "trains"
+                | "metadata"
+        ) {
+            unsupported(node, warnings);
+        }
+    }
+
+    let periods: HashMap<&str, (Option<Date>, Option<Date>)> = timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("timetablePeriod"))
+        .filter_map(|node| {
+            Some((
+                node.attribute("id")?,
+                (
+                    parse_date(node.attribute("startDate")),
+                    parse_date(node.attribute("endDate")),
+                ),
+            ))
+        })
+        .collect();
+    let calendars: HashMap<&str, CalendarHelper> = timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("operatingPeriod"))
+        .filter_map(|node| Some((node.attribute("id")?, read_operating_period(node, &periods))))
+        .collect();
+    let categories: HashMap<&str, &str> = timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("category"))
+        .filter_map(|node| {
+            let name = node
+                .attribute("code")
+                .or(node.attribute("name"))
+                .or(node.attribute("id"))?;
+            Some((node.attribute("id")?, name))
+        })
+        .collect();
+
+    let mut parts = HashMap::new();
+    let mut part_order = Vec::new();
+    for node in timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("trainPart"))
+    {
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        let mut ocp_times: Vec<(u32, Node)> = node
+            .descendants()
+            .filter(|node| node.has_tag_name("ocpTT"))
+            .enumerate()
+            .map(|(idx, node)| {
+                let sequence = node
+                    .attribute("sequence")
+                    .and_then(|sequence| sequence.parse().ok())
+                    .unwrap_or(idx as u32);
+                (sequence, node)
+            })
+            .collect();
+        ocp_times.sort_by_key(|(sequence, _)| *sequence);
+        let schedule = ocp_times
+            .into_iter()
+            .map(|(_, node)| {
+                let times = pick_times(node, time_scope);
+                let time = |attribute: &str, day: &str| -> Result<Option<Time>> {
+                    let Some(value) = times.and_then(|times| times.attribute(attribute)) else {
+                        return Ok(None);
+                    };
+                    let days: i32 = times
+                        .and_then(|times| times.attribute(day))
+                        .and_then(|day| day.parse().ok())
+                        .unwrap_or(0);
+                    Ok(Some(parse_time(value)? + Time::new(days * 86400)))
+                };
+                let entry = || -> Result<OcpTime> {
+                    let stop_description = child(node, "stopDescription");
+                    let kind = if node.attribute("ocpType") == Some("pass") {
+                        EntryKind::Pass
+                    } else if stop_description
+                        .and_then(|stop| stop.attribute("commercial"))
+                        .is_some_and(|commercial| commercial == "false")
+                    {
+                        EntryKind::TechnicalStop
+                    } else {
+                        EntryKind::Stop
+                    };
+                    Ok(OcpTime {
+                        ocp: node.attribute("ocpRef").unwrap_or_default().to_string(),
+                        arrival: time("arrival", "arrivalDay")?,
+                        departure: time("departure", "departureDay")?,
+                        kind,
+                        track: node.attribute("trackInfo").map(str::to_string),
+                    })
+                };
+                entry().with_context(|| format!("Failed to read the times of train part '{id}'"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut metadata = Metadata::new();
+        metadata.insert("railml_id".to_string(), text(id));
+        if let Some(category) = node
+            .attribute("categoryRef")
+            .and_then(|category| categories.get(category))
+        {
+            metadata.insert("type".to_string(), text(*category));
+        }
+        if let Some(line) = node.attribute("line") {
+            metadata.insert("line".to_string(), text(line));
+        }
+        let calendar = child(node, "operatingPeriodRef")
+            .and_then(|period| period.attribute("ref"))
+            .or(node.attribute("operatingPeriodRef"))
+            .and_then(|period| calendars.get(period))
+            .cloned();
+        part_order.push(id);
+        parts.insert(
+            id,
+            TrainPart {
+                schedule,
+                calendar,
+                metadata,
+            },
+        );
+    }
+
+    let train_nodes: Vec<Node> = timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("train"))
+        .collect();
+    let has_operational = train_nodes
+        .iter()
+        .any(|node| node.attribute("type") == Some("operational"));
+    let mut used_parts = HashSet::new();
+    let mut trains = Vec::new();
+    for node in train_nodes {
+        if has_operational && node.attribute("type") != Some("operational") {
+            continue;
+        }
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        let mut sequences: Vec<(u32, Vec<&str>)> = node
+            .children()
+            .filter(|node| node.has_tag_name("trainPartSequence"))
+            .enumerate()
+            .map(|(idx, sequence)| {
+                let order = sequence
+                    .attribute("sequence")
+                    .and_then(|order| order.parse().ok())
+                    .unwrap_or(idx as u32);
+                let refs = sequence
+                    .children()
+                    .filter(|node| node.has_tag_name("trainPartRef"))
+                    .filter_map(|node| node.attribute("ref"))
+                    .collect();
+                (order, refs)
+            })
+            .collect();
+        sequences.sort_by_key(|(order, _)| *order);
+        let parts: Vec<&str> = sequences.into_iter().flat_map(|(_, refs)| refs).collect();
+        used_parts.extend(parts.iter().copied());
+        let mut metadata = Metadata::new();
+        metadata.insert("railml_id".to_string(), text(id));
+        trains.push(RailmlTrain {
+            name: node
+                .attribute("trainNumber")
+                .or(node.attribute("name"))
+                .unwrap_or(id)
+                .to_string(),
+            parts,
+            metadata,
+        });
+    }
+    for part_id in part_order {
+        if used_parts.contains(part_id) {
+            continue;
+        }
+        let node = timetable
+            .descendants()
+            .find(|node| node.has_tag_name("trainPart") && node.attribute("id") == Some(part_id));
+        let name = node
+            .and_then(|node| node.attribute("trainNumber").or(node.attribute("name")))
+            .unwrap_or(part_id);
+        trains.push(RailmlTrain {
+            name: name.to_string(),
+            parts: vec![part_id],
+            metadata: Metadata::new(),
+        });
+    }
+    Ok((parts, trains))
+}
+
+/// Pick the times element of an `ocpTT` with the given scope, or the scheduled times.
+fn pick_times<'a, 'input>(
+    ocp_time: Node<'a, 'input>,
+    time_scope: Option<&str>,
+) -> Option<Node<'a, 'input>> {
+    let times: Vec<Node> = ocp_time
+        .children()
+        .filter(|node| node.has_tag_name("times"))
+        .collect();
+    let scoped = times
+        .iter()
+        .find(|node| node.attribute("scope") == Some(time_scope.unwrap_or("scheduled")));
+    match time_scope {
+        Some(_) => scoped.copied(),
+        None => scoped.or(times.first()).copied(),
+    }
+}
+
+/// Read an operating period of railML 2. A bitmask lists every day from the start date, and an
+/// operating code lists the weekdays from Monday to Sunday.
+fn read_operating_period(
+    node: Node,
+    periods: &HashMap<&str, (Option<Date>, Option<Date>)>,
+) -> CalendarHelper {
+    let period = node
+        .attribute("timetablePeriodRef")
+        .and_then(|period| periods.get(period))
+        .copied()
+        .unwrap_or_default();
+    let start = parse_date(node.attribute("startDate")).or(period.0);
+    let end = parse_date(node.attribute("endDate")).or(period.1);
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for special in node
+        .children()
+        .filter(|node| node.has_tag_name("specialService"))
+    {
+        let Some(date) = parse_date(special.attribute("singleDate")) else {
+            continue;
+        };
+        match special.attribute("type") {
+            Some("include") => include.push(date),
+            Some("exclude") => exclude.push(date),
+            _ => {}
+        }
+    }
+    if let (Some(bitmask), Some(start)) = (node.attribute("bitMask"), start) {
+        include.extend(
+            bitmask
+                .chars()
+                .enumerate()
+                .filter(|(_, bit)| *bit == '1')
+                .map(|(idx, _)| start.add_days(idx as i32)),
+        );
+        let include: BTreeSet<Date> = include.into_iter().collect();
+        return CalendarHelper {
+            weekdays: Some(0),
+            date_ranges: Vec::new(),
+            include: include.into_iter().collect(),
+            exclude,
+            holidays: Vec::new(),
+        };
+    }
+    let weekdays = node
+        .attribute("operatingCode")
+        .or_else(|| child(node, "operatingDay").and_then(|day| day.attribute("operatingCode")))
+        .map(|code| {
+            code.chars()
+                .take(7)
+                .enumerate()
+                .filter(|(_, bit)| *bit == '1')
+                .fold(0, |weekdays, (idx, _)| weekdays | (1 << idx))
+        });
+    CalendarHelper {
+        weekdays,
+        date_ranges: match (start, end) {
+            (Some(start), Some(end)) => vec![(start, end)],
+            _ => Vec::new(),
+        },
+        include,
+        exclude,
+        holidays: Vec::new(),
+    }
+}
+
+/// Read the operational points of railML 3, with their mileage from their spot locations.
+fn read_operational_points(infrastructure: Node, warnings: &mut Vec<Diagnostic>) -> Vec<Ocp> {
+    for node in elements(infrastructure) {
+        if !matches!(
+            node.tag_name().name(),
+            "topology" | "functionalInfrastructure" | "metadata"
+        ) {
+            unsupported(node, warnings);
+        }
+    }
+    // positioning systems in kilometers
+    let kilometers: HashSet<&str> = infrastructure
+        .descendants()
+        .filter(|node| {
+            node.has_tag_name("linearPositioningSystem") && node.attribute("units") == Some("km")
+        })
+        .filter_map(|node| node.attribute("id"))
+        .collect();
+
+    infrastructure
+        .descendants()
+        .filter(|node| node.has_tag_name("operationalPoint"))
+        .filter_map(|node| {
+            let id = node.attribute("id")?;
+            let name = child(node, "name")
+                .and_then(|name| name.attribute("name"))
+                .or(node.attribute("name"))
+                .unwrap_or(id);
+            let position = node
+                .descendants()
+                .find(|node| node.has_tag_name("linearCoordinate"))
+                .and_then(|coordinate| {
+                    let measure: f64 = coordinate.attribute("measure")?.parse().ok()?;
+                    let scale = match coordinate.attribute("positioningSystemRef") {
+                        Some(system) if kilometers.contains(system) => 1000.0,
+                        _ => 1.0,
+                    };
+                    Some(measure * scale)
+                });
+            let mut metadata = Metadata::new();
+            metadata.insert("railml_id".to_string(), text(id));
+            if let Some(code) = node
+                .descendants()
+                .find(|node| node.has_tag_name("designator"))
+                .and_then(|designator| designator.attribute("entry"))
+            {
+                metadata.insert("code".to_string(), text(code));
+            }
+            Some(Ocp {
+                id: id.to_string(),
+                name: name.to_string(),
+                position,
+                metadata,
+            })
+        })
+        .collect()
+}
+
+/// Read the base itineraries and operational trains of railML 3. The tracks of the points are
+/// named after the infrastructure elements they refer to. Operating days are not read yet, and
+/// trains that refer to them are drawn on every day, with a warning.
+fn read_itineraries<'a>(
+    timetable: Node<'a, '_>,
+    infrastructure: Node,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(HashMap<&'a str, TrainPart>, Vec<RailmlTrain<'a>>)> {
+    for node in elements(timetable) {
+        if !matches!(
+            node.tag_name().name(),
+            "baseItineraries"
+                | "itineraries"
+                | "operationalTrains"
+                | "commercialTrains"
+                | "categories"
+                | "metadata"
+        ) {
+            unsupported(node, warnings);
+        }
+    }
+
+    let mut parts = HashMap::new();
+    let mut part_order = Vec::new();
+    for node in timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("baseItinerary"))
+    {
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        let schedule = node
+            .children()
+            .filter(|node| node.has_tag_name("baseItineraryPoint"))
+            .map(|point| {
+                let time = |name: &str| -> Result<Option<Time>> {
+                    let Some(element) = child(point, name) else {
+                        return Ok(None);
+                    };
+                    let Some(value) = element.attribute("time") else {
+                        return Ok(None);
+                    };
+                    let days: i32 = element
+                        .attribute("dayOffset")
+                        .and_then(|day| day.parse().ok())
+                        .unwrap_or(0);
+                    Ok(Some(parse_time(value)? + Time::new(days * 86400)))
+                };
+                let passing = time("passing")?;
+                let kind = if passing.is_some() {
+                    EntryKind::Pass
+                } else if child(point, "stopDescription")
+                    .and_then(|stop| stop.attribute("commercial"))
+                    .is_some_and(|commercial| commercial == "false")
+                {
+                    EntryKind::TechnicalStop
+                } else {
+                    EntryKind::Stop
+                };
+                let track = point
+                    .descendants()
+                    .find(|node| TRACK_REFERENCES.iter().any(|name| node.has_tag_name(*name)))
+                    .and_then(|reference| reference.attribute("ref"))
+                    .map(|reference| infrastructure_name(infrastructure, reference));
+                Ok(OcpTime {
+                    ocp: point
+                        .attribute("locationRef")
+                        .unwrap_or_default()
+                        .to_string(),
+                    arrival: time("arrival")?.or(passing),
+                    departure: time("departure")?.or(passing),
+                    kind,
+                    track,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read the times of base itinerary '{id}'"))?;
+        let mut metadata = Metadata::new();
+        metadata.insert("railml_id".to_string(), text(id));
+        part_order.push(id);
+        parts.insert(
+            id,
+            TrainPart {
+                schedule,
+                calendar: None,
+                metadata,
+            },
+        );
+    }
+
+    let itineraries: HashMap<&str, Vec<&str>> = timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("itinerary"))
+        .filter_map(|node| {
+            let ranges = node
+                .children()
+                .filter(|node| node.has_tag_name("itineraryRange"))
+                .filter_map(|range| range.attribute("baseItineraryRef"))
+                .collect();
+            Some((node.attribute("id")?, ranges))
+        })
+        .collect();
+    let mut used_parts = HashSet::new();
+    let mut trains = Vec::new();
+    for node in timetable
+        .descendants()
+        .filter(|node| node.has_tag_name("operationalTrain"))
+    {
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        let name = child(node, "name")
+            .and_then(|name| name.attribute("name"))
+            .or(node.attribute("name"))
+            .unwrap_or(id);
+        for variant in node
+            .children()
+            .filter(|node| node.has_tag_name("operationalTrainVariant"))
+        {
+            let Some(itinerary) = variant.attribute("itineraryRef") else {
+                continue;
+            };
+            let Some(parts) = itineraries.get(itinerary) else {
+                warnings.push(Diagnostic::warning(ErrorCode::UnknownReference, format!(
+                    "Train '{name}' refers to the unknown itinerary '{itinerary}', the variant \
+                     is skipped"
+                )).train(name));
+                continue;
+            };
+            used_parts.extend(parts.iter().copied());
+            if [node, variant].iter().any(|node| {
+                node.children().any(|node| {
+                    OPERATING_DAY_REFERENCES
+                        .iter()
+                        .any(|name| node.has_tag_name(*name))
+                })
+            }) {
+                warnings.push(
+                    Diagnostic::warning(
+                        ErrorCode::UnsupportedFeature,
+                        format!(
+                            "The operating days of train '{name}' are not supported, the train \
+                             is drawn on every day"
+                        ),
+                    )
+                    .train(name),
+                );
+            }
+            let mut metadata = Metadata::new();
+            metadata.insert("railml_id".to_string(), text(id));
+            trains.push(RailmlTrain {
+                name: name.to_string(),
+                parts: parts.clone(),
+                metadata,
+            });
+        }
+    }
+    for part_id in part_order {
+        if !used_parts.contains(part_id) {
+            trains.push(RailmlTrain {
+                name: part_id.to_string(),
+                parts: vec![part_id],
+                metadata: Metadata::new(),
+            });
+        }
+    }
+    Ok((parts, trains))
+}
+
+/// The name of an element of the railML 3 infrastructure, or its ID if it has no name.
+fn infrastructure_name(infrastructure: Node, id: &str) -> String {
+    infrastructure
+        .descendants()
+        .find(|node| node.attribute("id") == Some(id))
+        .and_then(|node| {
+            child(node, "name")
+                .and_then(|name| name.attribute("name"))
+                .or_else(|| child(node, "designator").and_then(|it| it.attribute("entry")))
+        })
+        .unwrap_or(id)
+        .to_string()
+}
+
+/// Warn about an element that is not imported, once per kind of element.
+fn unsupported(node: Node, warnings: &mut Vec<Diagnostic>) {
+    let warning = format!(
+        "The element '{}' is not supported and is skipped",
+        node.tag_name().name()
+    );
+    if !warnings.iter().any(|known| known.message == warning) {
+        warnings.push(Diagnostic::warning(ErrorCode::UnsupportedFeature, warning));
+    }
+}
+
+/// The child elements of a node.
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+/// The first child element with the given name, ignoring namespaces.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|node| node.has_tag_name(name))
+}
+
+/// Parse an `xs:time`, ignoring fractions of seconds.
+fn parse_time(value: &str) -> Result<Time> {
+    let value = value.split('.').next().unwrap_or(value);
+    value.parse()
+}
+
+fn parse_date(value: Option<&str>) -> Option<Date> {
+    value?.get(..10)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const RAILML_2: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<railml xmlns="http://www.railml.org/schemas/2013" version="2.2">
+  <infrastructure id="is">
+    <tracks>
+      <track id="t1" name="main">
+        <trackTopology>
+          <trackBegin id="tb1" pos="0" absPos="1200"><macroscopicNode ocpRef="A"/></trackBegin>
+          <trackEnd id="te1" pos="20000" absPos="21200"><macroscopicNode ocpRef="C"/></trackEnd>
+          <crossSections><crossSection id="cs1" pos="8500" ocpRef="B"/></crossSections>
+        </trackTopology>
+      </track>
+    </tracks>
+    <operationControlPoints>
+      <ocp id="A" name="Alpha" code="AL"><propOperational operationalType="station"/><geoCoord coord="48.1 11.5"/></ocp>
+      <ocp id="B" name="Beta"/>
+      <ocp id="C" name="Gamma"/>
+      <ocp id="D" name="Delta"/>
+    </operationControlPoints>
+    <speedProfiles/>
+  </infrastructure>
+  <rollingstock/>
+  <timetable id="tt">
+    <timetablePeriods><timetablePeriod id="p" startDate="2025-01-01" endDate="2025-12-31"/></timetablePeriods>
+    <operatingPeriods>
+      <operatingPeriod id="wd" operatingCode="1111100" timetablePeriodRef="p"><specialService type="exclude" singleDate="2025-05-01"/></operatingPeriod>
+      <operatingPeriod id="bm" startDate="2025-03-01" bitMask="1010"/>
+    </operatingPeriods>
+    <categories><category id="cat1" code="RE" name="Regional Express"/></categories>
+    <trainParts>
+      <trainPart id="tp1" categoryRef="cat1">
+        <operatingPeriodRef ref="wd"/>
+        <ocpsTT>
+          <ocpTT ocpRef="A" sequence="1" ocpType="begin" trackInfo="2"><times scope="scheduled" departure="23:50:00"/></ocpTT>
+          <ocpTT ocpRef="B" sequence="2" ocpType="pass"><times scope="scheduled" arrival="23:58:30" departure="23:58:30"/></ocpTT>
+          <ocpTT ocpRef="C" sequence="3" ocpType="stop"><times scope="scheduled" arrival="00:05:00" departure="00:07:00" arrivalDay="1" departureDay="1"/></ocpTT>
+        </ocpsTT>
+      </trainPart>
+      <trainPart id="tp2">
+        <ocpsTT>
+          <ocpTT ocpRef="C" sequence="1"><times scope="scheduled" arrival="00:08:00" departure="00:09:00" arrivalDay="1" departureDay="1"/><stopDescription commercial="false"/></ocpTT>
+          <ocpTT ocpRef="D" sequence="2" ocpType="end"><times scope="scheduled" arrival="00:20:00" arrivalDay="1"/></ocpTT>
+        </ocpsTT>
+      </trainPart>
+      <trainPart id="tp3" trainNumber="99">
+        <operatingPeriodRef ref="bm"/>
+        <ocpsTT>
+          <ocpTT ocpRef="C"><times scope="published" departure="10:00"/></ocpTT>
+          <ocpTT ocpRef="X"><times scope="published" arrival="10:30"/></ocpTT>
+          <ocpTT ocpRef="A"><times scope="published" arrival="10:40"/></ocpTT>
+        </ocpsTT>
+      </trainPart>
+    </trainParts>
+    <trains>
+      <train id="tr1" type="operational" trainNumber="4711"><trainPartSequence sequence="1"><trainPartRef ref="tp1"/></trainPartSequence><trainPartSequence sequence="2"><trainPartRef ref="tp2"/></trainPartSequence></train>
+      <train id="tr1c" type="commercial" trainNumber="RE 4711"><trainPartSequence sequence="1"><trainPartRef ref="tp1"/></trainPartSequence></train>
+    </trains>
+    <rosterings/>
+  </timetable>
+</railml>"#;
+
+    const RAILML_3: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<railML xmlns="https://www.railml.org/schemas/3.2" version="3.2">
+  <infrastructure id="is">
+    <topology><netElements/><positioningSystems><linearPositioningSystem id="lps" units="km"/></positioningSystems></topology>
+    <functionalInfrastructure>
+      <operationalPoints>
+        <operationalPoint id="op1"><name name="North" language="en"/><designator register="_x" entry="NO"/><spotLocation id="s1" netElementRef="ne1" intrinsicCoord="0"><linearCoordinate positioningSystemRef="lps" measure="3.5"/></spotLocation></operationalPoint>
+        <operationalPoint id="op2"><name name="South" language="en"/><spotLocation id="s2" netElementRef="ne1" intrinsicCoord="1"><linearCoordinate positioningSystemRef="lps" measure="15.25"/></spotLocation></operationalPoint>
+      </operationalPoints>
+      <platformEdges>
+        <platformEdge id="pe1"><name name="Gleis 3" language="de"/></platformEdge>
+      </platformEdges>
+    </functionalInfrastructure>
+    <infrastructureVisualizations/>
+  </infrastructure>
+  <timetable>
+    <operatingDays/>
+    <baseItineraries>
+      <baseItinerary id="bi1">
+        <baseItineraryPoint id="p1" locationRef="op1"><departure time="08:00:00"/></baseItineraryPoint>
+        <baseItineraryPoint id="p2" locationRef="op2"><arrival time="08:12:00"/><stopDescription><platformEdgeRef ref="pe1"/></stopDescription></baseItineraryPoint>
+      </baseItinerary>
+    </baseItineraries>
+    <itineraries><itinerary id="it1"><itineraryRange baseItineraryRef="bi1"/></itinerary></itineraries>
+    <operationalTrains><operationalTrain id="ot1"><name name="IC 1" language="en"/><operationalTrainVariant id="v1" itineraryRef="it1"><validityRef ref="od1"/></operationalTrainVariant></operationalTrain></operationalTrains>
+  </timetable>
+</railML>"#;
+
+    #[test]
+    fn import_railml_2() {
+        let imported = import(RAILML_2.as_bytes(), RailmlConfig { time_scope: None }).unwrap();
+        assert_eq!(
+            codes(&imported),
+            [
+                ErrorCode::UnsupportedFeature,
+                ErrorCode::UnsupportedFeature,
+                ErrorCode::UnsupportedFeature,
+                ErrorCode::UnknownStation,
+                ErrorCode::UnmeasuredInterval,
+            ]
+        );
+        assert_eq!(
+            station_names(&imported),
+            ["Alpha", "Beta", "Gamma", "Delta"]
+        );
+        let alpha = &imported.network.stations[0].1;
+        assert_eq!(
+            alpha.metadata.get("code").and_then(|code| code.as_text()),
+            Some("AL")
+        );
+        assert!(alpha.metadata.contains_key("lat"));
+
+        // mileages come from the track topology
+        assert_eq!(interval_length(&imported, "Alpha", "Beta"), 8500);
+        assert_eq!(interval_length(&imported, "Beta", "Gamma"), 11500);
+        assert_eq!(
+            interval_length(&imported, "Gamma", "Delta"),
+            DEFAULT_INTERVAL_LENGTH
+        );
+
+        // the train parts of a train are joined, and the days of the times are added to them
+        let joined = train(&imported, "4711");
+        let stations: Vec<&str> = joined
+            .schedule
+            .iter()
+            .map(|entry| entry.station.as_str())
+            .collect();
+        assert_eq!(stations, ["Alpha", "Beta", "Gamma", "Delta"]);
+        assert!(matches!(&joined.schedule[0].track, Some(TrackRef::Name(name)) if name == "2"));
+        assert!(matches!(joined.schedule[1].kind, Some(EntryKind::Pass)));
+        assert_eq!(joined.schedule[2].arrival, hm(24, 5));
+        // where the parts meet, the train departs with the second part
+        assert_eq!(joined.schedule[2].departure, hm(24, 9));
+        assert_eq!(joined.schedule[3].arrival, hm(24, 20));
+        // stops that are not for passengers are technical stops
+        assert!(matches!(
+            joined.schedule[2].kind,
+            Some(EntryKind::TechnicalStop)
+        ));
+        let calendar = joined.calendar.as_ref().unwrap();
+        assert_eq!(calendar.weekdays, Some(0b11111));
+        assert_eq!(calendar.exclude, [Date::from_ymd(2025, 5, 1).unwrap()]);
+
+        // bit masks count days from the start date
+        let masked = train(&imported, "99");
+        assert_eq!(masked.schedule.len(), 2);
+        assert_eq!(
+            masked.calendar.as_ref().unwrap().include,
+            [
+                Date::from_ymd(2025, 3, 1).unwrap(),
+                Date::from_ymd(2025, 3, 3).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn import_railml_2_time_scope() {
+        let imported = import(
+            RAILML_2.as_bytes(),
+            RailmlConfig {
+                time_scope: Some("published".to_string()),
+            },
+        )
+        .unwrap();
+        // train 99 only has published times
+        assert_eq!(train(&imported, "99").schedule[1].arrival, hm(10, 40));
+    }
+
+    #[test]
+    fn import_railml_3() {
+        let imported = import(RAILML_3.as_bytes(), RailmlConfig { time_scope: None }).unwrap();
+        assert_eq!(station_names(&imported), ["North", "South"]);
+        // linear coordinates are in kilometers
+        assert_eq!(interval_length(&imported, "North", "South"), 11750);
+        let train = train(&imported, "IC 1");
+        assert_eq!(train.schedule[0].departure, hm(8, 0));
+        assert_eq!(train.schedule[1].arrival, hm(8, 12));
+        // tracks are named after the platform edges
+        assert!(train.schedule[0].track.is_none());
+        assert!(
+            matches!(&train.schedule[1].track, Some(TrackRef::Name(name)) if name == "Gleis 3")
+        );
+        let tracks: Vec<&str> = match &imported.network.stations[1].1.tracks {
+            Some(TracksHelper::List(tracks)) => {
+                tracks.iter().map(|track| track.name.as_str()).collect()
+            }
+            _ => panic!("South should have a list of tracks"),
+        };
+        assert_eq!(tracks, ["Gleis 3"]);
+        // operating days are reported, and the train runs on every day
+        assert!(train.calendar.is_none());
+        assert!(imported.warnings.iter().any(|warning| {
+            warning.code == ErrorCode::UnsupportedFeature
+                && warning.train.as_deref() == Some("IC 1")
+        }));
+    }
+
+    #[test]
+    fn import_untimed_ocps() {
+        let file = r#"<railml xmlns="http://www.railml.org/schemas/2013" version="2.2">
+  <infrastructure id="is">
+    <operationControlPoints>
+      <ocp id="A" name="Alpha"/>
+      <ocp id="B" name="Beta"/>
+      <ocp id="C" name="Gamma"/>
+    </operationControlPoints>
+  </infrastructure>
+  <timetable id="tt">
+    <trainParts>
+      <trainPart id="tp1" trainNumber="1">
+        <ocpsTT>
+          <ocpTT ocpRef="A"><times scope="scheduled" departure="08:00"/></ocpTT>
+          <ocpTT ocpRef="B" ocpType="pass"/>
+          <ocpTT ocpRef="C"><times scope="scheduled" arrival="08:20"/></ocpTT>
+        </ocpsTT>
+      </trainPart>
+    </trainParts>
+  </timetable>
+</railml>"#;
+        let imported = import(file.as_bytes(), RailmlConfig { time_scope: None }).unwrap();
+        // the pass without times is left out of the schedule, but not out of the line
+        assert_eq!(train(&imported, "1").schedule.len(), 2);
+        let pairs: Vec<(&str, &str)> = imported
+            .network
+            .intervals
+            .iter()
+            .map(|((from, to), _)| (from.as_str(), to.as_str()))
+            .collect();
+        assert_eq!(pairs, [("Alpha", "Beta"), ("Beta", "Gamma")]);
+    }
+
+    #[test]
+    fn import_infrastructure_without_trains() {
+        let end = RAILML_2.find("  <rollingstock/>").unwrap();
+        let file = format!("{}</railml>", &RAILML_2[..end]);
+        let imported = import(file.as_bytes(), RailmlConfig { time_scope: None }).unwrap();
+        assert!(imported.network.trains.is_empty());
+        // intervals come from the track topology
+        assert_eq!(interval_length(&imported, "Alpha", "Beta"), 8500);
+        assert_eq!(interval_length(&imported, "Beta", "Gamma"), 11500);
+        assert_eq!(imported.network.intervals.len(), 2);
+    }
+}
@@ -0,0 +1,699 @@
+use super::csv;
+use super::*;
+use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Used when one of the stations of an interval has no mileage.
+const DEFAULT_INTERVAL_LENGTH: u32 = 1000;
+
+/// Options of the spreadsheet reader and writer.
+#[derive(Deserialize)]
+pub struct SpreadsheetConfig {
+    /// The delimiter of the fields. When reading, tabs are used if the first row has one, and
+    /// commas otherwise. Commas are written if `None`.
+    delimiter: Option<char>,
+    /// Formats of the times, tried in order. `%H`, `%M` and `%S` stand for the hours, minutes
+    /// and seconds. Times are written in the first format.
+    #[serde(default = "default_time_formats")]
+    time_formats: Vec<String>,
+    /// Marks cells of stations that are passed. They could be written with or without a time.
+    #[serde(default = "default_pass_markers")]
+    pass_markers: Vec<String>,
+    /// Marks cells of stations where the train does not run. Empty cells are treated the same.
+    #[serde(default = "default_not_running_markers")]
+    not_running_markers: Vec<String>,
+    /// Labels of the arrival sub-rows.
+    #[serde(default = "default_arrival_labels")]
+    arrival_labels: Vec<String>,
+    /// Labels of the departure sub-rows.
+    #[serde(default = "default_departure_labels")]
+    departure_labels: Vec<String>,
+    /// The header of the column with the mileage of the stations, in kilometers.
+    mileage_column: Option<String>,
+    /// The stations to write, from the top to the bottom. Found from the trains if empty.
+    #[serde(default)]
+    stations: Vec<String>,
+    /// Write the stations from the bottom to the top, e.g. for up trains.
+    #[serde(default)]
+    reverse: bool,
+}
+
+fn default_time_formats() -> Vec<String> {
+    ["%H:%M", "%H:%M:%S", "%H%M"].map(String::from).to_vec()
+}
+
+fn default_pass_markers() -> Vec<String> {
+    ["|", "↓", "↑", "レ"].map(String::from).to_vec()
+}
+
+fn default_not_running_markers() -> Vec<String> {
+    ["-", "‥", "…", "||", "="].map(String::from).to_vec()
+}
+
+fn default_arrival_labels() -> Vec<String> {
+    ["arr", "arr.", "着", "到"].map(String::from).to_vec()
+}
+
+fn default_departure_labels() -> Vec<String> {
+    ["dep", "dep.", "発", "开"].map(String::from).to_vec()
+}
+
+/// A timetable written as a spreadsheet, and the problems found while writing it.
+#[derive(Serialize)]
+pub struct ExportedTable {
+    pub table: String,
+    /// Trains or parts of trains that could not be written.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Which times a row has.
+#[derive(Clone, Copy, PartialEq)]
+enum RowKind {
+    Arrival,
+    Departure,
+    Both,
+}
+
+/// A row of the table, after its station and kind are found.
+struct TableRow<'a> {
+    line: usize,
+    station: &'a str,
+    kind: RowKind,
+    cells: &'a [String],
+}
+
+/// The times of a train at a station, collected from one or two rows.
+#[derive(Default)]
+struct Visit<'a> {
+    station: &'a str,
+    arrival: Option<Time>,
+    departure: Option<Time>,
+    passes: bool,
+}
+
+impl SpreadsheetConfig {
+    /// Compile the time formats into regular expressions.
+    fn time_patterns(&self) -> Result<Vec<Regex>> {
+        self.time_formats
+            .iter()
+            .map(|format| {
+                let mut pattern = String::from("^");
+                let mut chars = format.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '%' => match chars.next() {
+                            Some('H') => pattern.push_str(r"(?P<h>\d{1,2})"),
+                            Some('M') => pattern.push_str(r"(?P<m>\d{2})"),
+                            Some('S') => pattern.push_str(r"(?P<s>\d{2})"),
+                            Some('%') => pattern.push('%'),
+                            other => {
+                                return Err(anyhow!(
+                                    "The time format '{}' has an unknown field '%{}'",
+                                    format,
+                                    other.map(String::from).unwrap_or_default()
+                                ));
+                            }
+                        },
+                        c => pattern.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                pattern.push('$');
+                Regex::new(&pattern)
+                    .with_context(|| format!("The time format '{format}' is invalid"))
+            })
+            .collect()
+    }
+
+    /// Write a time in the first time format. Hours go beyond 24 for times after midnight.
+    fn format_time(&self, time: Time) -> String {
+        let format = self.time_formats.first().map_or("%H:%M", String::as_str);
+        let seconds = time.seconds();
+        let mut formatted = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('%', Some(field @ ('H' | 'M' | 'S' | '%'))) => {
+                    chars.next();
+                    match field {
+                        'H' => formatted.push_str(&format!("{:02}", seconds / 3600)),
+                        'M' => formatted.push_str(&format!("{:02}", seconds / 60 % 60)),
+                        'S' => formatted.push_str(&format!("{:02}", seconds % 60)),
+                        _ => formatted.push('%'),
+                    }
+                }
+                _ => formatted.push(c),
+            }
+        }
+        formatted
+    }
+}
+
+/// Read a timetable laid out with one column per train and one row per station. The first
+/// column has the stations, and stations could have arrival and departure sub-rows, marked in
+/// another column. Stations of departure sub-rows could be left empty. Columns with an empty
+/// header are not trains.
+pub fn import(data: &[u8], config: SpreadsheetConfig) -> Result<Imported> {
+    let content = std::str::from_utf8(data).context("The file is not valid UTF-8")?;
+    let delimiter = config.delimiter.unwrap_or_else(|| {
+        let first_line = content.lines().next().unwrap_or_default();
+        if first_line.contains('\t') { '\t' } else { ',' }
+    });
+    let records = csv::parse_records(content, delimiter).context("Failed to parse the table")?;
+    let time_patterns = config.time_patterns()?;
+    let mut warnings = Vec::new();
+    let Some((header, records)) = records.split_first() else {
+        return Err(anyhow!("The table is empty"));
+    };
+    let width = records
+        .iter()
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0)
+        .max(header.len());
+    let is_label = |labels: &[String], value: &str| labels.iter().any(|label| label == value);
+
+    // the layout of the columns
+    let mileage_column = config
+        .mileage_column
+        .as_ref()
+        .and_then(|name| header.iter().position(|cell| cell.trim() == name));
+    let kind_column = (1..width)
+        .filter(|column| Some(*column) != mileage_column)
+        .find(|column| {
+            let mut labels = records
+                .iter()
+                .map(|record| cell(record, *column))
+                .filter(|value| !value.is_empty())
+                .peekable();
+            labels.peek().is_some()
+                && labels.all(|value| {
+                    is_label(&config.arrival_labels, value)
+                        || is_label(&config.departure_labels, value)
+                })
+        });
+    let train_columns: Vec<usize> = (1..header.len())
+        .filter(|column| Some(*column) != mileage_column && Some(*column) != kind_column)
+        .filter(|column| !cell(header, *column).is_empty())
+        .collect();
+    if train_columns.is_empty() {
+        return Err(anyhow!("The table does not have any trains"));
+    }
+
+    // stations
+    let mut rows = Vec::new();
+    let mut stations: Vec<(&str, Option<f64>)> = Vec::new();
+    let mut station_indices: HashMap<&str, usize> = HashMap::new();
+    let mut previous_station = None;
+    for (idx, record) in records.iter().enumerate() {
+        let line = idx + 2;
+        let kind = match kind_column.map(|column| cell(record, column)) {
+            Some(value) if is_label(&config.arrival_labels, value) => RowKind::Arrival,
+            Some(value) if is_label(&config.departure_labels, value) => RowKind::Departure,
+            _ => RowKind::Both,
+        };
+        let station = match cell(record, 0) {
+            "" if kind == RowKind::Departure => match previous_station {
+                Some(station) => station,
+                None => continue,
+            },
+            "" => continue,
+            station => station,
+        };
+        previous_station = Some(station);
+        let mileage = mileage_column
+            .map(|column| cell(record, column))
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Line {} has an invalid mileage '{}'", line, value))
+            })
+            .transpose()?;
+        match station_indices.get(station) {
+            Some(&station_idx) => {
+                if stations[station_idx].1.is_none() {
+                    stations[station_idx].1 = mileage;
+                }
+            }
+            None => {
+                station_indices.insert(station, stations.len());
+                stations.push((station, mileage));
+            }
+        }
+        rows.push(TableRow {
+            line,
+            station,
+            kind,
+            cells: record,
+        });
+    }
+
+    // trains
+    let mut seen_trains = HashSet::new();
+    let mut trains = Vec::new();
+    for column in train_columns {
+        let train_name = unique_name(cell(header, column), &mut seen_trains, &mut warnings);
+        let mut visits: Vec<Visit> = Vec::new();
+        let mut visit = Visit::default();
+        let mut previous_kind = None;
+        for row in &rows {
+            // a new visit starts with a new station, or with a row that could not follow
+            if visit.station != row.station
+                || row.kind != RowKind::Departure
+                || previous_kind == Some(RowKind::Departure)
+            {
+                visits.push(std::mem::take(&mut visit));
+                visit.station = row.station;
+            }
+            previous_kind = Some(row.kind);
+            let value = cell(row.cells, column);
+            if value.is_empty() || is_label(&config.not_running_markers, value) {
+                continue;
+            }
+            let mut value = value.to_string();
+            for marker in &config.pass_markers {
+                if value.contains(marker.as_str()) {
+                    visit.passes = true;
+                    value = value.replace(marker.as_str(), "");
+                }
+            }
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let time = parse_time(&time_patterns, value).ok_or_else(|| {
+                anyhow!(
+                    "Train '{}' has an invalid time '{}' at '{}' on line {}",
+                    train_name,
+                    value,
+                    row.station,
+                    row.line
+                )
+            })?;
+            match row.kind {
+                RowKind::Arrival => visit.arrival = Some(time),
+                RowKind::Departure => visit.departure = Some(time),
+                RowKind::Both => {
+                    visit.arrival = Some(time);
+                    visit.departure = Some(time);
+                }
+            }
+        }
+        visits.push(visit);
+
+        let mut unwrapper = DayUnwrapper::default();
+        let schedule: Vec<ScheduleEntryHelper> = visits
+            .into_iter()
+            .filter_map(|visit| {
+                // passes without times could not be drawn
+                let arrival = visit.arrival.or(visit.departure)?;
+                let departure = visit.departure.unwrap_or(arrival);
+                Some(ScheduleEntryHelper {
+                    arrival: unwrapper.unwrap(arrival),
+                    departure: unwrapper.unwrap(departure),
+                    station: visit.station.to_string(),
+                    track: None,
+                    kind: Some(if visit.passes {
+                        EntryKind::Pass
+                    } else {
+                        EntryKind::Stop
+                    }),
+                })
+            })
+            .collect();
+        if schedule.is_empty() {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!("Train '{train_name}' does not have any times and is skipped"),
+                )
+                .train(&train_name),
+            );
+            continue;
+        }
+        trains.push((
+            train_name,
+            TrainHelper {
+                frequency: None,
+                calendar: None,
+                label_size: Default::default(),
+                priority: None,
+                schedule,
+                metadata: Metadata::new(),
+            },
+        ));
+    }
+
+    // intervals between neighbouring rows
+    let mut missing_mileage = 0;
+    let intervals = stations
+        .windows(2)
+        .map(|pair| {
+            let [(from, from_mileage), (to, to_mileage)] = pair else {
+                unreachable!()
+            };
+            let length = match (from_mileage, to_mileage) {
+                (Some(from), Some(to)) => ((to - from).abs() * 1000.0).round() as u32,
+                _ => {
+                    missing_mileage += 1;
+                    DEFAULT_INTERVAL_LENGTH
+                }
+            };
+            (
+                (from.to_string(), to.to_string()),
+                IntervalHelper {
+                    length: IntervalLength::new(length),
+                    bidirectional: Some(true),
+                },
+            )
+        })
+        .collect();
+    if missing_mileage > 0 && mileage_column.is_some() {
+        warnings.push(Diagnostic::warning(
+            ErrorCode::UnmeasuredInterval,
+            format!(
+                "{missing_mileage} intervals have a station without mileage, they are \
+             {DEFAULT_INTERVAL_LENGTH} m long"
+            ),
+        ));
+    }
+
+    Ok(Imported {
+        network: NetworkHelper {
+            version: SCHEMA_VERSION,
+            stations: stations
+                .into_iter()
+                .map(|(name, _)| {
+                    (
+                        name.to_string(),
+                        StationHelper {
+                            label_size: Default::default(),
+                            tracks: None,
+                            metadata: Metadata::new(),
+                        },
+                    )
+                })
+                .collect(),
+            trains,
+            intervals,
+            holidays: HashMap::new(),
+        },
+        warnings,
+    })
+}
+
+/// Write the trains of a network in the layout read by `import`, with arrival and departure
+/// sub-rows for every station. Trains are ordered by their first departure. Trains that do not
+/// follow the order of the stations are skipped.
+pub fn export(network: &Network, config: SpreadsheetConfig) -> Result<ExportedTable> {
+    let mut warnings = Vec::new();
+    let station_name = |id: StationID| -> String {
+        network
+            .stations
+            .get(&id)
+            .map_or_else(|| network.names.display(id), |station| station.name.clone())
+    };
+
+    let mut trains: Vec<&Train> = network.trains.values().collect();
+    trains.sort_by(|a, b| {
+        let start = |train: &Train| train.schedule.first_key_value().map(|(time, _)| *time);
+        start(a).cmp(&start(b)).then_with(|| a.name.cmp(&b.name))
+    });
+
+    // the order of the stations
+    let mut stations: Vec<StationID> = Vec::new();
+    if config.stations.is_empty() {
+        let longest = trains
+            .iter()
+            .max_by_key(|train| train.schedule.len())
+            .ok_or_else(|| anyhow!("The network does not have any trains"))?;
+        let mut seen = HashSet::new();
+        stations.extend(
+            longest
+                .schedule
+                .values()
+                .map(|entry| entry.station)
+                .filter(|station| seen.insert(*station)),
+        );
+    } else {
+        for name in &config.stations {
+            let id = network
+                .stations
+                .iter()
+                .find(|(_, station)| station.name == *name)
+                .map(|(id, _)| *id)
+                .ok_or_else(|| anyhow!("Station '{}' not found", name))?;
+            stations.push(id);
+        }
+    }
+    if config.reverse {
+        stations.reverse();
+    }
+    let station_rows: HashMap<StationID, usize> = stations
+        .iter()
+        .enumerate()
+        .map(|(idx, station)| (*station, idx))
+        .collect();
+
+    let arrival_label = config.arrival_labels.first().map_or("arr", String::as_str);
+    let departure_label = config
+        .departure_labels
+        .first()
+        .map_or("dep", String::as_str);
+    let pass_marker = config.pass_markers.first().map_or("", String::as_str);
+    let not_running_marker = config
+        .not_running_markers
+        .first()
+        .map_or("", String::as_str);
+    let mut header = vec!["Station".to_string(), String::new()];
+    if let Some(mileage_column) = &config.mileage_column {
+        header.push(mileage_column.clone());
+    }
+    let mut rows: Vec<Vec<String>> = stations
+        .iter()
+        .flat_map(|station| {
+            [arrival_label, departure_label].map(|label| {
+                let mut row = vec![station_name(*station), label.to_string()];
+                if config.mileage_column.is_some() {
+                    row.push(String::new());
+                }
+                row
+            })
+        })
+        .collect();
+    if config.mileage_column.is_some() {
+        // mileage is unknown after a missing interval
+        let mut mileage = Some(0.0);
+        for (idx, station) in stations.iter().enumerate() {
+            if idx > 0 {
+                let pair = (stations[idx - 1], *station);
+                let length = network
+                    .intervals
+                    .get(&pair)
+                    .or_else(|| network.intervals.get(&pair.reverse()))
+                    .map(|interval| interval.length.kilometers());
+                mileage = mileage
+                    .zip(length)
+                    .map(|(mileage, length)| mileage + length);
+            }
+            if let Some(mileage) = mileage {
+                rows[idx * 2][2] = format!("{mileage}");
+                rows[idx * 2 + 1][2] = format!("{mileage}");
+            }
+        }
+    }
+
+    for train in trains {
+        let entries: Vec<(&Time, &ScheduleEntry)> = train.schedule.iter().collect();
+        let Some(indices) = entries
+            .iter()
+            .map(|(_, entry)| station_rows.get(&entry.station).copied())
+            .collect::<Option<Vec<usize>>>()
+            .filter(|indices| indices.windows(2).all(|pair| pair[0] < pair[1]))
+        else {
+            warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::SkippedTrain,
+                    format!(
+                        "Train '{}' does not follow the order of the stations and is skipped",
+                        train.name
+                    ),
+                )
+                .train(&train.name),
+            );
+            continue;
+        };
+        let Some(&(&schedule_start, _)) = entries.first() else {
+            continue;
+        };
+        let offset = match &train.frequency {
+            TrainFrequency::Fixed(times) => {
+                if times.len() > 1 {
+                    warnings.push(
+                        Diagnostic::warning(
+                            ErrorCode::SimplifiedSchedule,
+                            format!(
+                                "Train '{}' runs more than once, only its first run is written",
+                                train.name
+                            ),
+                        )
+                        .train(&train.name),
+                    );
+                }
+                times
+                    .iter()
+                    .min()
+                    .map_or(Time::new(0), |first| *first - schedule_start)
+            }
+            TrainFrequency::Repeating(repetition) => {
+                if repetition.interval.seconds().abs() < 86400 {
+                    warnings.push(Diagnostic::warning(ErrorCode::SimplifiedSchedule, format!(
+                        "Train '{}' repeats more than once a day, only its first run is written",
+                        train.name
+                    )).train(&train.name));
+                }
+                Time::new(0)
+            }
+        };
+
+        header.push(train.name.clone());
+        let mut cells = vec![String::new(); rows.len()];
+        let first_row = indices[0] * 2;
+        let last_row = indices[indices.len() - 1] * 2 + 1;
+        for cell in &mut cells[first_row..=last_row] {
+            *cell = not_running_marker.to_string();
+        }
+        let last = entries.len() - 1;
+        for (idx, ((arrival, entry), row)) in entries.iter().zip(&indices).enumerate() {
+            let arrival = config.format_time(**arrival + offset);
+            let departure = config.format_time(entry.departure + offset);
+            let (arrival_cell, departure_cell) = match entry.kind {
+                EntryKind::Pass => (pass_marker.to_string(), format!("{pass_marker}{departure}")),
+                _ if idx == 0 && arrival == departure => (String::new(), departure),
+                _ if idx == last && arrival == departure => (arrival, String::new()),
+                _ => (arrival, departure),
+            };
+            cells[row * 2] = arrival_cell;
+            cells[row * 2 + 1] = departure_cell;
+        }
+        for (row, cell) in rows.iter_mut().zip(cells) {
+            row.push(cell);
+        }
+    }
+
+    let header: Vec<&str> = header.iter().map(String::as_str).collect();
+    Ok(ExportedTable {
+        table: csv::write_delimited(config.delimiter.unwrap_or(','), &header, &rows),
+        warnings,
+    })
+}
+
+/// The trimmed content of a cell, which is empty if the row is too short.
+fn cell(record: &[String], column: usize) -> &str {
+    record.get(column).map_or("", |cell| cell.trim())
+}
+
+/// Parse a time with the first pattern that matches it.
+fn parse_time(patterns: &[Regex], value: &str) -> Option<Time> {
+    patterns.iter().find_map(|pattern| {
+        let captures = pattern.captures(value)?;
+        let field = |name: &str| -> Option<i32> {
+            captures
+                .name(name)
+                .map_or(Some(0), |field| field.as_str().parse().ok())
+        };
+        let (hours, minutes, seconds) = (field("h")?, field("m")?, field("s")?);
+        (minutes < 60 && seconds < 60).then(|| Time::new(hours * 3600 + minutes * 60 + seconds))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::foreign::test_util::*;
+
+    const TABLE: &str = "Station,,km,101,103,105,107
+A,arr,0.0,,,,
+,dep,0.0,23:10,06:00,-,23:50
+B,arr,12.5,23:40,|,-,00:05
+,dep,12.5,23:45,↓0612,-,00:06
+C,arr,30.2,24:20,06:40,07:00,00:20
+,dep,30.2,,,07:05,
+D,arr,41,25:05,-,07:30,-
+,dep,41,,,,
+";
+
+    fn config() -> SpreadsheetConfig {
+        serde_json::from_str(r#"{"mileage_column": "km"}"#).unwrap()
+    }
+
+    /// The stations and times of a train.
+    type Schedule = Vec<(String, Time, Time)>;
+
+    fn schedules(imported: &Imported) -> Vec<(String, Schedule)> {
+        let mut schedules: Vec<(String, Schedule)> = imported
+            .network
+            .trains
+            .iter()
+            .map(|(name, train)| {
+                let schedule = train
+                    .schedule
+                    .iter()
+                    .map(|entry| (entry.station.clone(), entry.arrival, entry.departure))
+                    .collect();
+                (name.clone(), schedule)
+            })
+            .collect();
+        schedules.sort_by(|a, b| a.0.cmp(&b.0));
+        schedules
+    }
+
+    #[test]
+    fn import_times_past_midnight() {
+        let imported = import(TABLE.as_bytes(), config()).unwrap();
+        assert!(imported.warnings.is_empty());
+        let schedules = schedules(&imported);
+        let names: Vec<&str> = schedules.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["101", "103", "105", "107"]);
+
+        // times past 24:00 are kept as they are
+        let (_, late) = &schedules[0];
+        assert_eq!(late[2].1, hm(24, 20));
+        assert_eq!(late[3].1, hm(25, 5));
+
+        // times that go back past midnight are on the next day
+        let (_, night) = &schedules[3];
+        assert_eq!(night[1].1, hm(24, 5));
+        assert_eq!(night[2].1, hm(24, 20));
+
+        // passes may have a time
+        let pass = &imported.network.trains[1].1.schedule[1];
+        assert!(matches!(pass.kind, Some(EntryKind::Pass)));
+        assert_eq!(pass.arrival, hm(6, 12));
+
+        let lengths: Vec<u32> = imported
+            .network
+            .intervals
+            .iter()
+            .map(|(_, interval)| interval.length.meters())
+            .collect();
+        assert_eq!(lengths, [12500, 17700, 10800]);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let imported = import(TABLE.as_bytes(), config()).unwrap();
+        let network =
+            Network::try_from(import(TABLE.as_bytes(), config()).unwrap().network).unwrap();
+        let exported = export(&network, config()).unwrap();
+        assert!(exported.warnings.is_empty());
+        assert!(exported.table.contains("25:05"));
+
+        // tabs are found from the first row
+        let tsv = exported.table.replace(',', "\t");
+        let again = import(tsv.as_bytes(), config()).unwrap();
+        assert_eq!(schedules(&again), schedules(&imported));
+    }
+}
@@ -0,0 +1,229 @@
+use super::*;
+
+/// Bit of `Calendar::weekdays` that stands for holidays. Bits 0 to 6 stand for Monday to Sunday.
+pub const HOLIDAY_BIT: u8 = 1 << 7;
+
+/// The day a diagram is drawn for.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperatingDay {
+    /// A specific date.
+    Date(Date),
+    /// Any day of the given weekday.
+    Weekday(Weekday),
+    /// Any holiday.
+    Holiday,
+}
+
+impl OperatingDay {
+    /// Get the operating day `days` days later. Holidays are assumed to be next to other
+    /// holidays.
+    pub fn add_days(self, days: i32) -> Self {
+        match self {
+            OperatingDay::Date(date) => OperatingDay::Date(date.add_days(days)),
+            OperatingDay::Weekday(weekday) => OperatingDay::Weekday(weekday.add_days(days)),
+            OperatingDay::Holiday => OperatingDay::Holiday,
+        }
+    }
+}
+
+/// The days on which a train runs.
+pub struct Calendar {
+    /// Bitmask of the day types the train runs on. See `HOLIDAY_BIT`.
+    pub weekdays: u8,
+    /// The date ranges, both ends included, in which the train runs. Unbounded if empty.
+    pub date_ranges: Vec<(Date, Date)>,
+    /// Dates the train always runs on.
+    pub include: HashSet<Date>,
+    /// Dates the train never runs on.
+    pub exclude: HashSet<Date>,
+    /// Dates that are treated as holidays instead of their weekdays.
+    pub holidays: HashSet<Date>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct CalendarHelper {
+    pub weekdays: Option<u8>,
+    #[serde(default)]
+    pub date_ranges: Vec<(Date, Date)>,
+    #[serde(default)]
+    pub include: Vec<Date>,
+    #[serde(default)]
+    pub exclude: Vec<Date>,
+    /// Names of the holiday sets that apply to this calendar.
+    #[serde(default)]
+    pub holidays: Vec<String>,
+}
+
+impl Calendar {
+    pub(super) fn from_helper(
+        helper: CalendarHelper,
+        holiday_sets: &HashMap<String, Vec<Date>>,
+    ) -> Result<Self> {
+        let mut holidays = HashSet::new();
+        for holiday_set in &helper.holidays {
+            let Some(dates) = holiday_sets.get(holiday_set) else {
+                return Err(Diagnostic::error(
+                    ErrorCode::UnknownHolidaySet,
+                    format!("Holiday set '{}' does not exist", holiday_set),
+                )
+                .into());
+            };
+            holidays.extend(dates.iter().copied());
+        }
+        for (range_start, range_end) in &helper.date_ranges {
+            if range_start > range_end {
+                return Err(Diagnostic::error(
+                    ErrorCode::InvalidDateRange,
+                    format!(
+                        "The date range {} to {} ends before it begins",
+                        range_start, range_end
+                    ),
+                )
+                .into());
+            }
+        }
+        Ok(Calendar {
+            weekdays: helper.weekdays.unwrap_or(u8::MAX),
+            date_ranges: helper.date_ranges,
+            include: helper.include.into_iter().collect(),
+            exclude: helper.exclude.into_iter().collect(),
+            holidays,
+        })
+    }
+
+    /// Whether the train runs on the given day.
+    pub fn runs_on(&self, day: OperatingDay) -> bool {
+        let bit = match day {
+            OperatingDay::Date(date) => {
+                if self.exclude.contains(&date) {
+                    return false;
+                }
+                if self.include.contains(&date) {
+                    return true;
+                }
+                if !self.date_ranges.is_empty()
+                    && !self
+                        .date_ranges
+                        .iter()
+                        .any(|(range_start, range_end)| (*range_start..=*range_end).contains(&date))
+                {
+                    return false;
+                }
+                if self.holidays.contains(&date) {
+                    HOLIDAY_BIT
+                } else {
+                    1 << date.weekday().index()
+                }
+            }
+            OperatingDay::Weekday(weekday) => 1 << weekday.index(),
+            OperatingDay::Holiday => HOLIDAY_BIT,
+        };
+        self.weekdays & bit != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u8) -> Date {
+        Date::from_ymd(2026, 10, day).unwrap()
+    }
+
+    fn calendar(helper: CalendarHelper) -> Calendar {
+        let holiday_sets = HashMap::from([("national".to_string(), vec![date(21), date(24)])]);
+        Calendar::from_helper(helper, &holiday_sets).unwrap()
+    }
+
+    fn weekdays(mask: u8) -> CalendarHelper {
+        CalendarHelper {
+            weekdays: Some(mask),
+            date_ranges: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            holidays: vec!["national".to_string()],
+        }
+    }
+
+    #[test]
+    fn weekdays_and_holidays() {
+        // 19 October 2026 is a Monday, and the 21st and the 24th are holidays
+        assert!(matches!(date(19).weekday(), Weekday::Monday));
+        let mondays_to_fridays = 0b0011111;
+
+        let workdays = calendar(weekdays(mondays_to_fridays));
+        assert!(workdays.runs_on(OperatingDay::Weekday(Weekday::Friday)));
+        assert!(!workdays.runs_on(OperatingDay::Weekday(Weekday::Saturday)));
+        assert!(!workdays.runs_on(OperatingDay::Holiday));
+        assert!(workdays.runs_on(OperatingDay::Date(date(20))));
+        // a holiday on a Wednesday is not a workday
+        assert!(!workdays.runs_on(OperatingDay::Date(date(21))));
+
+        let holidays = calendar(weekdays(HOLIDAY_BIT));
+        assert!(holidays.runs_on(OperatingDay::Holiday));
+        assert!(!holidays.runs_on(OperatingDay::Weekday(Weekday::Saturday)));
+        assert!(holidays.runs_on(OperatingDay::Date(date(21))));
+        // a holiday on a Saturday counts as a holiday, not as a Saturday
+        assert!(holidays.runs_on(OperatingDay::Date(date(24))));
+        assert!(!calendar(weekdays(1 << 5)).runs_on(OperatingDay::Date(date(24))));
+        assert!(!holidays.runs_on(OperatingDay::Date(date(25))));
+
+        // every day if the weekdays are not given
+        let every_day = calendar(CalendarHelper {
+            weekdays: None,
+            ..weekdays(0)
+        });
+        assert!(every_day.runs_on(OperatingDay::Holiday));
+        assert!(every_day.runs_on(OperatingDay::Date(date(25))));
+    }
+
+    #[test]
+    fn date_ranges_and_exceptions() {
+        let autumn = calendar(CalendarHelper {
+            date_ranges: vec![(date(1), date(10)), (date(20), date(22))],
+            include: vec![date(25), date(26)],
+            exclude: vec![date(2), date(26)],
+            ..weekdays(u8::MAX)
+        });
+        // both ends of a range are included
+        assert!(autumn.runs_on(OperatingDay::Date(date(1))));
+        assert!(autumn.runs_on(OperatingDay::Date(date(10))));
+        assert!(!autumn.runs_on(OperatingDay::Date(date(11))));
+        assert!(autumn.runs_on(OperatingDay::Date(date(22))));
+        assert!(!autumn.runs_on(OperatingDay::Date(date(23))));
+        // included dates are outside of the ranges, and excluded dates win over everything
+        assert!(autumn.runs_on(OperatingDay::Date(date(25))));
+        assert!(!autumn.runs_on(OperatingDay::Date(date(2))));
+        assert!(!autumn.runs_on(OperatingDay::Date(date(26))));
+        // ranges only apply to dates
+        assert!(autumn.runs_on(OperatingDay::Weekday(Weekday::Sunday)));
+
+        // included dates do not have to match the weekdays
+        let sundays = calendar(CalendarHelper {
+            include: vec![date(20)],
+            ..weekdays(1 << 6)
+        });
+        assert!(sundays.runs_on(OperatingDay::Date(date(20))));
+        assert!(!sundays.runs_on(OperatingDay::Date(date(27))));
+        assert!(sundays.runs_on(OperatingDay::Date(date(25))));
+    }
+
+    #[test]
+    fn invalid_calendars() {
+        let error = Calendar::from_helper(
+            CalendarHelper {
+                date_ranges: vec![(date(10), date(1))],
+                ..weekdays(u8::MAX)
+            },
+            &HashMap::from([("national".to_string(), Vec::new())]),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(Diagnostic::of(&error).code, ErrorCode::InvalidDateRange);
+        let error = Calendar::from_helper(weekdays(u8::MAX), &HashMap::new())
+            .err()
+            .unwrap();
+        assert_eq!(Diagnostic::of(&error).code, ErrorCode::UnknownHolidaySet);
+    }
+}
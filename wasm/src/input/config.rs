@@ -1,7 +1,7 @@
 use super::*;
 
 #[derive(Deserialize)]
-#[serde(try_from = "NetworkConfigHelper")]
+#[serde(try_from = "ciborium::Value")]
 pub struct NetworkConfig {
     pub stations_to_draw: Vec<StationID>,
     pub start_time: Time,
@@ -13,36 +13,126 @@ pub struct NetworkConfig {
     pub time_axis_scale: f64,
     pub label_angle: f64,
     pub line_stack_space: GraphLength,
+    /// Only draw trains running on this day. All trains are drawn if `None`.
+    pub operating_day: Option<OperatingDay>,
+    /// What to do with labels that could not be placed without overlapping others.
+    pub label_fallback: LabelFallback,
+    /// The order in which trains are laid out.
+    pub layout_order: LayoutOrder,
+    /// Train classes, from the highest priority to the lowest. Used if the layout order is by
+    /// class.
+    pub class_priorities: Vec<String>,
+    /// How the places of the labels are chosen.
+    pub label_layout: LabelLayout,
+    /// How many steps the global search for label places takes. A step count rather than a time
+    /// limit, as `Instant` is not available in WebAssembly, and the result must not depend on
+    /// the speed of the machine.
+    pub label_search_steps: u32,
+    /// The seed of the global search. The same seed always gives the same places.
+    pub label_search_seed: u64,
+    /// Names of the stations to draw.
+    pub names: NameTable,
+}
+
+/// What to do with a label that could not be placed without overlapping others.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelFallback {
+    /// Do not draw the label.
+    #[default]
+    Drop,
+    /// Draw the label where it overlaps the least.
+    BestEffort,
+    /// Draw the label in an area below the diagram.
+    Overflow,
+}
+
+/// The order in which trains are laid out. Trains laid out earlier get the preferred spots for
+/// their labels. Ties are broken by the first departure, and then by the name of the train.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutOrder {
+    /// The train that departs first is laid out first.
+    #[default]
+    FirstDeparture,
+    /// The train with the highest `priority` is laid out first.
+    Priority,
+    /// Trains are laid out in the order of their classes in `class_priorities`. Trains of other
+    /// classes, or without a class, come last.
+    Class,
+}
+
+/// How the places of the labels are chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelLayout {
+    /// Labels are placed one at a time in the layout order, each moved away from its line until
+    /// it no longer overlaps the labels before it.
+    #[default]
+    Greedy,
+    /// Labels are placed together, by searching for a combination of candidate places that
+    /// overlaps little, stays close to the lines, and drops few labels.
+    Global,
+}
+
+/// The default number of steps of the global label search.
+pub const DEFAULT_LABEL_SEARCH_STEPS: u32 = 100_000;
+
+fn default_label_search_steps() -> u32 {
+    DEFAULT_LABEL_SEARCH_STEPS
 }
 
 #[derive(Deserialize)]
-struct NetworkConfigHelper {
-    stations_to_draw: Vec<String>,
-    start_time: Time,
-    end_time: Time,
-    unit_length: GraphLength,
-    position_axis_scale_mode: ScaleMode,
+pub(crate) struct NetworkConfigHelper {
+    pub stations_to_draw: Vec<String>,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub unit_length: GraphLength,
+    pub position_axis_scale_mode: ScaleMode,
     // time_axis_scale_mode: ScaleMode,
-    position_axis_scale: f64,
-    time_axis_scale: f64,
-    label_angle: f64,
-    line_stack_space: GraphLength,
+    pub position_axis_scale: f64,
+    pub time_axis_scale: f64,
+    pub label_angle: f64,
+    pub line_stack_space: GraphLength,
+    pub operating_day: Option<OperatingDay>,
+    #[serde(default)]
+    pub label_fallback: LabelFallback,
+    #[serde(default)]
+    pub layout_order: LayoutOrder,
+    #[serde(default)]
+    pub class_priorities: Vec<String>,
+    #[serde(default)]
+    pub label_layout: LabelLayout,
+    #[serde(default = "default_label_search_steps")]
+    pub label_search_steps: u32,
+    #[serde(default)]
+    pub label_search_seed: u64,
+}
+
+impl TryFrom<ciborium::Value> for NetworkConfig {
+    type Error = anyhow::Error;
+    fn try_from(document: ciborium::Value) -> Result<Self, Self::Error> {
+        NetworkConfig::try_from(NetworkConfigHelper::from_document(document)?)
+    }
 }
 
 impl TryFrom<NetworkConfigHelper> for NetworkConfig {
     type Error = anyhow::Error;
     fn try_from(helper: NetworkConfigHelper) -> Result<Self, Self::Error> {
         if helper.stations_to_draw.is_empty() {
-            return Err(anyhow::anyhow!(
-                "You must specify at least one station to draw"
-            ));
+            return Err(Diagnostic::error(
+                ErrorCode::NoStationsToDraw,
+                "You must specify at least one station to draw",
+            )
+            .into());
         }
 
+        let mut names = NameTable::default();
         let stations_to_draw: Vec<StationID> = helper
             .stations_to_draw
             .iter()
-            .map(|station_name| hash_id(station_name))
-            .collect();
+            .map(|station_name| names.intern(station_name))
+            .collect::<Result<_>>()?;
 
         for (window_idx, station_window) in helper.stations_to_draw.windows(3).enumerate() {
             let [_, current_station_name, next_station_name] = station_window else {
@@ -52,25 +142,39 @@ impl TryFrom<NetworkConfigHelper> for NetworkConfig {
             let current_station_id = stations_to_draw[window_idx + 1];
             let next_station_id = stations_to_draw[window_idx + 2];
             if current_station_id == next_station_id {
-                return Err(anyhow::anyhow!(
-                    "Two consecutive stations cannot be the same: '{}'",
-                    current_station_name
-                ));
+                return Err(Diagnostic::error(
+                    ErrorCode::InvalidStationOrder,
+                    format!(
+                        "Two consecutive stations cannot be the same: '{}'",
+                        current_station_name
+                    ),
+                )
+                .station(current_station_name)
+                .into());
             }
             if previous_station_id == next_station_id {
-                return Err(anyhow::anyhow!(
-                    "The station '{}' cannot be both the beginning of the previous interval and the end of the next one",
-                    next_station_name
-                ));
+                return Err(Diagnostic::error(
+                    ErrorCode::InvalidStationOrder,
+                    format!(
+                        "The station '{}' cannot be both the beginning of the previous interval and the end of the next one",
+                        next_station_name
+                    ),
+                )
+                .station(next_station_name)
+                .into());
             }
         }
 
         if helper.start_time > helper.end_time {
-            return Err(anyhow::anyhow!(
-                "The beginning time {} cannot be after the end time {}",
-                helper.start_time,
-                helper.end_time
-            ));
+            return Err(Diagnostic::error(
+                ErrorCode::InvalidTimeRange,
+                format!(
+                    "The beginning time {} cannot be after the end time {}",
+                    helper.start_time, helper.end_time
+                ),
+            )
+            .times([helper.start_time, helper.end_time])
+            .into());
         }
 
         Ok(NetworkConfig {
@@ -84,6 +188,82 @@ impl TryFrom<NetworkConfigHelper> for NetworkConfig {
             position_axis_scale: helper.position_axis_scale,
             time_axis_scale: helper.time_axis_scale,
             label_angle: helper.label_angle,
+            operating_day: helper.operating_day,
+            label_fallback: helper.label_fallback,
+            layout_order: helper.layout_order,
+            class_priorities: helper.class_priorities,
+            label_layout: helper.label_layout,
+            label_search_steps: helper.label_search_steps,
+            label_search_seed: helper.label_search_seed,
+            names,
+        })
+    }
+}
+
+/// Configuration of a station track occupancy diagram.
+#[derive(Deserialize)]
+#[serde(try_from = "OccupancyConfigHelper")]
+pub struct OccupancyConfig {
+    pub station: StationID,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub unit_length: GraphLength,
+    pub time_axis_scale: f64,
+    /// The space between two tracks.
+    pub track_space: GraphLength,
+    /// Only draw trains running on this day. All trains are drawn if `None`.
+    pub operating_day: Option<OperatingDay>,
+    /// What to do with labels that could not be placed without overlapping others.
+    pub label_fallback: LabelFallback,
+    /// Name of the station.
+    pub names: NameTable,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OccupancyConfigHelper {
+    station: String,
+    start_time: Time,
+    end_time: Time,
+    unit_length: GraphLength,
+    time_axis_scale: f64,
+    track_space: GraphLength,
+    operating_day: Option<OperatingDay>,
+    #[serde(default)]
+    label_fallback: LabelFallback,
+}
+
+impl TryFrom<ciborium::Value> for OccupancyConfig {
+    type Error = anyhow::Error;
+    fn try_from(document: ciborium::Value) -> Result<Self, Self::Error> {
+        OccupancyConfig::try_from(OccupancyConfigHelper::from_document(document)?)
+    }
+}
+
+impl TryFrom<OccupancyConfigHelper> for OccupancyConfig {
+    type Error = anyhow::Error;
+    fn try_from(helper: OccupancyConfigHelper) -> Result<Self, Self::Error> {
+        if helper.start_time > helper.end_time {
+            return Err(Diagnostic::error(
+                ErrorCode::InvalidTimeRange,
+                format!(
+                    "The beginning time {} cannot be after the end time {}",
+                    helper.start_time, helper.end_time
+                ),
+            )
+            .times([helper.start_time, helper.end_time])
+            .into());
+        }
+        let mut names = NameTable::default();
+        Ok(OccupancyConfig {
+            station: names.intern(&helper.station)?,
+            start_time: helper.start_time,
+            end_time: helper.end_time,
+            unit_length: helper.unit_length,
+            time_axis_scale: helper.time_axis_scale,
+            track_space: helper.track_space,
+            operating_day: helper.operating_day,
+            label_fallback: helper.label_fallback,
+            names,
         })
     }
 }
@@ -1,19 +1,29 @@
+pub mod calendar;
 pub mod config;
+pub mod names;
 pub mod network;
+pub mod schema;
 pub mod station;
 pub mod train;
 
+use crate::diagnostic::*;
 use crate::types::*;
 use anyhow::Result;
+pub use calendar::*;
 pub use config::*;
+pub use names::*;
 pub use network::*;
-use serde::Deserialize;
+pub use schema::*;
+use serde::{Deserialize, Serialize};
 pub use station::*;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 pub use train::*;
 
+/// Extra information of a station or a train, such as its colour, that is not used for drawing.
+pub type Metadata = BTreeMap<String, ciborium::Value>;
+
 /// hash string to ids
 fn hash_id(s: &str) -> u64 {
     let mut hasher = DefaultHasher::new();
@@ -0,0 +1,46 @@
+use super::*;
+use std::collections::hash_map::Entry;
+
+/// Human-readable names of stations and trains, keyed by their IDs.
+#[derive(Default)]
+pub struct NameTable {
+    names: HashMap<u64, String>,
+}
+
+impl NameTable {
+    /// Get the ID of the name and remember the name. Two different names with the same ID are
+    /// rejected, so that they would not be merged silently.
+    pub fn intern(&mut self, name: &str) -> Result<u64> {
+        let id = hash_id(name);
+        match self.names.entry(id) {
+            Entry::Occupied(entry) if entry.get() != name => Err(Diagnostic::error(
+                ErrorCode::NameCollision,
+                format!(
+                    "The names '{}' and '{}' have the same ID {}, please rename one of them",
+                    entry.get(),
+                    name,
+                    id
+                ),
+            )
+            .into()),
+            Entry::Occupied(_) => Ok(id),
+            Entry::Vacant(entry) => {
+                entry.insert(name.to_string());
+                Ok(id)
+            }
+        }
+    }
+
+    /// Get the name behind the ID.
+    pub fn get(&self, id: u64) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Get the name behind the ID for messages. Falls back to the ID itself if the name is unknown.
+    pub fn display(&self, id: u64) -> String {
+        match self.get(id) {
+            Some(name) => format!("'{name}'"),
+            None => format!("#{id}"),
+        }
+    }
+}
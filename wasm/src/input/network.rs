@@ -1,42 +1,75 @@
 use super::*;
 
 #[derive(Deserialize)]
-#[serde(try_from = "NetworkHelper")]
+#[serde(try_from = "ciborium::Value")]
 pub struct Network {
     pub stations: HashMap<StationID, Station>,
     pub trains: HashMap<TrainID, Train>,
     pub intervals: HashMap<IntervalID, Interval>,
+    /// Names of the stations and trains.
+    pub names: NameTable,
+    /// Parts of the network that were ignored.
+    pub warnings: Vec<Diagnostic>,
 }
 
-#[derive(Deserialize)]
-struct NetworkHelper {
-    stations: HashMap<String, StationHelper>,
-    trains: HashMap<String, TrainHelper>,
-    intervals: Vec<((String, String), IntervalHelper)>,
+/// Stations and trains are kept in order, so that importers could pass on the order of the
+/// source file.
+#[serde_with::serde_as]
+#[derive(Deserialize, Serialize)]
+pub(crate) struct NetworkHelper {
+    /// The version of the schema. See `SCHEMA_VERSION`.
+    #[serde(default)]
+    pub version: u32,
+    #[serde_as(as = "serde_with::Map<_, _>")]
+    pub stations: Vec<(String, StationHelper)>,
+    #[serde_as(as = "serde_with::Map<_, _>")]
+    pub trains: Vec<(String, TrainHelper)>,
+    pub intervals: Vec<((String, String), IntervalHelper)>,
+    /// Named sets of holidays that calendars could refer to.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub holidays: HashMap<String, Vec<Date>>,
+}
+
+impl TryFrom<ciborium::Value> for Network {
+    type Error = anyhow::Error;
+    fn try_from(document: ciborium::Value) -> Result<Self, Self::Error> {
+        Network::try_from(NetworkHelper::from_document(document)?)
+    }
 }
 
 impl TryFrom<NetworkHelper> for Network {
     type Error = anyhow::Error;
     fn try_from(helper: NetworkHelper) -> Result<Self, Self::Error> {
+        let mut names = NameTable::default();
+        let mut warnings = Vec::new();
         let mut stations: HashMap<StationID, Station> =
             HashMap::with_capacity(helper.stations.len());
         let mut intervals: HashMap<IntervalID, Interval> =
             HashMap::with_capacity(helper.intervals.len());
         for (station_name, station_helper) in helper.stations {
-            let station_id = hash_id(&station_name);
+            let station_id = names.intern(&station_name)?;
+            if stations.contains_key(&station_id) {
+                return Err(Diagnostic::error(
+                    ErrorCode::DuplicateStation,
+                    format!("Station '{}' is defined more than once", station_name),
+                )
+                .station(station_name)
+                .into());
+            }
             let station = Station {
                 label_size: station_helper.label_size,
+                metadata: station_helper.metadata,
                 // milestones: station_helper.milestones,
-                // tracks: station_helper.tracks.unwrap_or(1),
-                // name: station_name,
+                tracks: station_helper.tracks.map(Vec::from).unwrap_or_default(),
+                name: station_name,
                 intervals: HashSet::new(),
                 trains: HashSet::new(),
             };
             stations.insert(station_id, station);
         }
         for ((from_station, to_station), interval_helper) in helper.intervals {
-            let from_station_id = hash_id(&from_station);
-            let to_station_id = hash_id(&to_station);
+            let from_station_id = names.intern(&from_station)?;
+            let to_station_id = names.intern(&to_station)?;
             let interval_id = (from_station_id, to_station_id);
             let new_interval = Interval {
                 // name: interval_helper.name,
@@ -45,68 +78,152 @@ impl TryFrom<NetworkHelper> for Network {
             match interval_helper.bidirectional {
                 Some(true) | None => {
                     if intervals.contains_key(&interval_id.reverse()) {
-                        return Err(anyhow::anyhow!(
-                            "Interval from '{}' to '{}' already exists",
-                            to_station,
-                            from_station
-                        ));
+                        return Err(Diagnostic::error(
+                            ErrorCode::DuplicateInterval,
+                            format!(
+                                "Interval from '{}' to '{}' already exists",
+                                to_station, from_station
+                            ),
+                        )
+                        .station(to_station)
+                        .into());
                     }
                     intervals.insert(interval_id.reverse(), new_interval.clone());
                     if intervals.contains_key(&interval_id) {
-                        return Err(anyhow::anyhow!(
-                            "Interval from '{}' to '{}' already exists",
-                            from_station,
-                            to_station
-                        ));
+                        return Err(Diagnostic::error(
+                            ErrorCode::DuplicateInterval,
+                            format!(
+                                "Interval from '{}' to '{}' already exists",
+                                from_station, to_station
+                            ),
+                        )
+                        .station(from_station)
+                        .into());
                     }
                     intervals.insert(interval_id, new_interval);
                 }
                 _ => {
                     if intervals.contains_key(&interval_id) {
-                        return Err(anyhow::anyhow!(
-                            "Interval from '{}' to '{}' already exists",
-                            from_station,
-                            to_station
-                        ));
+                        return Err(Diagnostic::error(
+                            ErrorCode::DuplicateInterval,
+                            format!(
+                                "Interval from '{}' to '{}' already exists",
+                                from_station, to_station
+                            ),
+                        )
+                        .station(from_station)
+                        .into());
                     }
                     intervals.insert(interval_id, new_interval);
                 }
             }
-            if let Some(from_station_obj) = stations.get_mut(&from_station_id) {
-                from_station_obj.intervals.insert(interval_id);
-            }
-            if let Some(to_station_obj) = stations.get_mut(&to_station_id) {
-                to_station_obj.intervals.insert(interval_id);
+            for (station_id, station_name) in [
+                (from_station_id, &from_station),
+                (to_station_id, &to_station),
+            ] {
+                match stations.get_mut(&station_id) {
+                    Some(station) => {
+                        station.intervals.insert(interval_id);
+                    }
+                    None => warnings.push(
+                        Diagnostic::warning(
+                            ErrorCode::UnknownStation,
+                            format!(
+                                "The interval from '{}' to '{}' refers to the unknown station '{}'",
+                                from_station, to_station, station_name
+                            ),
+                        )
+                        .station(station_name),
+                    ),
+                }
             }
         }
         let mut trains: HashMap<TrainID, Train> = HashMap::with_capacity(helper.trains.len());
         for (train_name, train_helper) in helper.trains {
-            let train_id = hash_id(&train_name);
+            let train_id = names.intern(&train_name)?;
+            if trains.contains_key(&train_id) {
+                return Err(Diagnostic::error(
+                    ErrorCode::DuplicateTrain,
+                    format!("Train '{}' is defined more than once", train_name),
+                )
+                .train(train_name)
+                .into());
+            }
             let label_size = train_helper.label_size;
             let mut schedule = BTreeMap::new();
             let mut previous_departure: Option<Time> = None;
             for entry_idx in 0..train_helper.schedule.len() {
                 let current_entry = &train_helper.schedule[entry_idx];
-                let station_id = hash_id(&current_entry.station);
+                let station_id = names.intern(&current_entry.station)?;
                 if current_entry.departure < current_entry.arrival {
-                    return Err(anyhow::anyhow!(
-                        "Departure time cannot be before arrival time for train '{}'",
-                        train_name
-                    ));
+                    return Err(Diagnostic::error(
+                        ErrorCode::DepartureBeforeArrival,
+                        format!(
+                            "Departure time cannot be before arrival time for train '{}', arrival: {}, departure: {}",
+                            train_name, current_entry.arrival, current_entry.departure
+                        ),
+                    )
+                    .train(train_name)
+                    .station(&current_entry.station)
+                    .entry(entry_idx)
+                    .times([current_entry.arrival, current_entry.departure])
+                    .into());
                 }
                 if let Some(previous_departure) = previous_departure {
                     // there is a previous entry, so preform some extra checks
                     // the previous departure time MUST be before the current arrival time
                     // however we don't care about them being identical
                     if current_entry.arrival < previous_departure {
-                        return Err(anyhow::anyhow!(
-                            "Arrival time cannot be before previous departure time for train '{}', current arrival: {}, previous departure: {}",
-                            train_name,
-                            current_entry.arrival,
-                            previous_departure
-                        ));
+                        return Err(Diagnostic::error(
+                            ErrorCode::ArrivalBeforePreviousDeparture,
+                            format!(
+                                "Arrival time cannot be before previous departure time for train '{}', current arrival: {}, previous departure: {}",
+                                train_name, current_entry.arrival, previous_departure
+                            ),
+                        )
+                        .train(train_name)
+                        .station(&current_entry.station)
+                        .entry(entry_idx)
+                        .times([previous_departure, current_entry.arrival])
+                        .into());
                     }
                 };
+                let track = match &current_entry.track {
+                    Some(track_ref) => {
+                        let tracks = stations
+                            .get(&station_id)
+                            .map_or(&[][..], |station| &station.tracks);
+                        let track = match track_ref {
+                            TrackRef::Index(index) => {
+                                (usize::from(*index) < tracks.len()).then_some(*index)
+                            }
+                            TrackRef::Name(name) => tracks
+                                .iter()
+                                .position(|track| track.name == *name)
+                                .map(|index| index as u16),
+                        };
+                        if track.is_none() {
+                            return Err(Diagnostic::error(
+                                ErrorCode::UnknownTrack,
+                                format!(
+                                    "Station '{}' used by train '{}' does not have the track {}",
+                                    current_entry.station,
+                                    train_name,
+                                    match track_ref {
+                                        TrackRef::Index(index) => index.to_string(),
+                                        TrackRef::Name(name) => format!("'{name}'"),
+                                    }
+                                ),
+                            )
+                            .train(train_name)
+                            .station(&current_entry.station)
+                            .entry(entry_idx)
+                            .into());
+                        }
+                        track
+                    }
+                    None => None,
+                };
                 // either there isn't a previous entry or the checks are done
                 // insert the current entry into the schedule
                 schedule.insert(
@@ -114,6 +231,14 @@ impl TryFrom<NetworkHelper> for Network {
                     ScheduleEntry {
                         departure: current_entry.departure,
                         station: station_id,
+                        track,
+                        kind: current_entry.kind.unwrap_or(
+                            if current_entry.arrival == current_entry.departure {
+                                EntryKind::Pass
+                            } else {
+                                EntryKind::Stop
+                            },
+                        ),
                     },
                 );
                 previous_departure = Some(current_entry.departure);
@@ -121,15 +246,25 @@ impl TryFrom<NetworkHelper> for Network {
                     station.trains.insert(train_id);
                 }
             }
+            let calendar = match train_helper.calendar {
+                Some(calendar_helper) => Some(
+                    Calendar::from_helper(calendar_helper, &helper.holidays)
+                        .map_err(|error| with_train(error, &train_name))?,
+                ),
+                None => None,
+            };
             trains.insert(
                 train_id,
                 Train {
+                    calendar,
                     name: train_name,
                     label_size,
+                    priority: train_helper.priority.unwrap_or_default(),
+                    metadata: train_helper.metadata,
                     schedule,
                     frequency: train_helper
                         .frequency
-                        .unwrap_or(TrainFrequency::Repeating(Time::new(86400))),
+                        .unwrap_or(TrainFrequency::Repeating(Time::new(86400).into())),
                 },
             );
         }
@@ -137,6 +272,8 @@ impl TryFrom<NetworkHelper> for Network {
             stations,
             trains,
             intervals,
+            names,
+            warnings,
         })
     }
 }
@@ -0,0 +1,391 @@
+use super::*;
+use anyhow::anyhow;
+use ciborium::Value;
+use serde::de::DeserializeOwned;
+
+/// The version of the documents passed to and returned by the plugin. Documents without a
+/// `version` field were written before versions were introduced, and are read as version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The JSON Schema of the documents of the current version.
+pub const JSON_SCHEMA: &str = include_str!("../schema.json");
+
+/// Upgrades a document from the version at its index to the next version.
+type Migration = fn(&mut Vec<(Value, Value)>) -> Result<()>;
+
+/// Version 0 accepted the empty dictionary that `paiagram` takes for `intervals` by default, and
+/// which is encoded as an empty map.
+const NETWORK_MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [|network| {
+    for (key, value) in network {
+        if key.as_text() == Some("intervals")
+            && let Value::Map(map) = value
+        {
+            if !map.is_empty() {
+                return Err(anyhow!(
+                    "The intervals must be an array of ((from, to), interval) pairs"
+                ));
+            }
+            *value = Value::Array(Vec::new());
+        }
+    }
+    Ok(())
+}];
+
+const CONFIG_MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [|_| Ok(())];
+
+const OCCUPANCY_CONFIG_MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [|_| Ok(())];
+
+impl NetworkHelper {
+    /// Read a network of any version, upgrading it to the current version first.
+    pub(crate) fn from_document(document: Value) -> Result<Self> {
+        upgrade(document, &NETWORK_MIGRATIONS, "network")
+    }
+}
+
+impl NetworkConfigHelper {
+    /// Read a config of any version, upgrading it to the current version first.
+    pub(crate) fn from_document(document: Value) -> Result<Self> {
+        upgrade(document, &CONFIG_MIGRATIONS, "config")
+    }
+}
+
+impl OccupancyConfigHelper {
+    /// Read an occupancy config of any version, upgrading it to the current version first.
+    pub(crate) fn from_document(document: Value) -> Result<Self> {
+        upgrade(document, &OCCUPANCY_CONFIG_MIGRATIONS, "occupancy config")
+    }
+}
+
+fn upgrade<T: DeserializeOwned>(
+    document: Value,
+    migrations: &[Migration],
+    name: &str,
+) -> Result<T> {
+    let Value::Map(mut fields) = document else {
+        return Err(Diagnostic::error(
+            ErrorCode::InvalidDocument,
+            format!("The {} must be a map", name),
+        )
+        .into());
+    };
+    let version = match fields
+        .iter()
+        .find(|(key, _)| key.as_text() == Some("version"))
+    {
+        Some((_, Value::Integer(version))) => u32::try_from(*version).ok(),
+        Some(_) => None,
+        None => Some(0),
+    };
+    let Some(version) = version else {
+        return Err(Diagnostic::error(
+            ErrorCode::InvalidDocument,
+            format!("The version of the {} must be a positive integer", name),
+        )
+        .into());
+    };
+    if version > SCHEMA_VERSION {
+        return Err(Diagnostic::error(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "The {} is of version {}, but only versions up to {} are supported. Please update paiagram",
+                name, version, SCHEMA_VERSION
+            ),
+        )
+        .into());
+    }
+    for migration in &migrations[version as usize..] {
+        migration(&mut fields).map_err(|error| {
+            Diagnostic::error(
+                ErrorCode::InvalidDocument,
+                format!(
+                    "Failed to upgrade the {} of version {}: {}",
+                    name, version, error
+                ),
+            )
+        })?;
+    }
+    fields.retain(|(key, _)| key.as_text() != Some("version"));
+    fields.push((Value::Text("version".into()), SCHEMA_VERSION.into()));
+    // the errors are shown through serde, which only shows the outermost message of a chain
+    Value::Map(fields).deserialized().map_err(|error| {
+        let ciborium::value::Error::Custom(message) = error;
+        Diagnostic::error(
+            ErrorCode::InvalidDocument,
+            format!(
+                "The {} does not match version {} of the schema: {}",
+                name, SCHEMA_VERSION, message
+            ),
+        )
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::{from_reader, into_writer};
+    use serde_json::{Value as Json, json};
+
+    /// Checks a document against a definition of `JSON_SCHEMA`. Only the keywords the schema uses
+    /// are checked, and string patterns are ignored.
+    fn check(schema: &Json, document: &Json, path: &str) -> Result<(), String> {
+        let root: Json = serde_json::from_str(JSON_SCHEMA).unwrap();
+        check_against(&root, schema, document, path)
+    }
+
+    fn check_against(
+        root: &Json,
+        schema: &Json,
+        document: &Json,
+        path: &str,
+    ) -> Result<(), String> {
+        let fail = |reason: &str| Err(format!("{path}: {reason}, found {document}"));
+        let schema = match schema {
+            Json::Bool(true) => return Ok(()),
+            Json::Bool(false) => return fail("no value allowed"),
+            Json::Object(schema) => schema,
+            _ => panic!("{path}: not a schema"),
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Json::as_str) {
+            let target = root
+                .pointer(reference.trim_start_matches('#'))
+                .unwrap_or_else(|| panic!("{path}: unknown reference {reference}"));
+            check_against(root, target, document, path)?;
+        }
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Json::Array(types) => types.iter().filter_map(Json::as_str).collect(),
+                types => types.as_str().into_iter().collect(),
+            };
+            let found = types.iter().any(|&kind| match kind {
+                "integer" => document.is_i64() || document.is_u64(),
+                "number" => document.is_number(),
+                "string" => document.is_string(),
+                "boolean" => document.is_boolean(),
+                "null" => document.is_null(),
+                "object" => document.is_object(),
+                "array" => document.is_array(),
+                _ => panic!("{path}: unknown type {kind}"),
+            });
+            if !found {
+                return fail(&format!("expected {}", types.join(" or ")));
+            }
+        }
+        if let Some(Json::Array(values)) = schema.get("enum")
+            && !values.contains(document)
+        {
+            return fail("not one of the enum values");
+        }
+        if let Some(value) = schema.get("const")
+            && value != document
+        {
+            return fail(&format!("expected {value}"));
+        }
+        if let Some(number) = document.as_f64() {
+            if let Some(minimum) = schema.get("minimum").and_then(Json::as_f64)
+                && number < minimum
+            {
+                return fail(&format!("below {minimum}"));
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Json::as_f64)
+                && number > maximum
+            {
+                return fail(&format!("above {maximum}"));
+            }
+        }
+        if let Some(Json::Array(options)) = schema.get("oneOf") {
+            let matching = options
+                .iter()
+                .filter(|option| check_against(root, option, document, path).is_ok())
+                .count();
+            if matching != 1 {
+                return fail(&format!("{matching} of the oneOf options match"));
+            }
+        }
+        if let Json::Object(fields) = document {
+            for key in schema
+                .get("required")
+                .and_then(Json::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if !fields.contains_key(key.as_str().unwrap()) {
+                    return fail(&format!("missing {key}"));
+                }
+            }
+            let properties = schema.get("properties").and_then(Json::as_object);
+            for (key, value) in fields {
+                let path = format!("{path}.{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property) => check_against(root, property, value, &path)?,
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            check_against(root, additional, value, &path)?;
+                        }
+                    }
+                }
+            }
+        }
+        if let Json::Array(items) = document {
+            let min_items = schema.get("minItems").and_then(Json::as_u64).unwrap_or(0);
+            if (items.len() as u64) < min_items {
+                return fail(&format!("fewer than {min_items} items"));
+            }
+            let prefix = schema.get("prefixItems").and_then(Json::as_array);
+            for (index, item) in items.iter().enumerate() {
+                let path = format!("{path}[{index}]");
+                match prefix.and_then(|prefix| prefix.get(index)) {
+                    Some(item_schema) => check_against(root, item_schema, item, &path)?,
+                    None => {
+                        if let Some(item_schema) = schema.get("items") {
+                            check_against(root, item_schema, item, &path)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn definition(name: &str) -> Json {
+        json!({ "$ref": format!("#/$defs/{name}") })
+    }
+
+    fn encode(document: &Json) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        into_writer(document, &mut encoded).unwrap();
+        encoded
+    }
+
+    fn decode(encoded: &[u8]) -> Json {
+        from_reader(encoded).unwrap()
+    }
+
+    fn network() -> Json {
+        json!({
+            "version": SCHEMA_VERSION,
+            "stations": {
+                "A": { "tracks": 2 },
+                "B": { "tracks": [{ "name": "1" }, { "name": "2", "main": false }] },
+            },
+            "trains": {
+                "101": {
+                    "label_size": [20.0, 10.0],
+                    "schedule": [
+                        { "arrival": 28800, "departure": 28800, "station": "A", "track": 1 },
+                        { "arrival": 29400, "departure": 29460, "station": "B", "track": "2" },
+                    ],
+                },
+                "102": {
+                    "label_size": [20.0, 10.0],
+                    "frequency": { "repeating": { "interval": 1800, "last": 34200 } },
+                    "calendar": { "weekdays": 0b0011111 },
+                    "schedule": [
+                        { "arrival": 30000, "departure": 30000, "station": "B" },
+                        { "arrival": 30600, "departure": 30600, "station": "A" },
+                    ],
+                },
+            },
+            "intervals": [[["A", "B"], { "length": 5000 }]],
+        })
+    }
+
+    #[test]
+    fn outputs_match_schema() {
+        let network = network();
+        let config = json!({
+            "version": SCHEMA_VERSION,
+            "stations_to_draw": ["A", "B"],
+            "start_time": 25200,
+            "end_time": 36000,
+            "unit_length": 20.0,
+            "position_axis_scale_mode": "linear",
+            "position_axis_scale": 1.0,
+            "time_axis_scale": 4.0,
+            "label_angle": 0.3,
+            "line_stack_space": 2.0,
+            "operating_day": { "weekday": "monday" },
+        });
+        check(&definition("network"), &network, "network").unwrap();
+        check(&definition("config"), &config, "config").unwrap();
+        let output = crate::process_internal(&encode(&network), &encode(&config)).unwrap();
+        let output = decode(&output);
+        assert_eq!(output["trains"].as_array().unwrap().len(), 2);
+        check(&definition("output"), &output, "output").unwrap();
+
+        let occupancy_config = json!({
+            "version": SCHEMA_VERSION,
+            "station": "B",
+            "start_time": 25200,
+            "end_time": 36000,
+            "unit_length": 20.0,
+            "time_axis_scale": 4.0,
+            "track_space": 10.0,
+            "label_fallback": "overflow",
+        });
+        check(
+            &definition("occupancy_config"),
+            &occupancy_config,
+            "occupancy config",
+        )
+        .unwrap();
+        let occupancy =
+            crate::process_occupancy_internal(&encode(&network), &encode(&occupancy_config))
+                .unwrap();
+        let occupancy = decode(&occupancy);
+        assert!(!occupancy["blocks"].as_array().unwrap().is_empty());
+        check(
+            &definition("occupancy_output"),
+            &occupancy,
+            "occupancy output",
+        )
+        .unwrap();
+
+        let mut config = config;
+        config["stations_to_draw"] = json!(["A", "C"]);
+        let failure =
+            crate::respond(crate::process_internal(&encode(&network), &encode(&config))).unwrap();
+        let failure = decode(&failure);
+        assert_eq!(failure["errors"][0]["code"], "unknown_station");
+        check(&definition("failure"), &failure, "failure").unwrap();
+        // an output is not a failure, and the other way round
+        assert!(check(&definition("output"), &failure, "failure").is_err());
+        assert!(check(&definition("failure"), &output, "output").is_err());
+    }
+
+    #[test]
+    fn upgrade_version_0_network() {
+        let mut network = network();
+        let fields = network.as_object_mut().unwrap();
+        fields.remove("version");
+        fields.insert("intervals".into(), json!({}));
+        assert!(check(&definition("network"), &network, "network").is_err());
+
+        let helper = NetworkHelper::from_document(Value::serialized(&network).unwrap()).unwrap();
+        assert_eq!(helper.version, SCHEMA_VERSION);
+        assert!(helper.intervals.is_empty());
+        assert_eq!(helper.stations.len(), 2);
+        assert_eq!(helper.trains.len(), 2);
+        let upgraded = serde_json::to_value(&helper).unwrap();
+        check(&definition("network"), &upgraded, "upgraded network").unwrap();
+        assert_eq!(upgraded["intervals"], json!([]));
+
+        network["intervals"] = json!({ "A": 5000 });
+        let Err(error) = NetworkHelper::from_document(Value::serialized(&network).unwrap()) else {
+            panic!("the network should not be read");
+        };
+        assert!(matches!(
+            Diagnostic::of(&error).code,
+            ErrorCode::InvalidDocument
+        ));
+
+        network["version"] = json!(SCHEMA_VERSION + 1);
+        let Err(error) = NetworkHelper::from_document(Value::serialized(&network).unwrap()) else {
+            panic!("the network should not be read");
+        };
+        assert!(matches!(
+            Diagnostic::of(&error).code,
+            ErrorCode::UnsupportedVersion
+        ));
+    }
+}
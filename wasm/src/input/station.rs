@@ -2,19 +2,74 @@ use super::*;
 
 pub struct Station {
     // pub milestones: Option<HashMap<String, IntervalLength>>,
-    // pub tracks: u16,
-    // pub name: String,
+    /// The tracks of the station. Empty if the tracks are unknown.
+    pub tracks: Vec<Track>,
+    pub name: String,
     // those fields are completed afterwards
     pub intervals: HashSet<IntervalID>,
     pub trains: HashSet<TrainID>,
     pub label_size: (GraphLength, GraphLength),
+    pub metadata: Metadata,
 }
 
-#[derive(Deserialize)]
-pub(super) struct StationHelper {
+#[derive(Deserialize, Serialize)]
+pub(crate) struct StationHelper {
+    #[serde(default)]
     pub label_size: (GraphLength, GraphLength),
     // milestones: Option<HashMap<String, IntervalLength>>,
-    // tracks: Option<u16>,
+    pub tracks: Option<TracksHelper>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+pub struct Track {
+    /// The name of the track.
+    pub name: String,
+    /// Whether the track is a main track, or a siding.
+    pub main: bool,
+}
+
+/// Accepts either the number of tracks, or the tracks themselves.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum TracksHelper {
+    Count(u16),
+    List(Vec<TrackHelper>),
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct TrackHelper {
+    pub name: String,
+    pub main: Option<bool>,
+}
+
+impl From<TracksHelper> for Vec<Track> {
+    fn from(helper: TracksHelper) -> Self {
+        match helper {
+            // tracks are numbered from 1, as they usually are on the field
+            TracksHelper::Count(count) => (1..=count)
+                .map(|number| Track {
+                    name: number.to_string(),
+                    main: true,
+                })
+                .collect(),
+            TracksHelper::List(tracks) => tracks
+                .into_iter()
+                .map(|track| Track {
+                    name: track.name,
+                    main: track.main.unwrap_or(true),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Refers to a track either by its index or by its name.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum TrackRef {
+    Index(u16),
+    Name(String),
 }
 
 #[derive(Clone)]
@@ -22,8 +77,8 @@ pub struct Interval {
     pub length: IntervalLength,
 }
 
-#[derive(Deserialize)]
-pub(super) struct IntervalHelper {
+#[derive(Deserialize, Serialize)]
+pub(crate) struct IntervalHelper {
     // name: Option<String>,
     pub length: IntervalLength,
     pub bidirectional: Option<bool>,
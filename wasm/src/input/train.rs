@@ -1,32 +1,124 @@
 use super::*;
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 /// The frequency of a train.
 pub enum TrainFrequency {
-    /// The train departs once every `Time` units.
-    Repeating(Time),
-    // The train departs at a fixed schedule.
-    // Fixed(Vec<Time>),
+    /// The train departs once every `interval`, optionally only within a validity window.
+    Repeating(Repetition),
+    /// The train departs at each of the given times. Each time is the arrival time at the
+    /// first station of the schedule, so one schedule could be drawn many times.
+    Fixed(Vec<Time>),
 }
 
+/// A repeating pattern. Instances are identified by their arrival time at the first station of
+/// the schedule.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(from = "RepetitionHelper")]
+pub struct Repetition {
+    /// The time between two instances.
+    pub interval: Time,
+    /// The first instance that runs. Unbounded if `None`.
+    pub first: Option<Time>,
+    /// The last instance that runs. Unbounded if `None`.
+    pub last: Option<Time>,
+    /// Instances that are skipped.
+    pub except: Vec<Time>,
+}
+
+impl From<Time> for Repetition {
+    fn from(interval: Time) -> Self {
+        Self {
+            interval,
+            first: None,
+            last: None,
+            except: Vec::new(),
+        }
+    }
+}
+
+/// Accepts either a bare interval or a bounded pattern.
 #[derive(Deserialize)]
-pub(super) struct TrainHelper {
+#[serde(untagged)]
+enum RepetitionHelper {
+    Interval(Time),
+    Bounded {
+        interval: Time,
+        first: Option<Time>,
+        last: Option<Time>,
+        #[serde(default)]
+        except: Vec<Time>,
+    },
+}
+
+impl From<RepetitionHelper> for Repetition {
+    fn from(helper: RepetitionHelper) -> Self {
+        match helper {
+            RepetitionHelper::Interval(interval) => interval.into(),
+            RepetitionHelper::Bounded {
+                interval,
+                first,
+                last,
+                except,
+            } => Self {
+                interval,
+                first,
+                last,
+                except,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct TrainHelper {
     pub frequency: Option<TrainFrequency>,
+    pub calendar: Option<CalendarHelper>,
+    #[serde(default)]
     pub label_size: (GraphLength, GraphLength),
     pub schedule: Vec<ScheduleEntryHelper>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub metadata: Metadata,
+}
+
+/// What the train does at a station.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// The train stops for passengers or goods.
+    Stop,
+    /// The train passes the station without stopping.
+    Pass,
+    /// The train stops, but not for passengers or goods. E.g. for crew changes or overtaking.
+    TechnicalStop,
+}
+
+impl EntryKind {
+    /// Whether the train actually stops at the station.
+    #[inline]
+    pub fn is_stop(self) -> bool {
+        self != EntryKind::Pass
+    }
 }
 
 pub struct ScheduleEntry {
     pub departure: Time,
     pub station: StationID,
+    pub kind: EntryKind,
+    /// The index of the track the train uses at the station.
+    pub track: Option<u16>,
 }
 
-#[derive(Deserialize)]
-pub(super) struct ScheduleEntryHelper {
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct ScheduleEntryHelper {
     pub arrival: Time,
     pub departure: Time,
     pub station: String,
+    pub track: Option<TrackRef>,
+    /// Inferred from the times if not given.
+    pub kind: Option<EntryKind>,
 }
 
 pub struct Train {
@@ -34,18 +126,53 @@ pub struct Train {
     pub name: String,
     /// The frequency of the train.
     pub frequency: TrainFrequency,
+    /// The days on which the train runs. The train runs every day if `None`.
+    pub calendar: Option<Calendar>,
     // TODO: change this to a pre-calculated bounding box
     /// The size of the train's label. This would be changed in future versions
     pub label_size: (GraphLength, GraphLength),
     /// The schedule of the train, with the key being the arrival time at the station.
     pub schedule: BTreeMap<Time, ScheduleEntry>,
+    /// Trains with a higher priority are laid out first if the layout order is by priority.
+    pub priority: i32,
+    pub metadata: Metadata,
 }
 
 impl Train {
+    /// Whether the instance of the schedule shifted by `time_offset` runs on `operating_day`.
+    /// Instances that start on the next day belong to the next operating day.
+    pub fn runs_on(&self, operating_day: Option<OperatingDay>, time_offset: Time) -> bool {
+        let (Some(operating_day), Some(calendar)) = (operating_day, &self.calendar) else {
+            return true;
+        };
+        let Some((&schedule_start_time, _)) = self.schedule.first_key_value() else {
+            return true;
+        };
+        let day_offset = (schedule_start_time + time_offset).day() - schedule_start_time.day();
+        calendar.runs_on(operating_day.add_days(day_offset))
+    }
+
+    /// The departure time from the first station of the earliest instance. Repeating trains
+    /// without a first instance use the schedule as it is written.
+    pub fn first_departure(&self) -> Option<Time> {
+        let (&arrival, entry) = self.schedule.first_key_value()?;
+        let first_arrival = match &self.frequency {
+            TrainFrequency::Repeating(repetition) => repetition.first.unwrap_or(arrival),
+            TrainFrequency::Fixed(arrivals) => arrivals.iter().min().copied().unwrap_or(arrival),
+        };
+        Some(entry.departure + first_arrival - arrival)
+    }
+
+    /// The class of the train, taken from the `type` metadata.
+    pub fn class(&self) -> Option<&str> {
+        self.metadata.get("type")?.as_text()
+    }
+
     pub fn iter_schedule<'a>(
         &'a self,
         start_time: Time,
         end_time: Time,
+        operating_day: Option<OperatingDay>,
     ) -> Result<Option<Box<dyn Iterator<Item = IterateScheduleEntry<'a>> + 'a>>> {
         if start_time > end_time {
             return Err(anyhow::anyhow!(
@@ -56,28 +183,68 @@ impl Train {
         } else if self.schedule.is_empty() {
             return Ok(None);
         }
-        // check the schedule start and end time, if they are not between start_time and end_time, return None
-        let (&schedule_start_time, _) = self.schedule.first_key_value().unwrap();
-        let schedule_end_time = {
-            let (_, last_entry) = self.schedule.last_key_value().unwrap();
-            last_entry.departure
-        };
-        if schedule_start_time > end_time || schedule_end_time < start_time {
-            return Ok(None);
-        }
         match &self.frequency {
-            TrainFrequency::Repeating(interval) => {
-                let iter =
-                    TrainScheduleRepeatingIterator::new(self, start_time, end_time, *interval)?;
+            TrainFrequency::Repeating(repetition) => {
+                let Some(iter) = TrainScheduleRepeatingIterator::new(
+                    self,
+                    start_time,
+                    end_time,
+                    repetition,
+                    operating_day,
+                )?
+                else {
+                    return Ok(None);
+                };
                 Ok(Some(Box::new(iter)))
-            } // TrainFrequency::Fixed(schedule) => {
-              //     let iter = TrainScheduleFixedIterator::new(self, start, end, schedule);
-              //     Ok(Some(TrainScheduleIterator::Fixed(iter)))
-              // }
+            }
+            TrainFrequency::Fixed(departures) => {
+                let Some(iter) = TrainScheduleFixedIterator::new(
+                    self,
+                    start_time,
+                    end_time,
+                    departures,
+                    operating_day,
+                ) else {
+                    return Ok(None);
+                };
+                Ok(Some(Box::new(iter)))
+            }
         }
     }
 }
 
+/// Get the entries that are visible between `start_time` and `end_time` after shifting the
+/// schedule by `time_offset`. The entries right before and after the range are included, so
+/// that the edges could be clipped at the borders of the diagram.
+fn visible_entries<'a>(
+    train: &'a Train,
+    start_time: Time,
+    end_time: Time,
+    time_offset: Time,
+) -> Box<dyn Iterator<Item = (&'a Time, &'a ScheduleEntry)> + 'a> {
+    let before_start_iter = train
+        .schedule
+        .range(..start_time - time_offset)
+        .next_back()
+        .into_iter();
+    let between_iter = train
+        .schedule
+        .range(start_time - time_offset..end_time - time_offset);
+    // test for the last entry in between_iter
+    let after_end_iter = train
+        .schedule
+        .range(end_time - time_offset..)
+        .next()
+        .into_iter();
+    let last_entry = between_iter.clone().next_back();
+    match last_entry {
+        Some((_, last_entry)) if last_entry.departure > end_time - time_offset => {
+            Box::new(before_start_iter.chain(between_iter))
+        }
+        _ => Box::new(before_start_iter.chain(between_iter).chain(after_end_iter)),
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct IterateScheduleEntry<'a> {
     /// The normalized arrival time of the entry.
@@ -86,6 +253,10 @@ pub struct IterateScheduleEntry<'a> {
     pub departure: Time,
     /// clear the current edges, and push them to the global output
     pub clear: bool,
+    /// Whether the entry is not listed in the schedule, and its times are interpolated.
+    pub interpolated: bool,
+    /// What the train does at the station.
+    pub kind: EntryKind,
     /// The original entry.
     pub original_entry: &'a ScheduleEntry,
 }
@@ -101,23 +272,36 @@ pub struct TrainScheduleRepeatingIterator<'a> {
     pub interval: Time,
     /// whether to clear the current edges
     pub clear: bool,
-    num_of_repeats: u32,
+    schedule_start_time: Time,
+    except: &'a [Time],
+    operating_day: Option<OperatingDay>,
+    current_repeat: i32,
+    last_repeat: i32,
     time_offset: Time,
     current: Box<dyn Iterator<Item = (&'a Time, &'a ScheduleEntry)> + 'a>,
 }
 
 impl<'a> TrainScheduleRepeatingIterator<'a> {
-    fn new(train: &'a Train, start_time: Time, end_time: Time, interval: Time) -> Result<Self> {
+    /// Returns `None` if none of the repeats are visible between `start_time` and `end_time`.
+    fn new(
+        train: &'a Train,
+        start_time: Time,
+        end_time: Time,
+        repetition: &'a Repetition,
+        operating_day: Option<OperatingDay>,
+    ) -> Result<Option<Self>> {
         // normalize the interval. It must be nonnegative
-        let interval = match interval.cmp(&Time::new(0)) {
+        let interval = match repetition.interval.cmp(&Time::new(0)) {
             std::cmp::Ordering::Equal => {
-                return Err(anyhow::anyhow!(
-                    "Interval cannot be zero for train '{}'",
-                    train.name
-                ));
+                return Err(Diagnostic::error(
+                    ErrorCode::ZeroInterval,
+                    format!("Interval cannot be zero for train '{}'", train.name),
+                )
+                .train(&train.name)
+                .into());
             }
-            std::cmp::Ordering::Less => -interval,
-            std::cmp::Ordering::Greater => interval,
+            std::cmp::Ordering::Less => -repetition.interval,
+            std::cmp::Ordering::Greater => repetition.interval,
         };
         // get the schedule's times and duration
         let (&schedule_start_time, _) = train.schedule.first_key_value().unwrap();
@@ -125,56 +309,73 @@ impl<'a> TrainScheduleRepeatingIterator<'a> {
             let (_, last_entry) = train.schedule.last_key_value().unwrap();
             last_entry.departure
         };
-        // calculate the number of repeats required.
-        // floor division
-        let past_repeats = (schedule_end_time - start_time)
+        // calculate the range of repeats that are visible.
+        // ceil division
+        let mut first_repeat = -(schedule_end_time - start_time)
             .seconds()
             .div_euclid(interval.seconds());
-        // ceil division
-        let future_repeats = (end_time - schedule_start_time)
+        // floor division
+        let mut last_repeat = (end_time - schedule_start_time)
             .seconds()
             .div_euclid(interval.seconds());
-        // this is guaranteed to be non-negative
-        let num_of_repeats = (past_repeats + future_repeats) as u32;
-        // calculate the initial offset
-        let time_offset = -past_repeats * interval;
-        // create the iterator
-        let before_start_iter = train
-            .schedule
-            .range(..start_time - time_offset)
-            .next_back()
-            .into_iter();
-        let between_iter = train
-            .schedule
-            .range(start_time - time_offset..end_time - time_offset);
-        // test for the last entry in between_iter
-        let after_end_iter = train
-            .schedule
-            .range(end_time - time_offset..)
-            .next()
-            .into_iter();
-        let last_entry = between_iter.clone().next_back();
-        let current: Box<dyn Iterator<Item = (&'a Time, &'a ScheduleEntry)> + 'a> = match last_entry
-        {
-            Some((_, last_entry)) => {
-                if last_entry.departure > end_time - time_offset {
-                    Box::new(before_start_iter.chain(between_iter))
-                } else {
-                    Box::new(before_start_iter.chain(between_iter).chain(after_end_iter))
-                }
-            }
-            None => Box::new(before_start_iter.chain(between_iter).chain(after_end_iter)),
-        };
-        Ok(Self {
+        // narrow the range down to the validity window
+        if let Some(first) = repetition.first {
+            first_repeat = first_repeat.max(
+                -(schedule_start_time - first)
+                    .seconds()
+                    .div_euclid(interval.seconds()),
+            );
+        }
+        if let Some(last) = repetition.last {
+            last_repeat = last_repeat.min(
+                (last - schedule_start_time)
+                    .seconds()
+                    .div_euclid(interval.seconds()),
+            );
+        }
+        let mut iter = Self {
             clear: false,
             train,
             start_time,
             end_time,
             interval,
-            num_of_repeats,
-            time_offset,
-            current,
-        })
+            schedule_start_time,
+            except: &repetition.except,
+            operating_day,
+            current_repeat: first_repeat - 1,
+            last_repeat,
+            time_offset: Time::new(0),
+            current: Box::new(std::iter::empty()),
+        };
+        if !iter.advance() {
+            return Ok(None);
+        }
+        iter.clear = false;
+        Ok(Some(iter))
+    }
+
+    /// Move on to the next repeat that is not skipped and runs on the operating day. Returns
+    /// `false` if there are no repeats left.
+    fn advance(&mut self) -> bool {
+        while self.current_repeat < self.last_repeat {
+            self.current_repeat += 1;
+            let time_offset = self.current_repeat * self.interval;
+            if self
+                .except
+                .contains(&(self.schedule_start_time + time_offset))
+                || !self.train.runs_on(self.operating_day, time_offset)
+            {
+                continue;
+            }
+            // refresh the time offset and current iterator
+            self.time_offset = time_offset;
+            self.current =
+                visible_entries(self.train, self.start_time, self.end_time, self.time_offset);
+            // refresh the renew state
+            self.clear = true;
+            return true;
+        }
+        false
     }
 }
 
@@ -184,39 +385,89 @@ impl<'a> Iterator for TrainScheduleRepeatingIterator<'a> {
         // only give content that are in visible range. That is, between start_time and end_time
         // This assumes that start time is always before end time
         let Some((&arrival_time, original_entry)) = self.current.next() else {
-            if self.num_of_repeats == 0 {
+            if !self.advance() {
                 return None;
             }
-            self.num_of_repeats -= 1;
-            // refresh the time offset and current iterator
-            self.time_offset += self.interval;
-            let before_start_iter = self
-                .train
-                .schedule
-                .range(..self.start_time - self.time_offset)
-                .next_back()
-                .into_iter();
-            let between_iter = self
-                .train
-                .schedule
-                .range(self.start_time - self.time_offset..self.end_time - self.time_offset);
-            let after_end_iter = self
-                .train
-                .schedule
-                .range(self.end_time - self.time_offset..)
-                .next()
-                .into_iter();
-            let last_entry = between_iter.clone().next_back();
-            self.current = if let Some((_, last_entry)) = last_entry {
-                if last_entry.departure > self.end_time - self.time_offset {
-                    Box::new(before_start_iter.chain(between_iter))
-                } else {
-                    Box::new(before_start_iter.chain(between_iter).chain(after_end_iter))
-                }
-            } else {
-                Box::new(before_start_iter.chain(between_iter).chain(after_end_iter))
-            };
-            // refresh the renew state
+            return self.next();
+        };
+        let ret = Some(Self::Item {
+            arrival: arrival_time + self.time_offset,
+            departure: original_entry.departure + self.time_offset,
+            clear: self.clear,
+            interpolated: false,
+            kind: original_entry.kind,
+            original_entry,
+        });
+        self.clear = false;
+        ret
+    }
+}
+
+pub struct TrainScheduleFixedIterator<'a> {
+    /// The original Train object.
+    pub train: &'a Train,
+    /// The start time of the iteration.
+    pub start_time: Time,
+    /// The end time of the iteration.
+    pub end_time: Time,
+    /// whether to clear the current edges
+    pub clear: bool,
+    time_offsets: std::vec::IntoIter<Time>,
+    time_offset: Time,
+    current: Box<dyn Iterator<Item = (&'a Time, &'a ScheduleEntry)> + 'a>,
+}
+
+impl<'a> TrainScheduleFixedIterator<'a> {
+    /// Returns `None` if none of the departures are visible between `start_time` and `end_time`.
+    fn new(
+        train: &'a Train,
+        start_time: Time,
+        end_time: Time,
+        departures: &[Time],
+        operating_day: Option<OperatingDay>,
+    ) -> Option<Self> {
+        // get the schedule's times and duration
+        let (&schedule_start_time, _) = train.schedule.first_key_value().unwrap();
+        let schedule_end_time = {
+            let (_, last_entry) = train.schedule.last_key_value().unwrap();
+            last_entry.departure
+        };
+        // only keep the departures that are visible and run on the operating day, in
+        // chronological order
+        let mut time_offsets: Vec<Time> = departures
+            .iter()
+            .map(|&departure| departure - schedule_start_time)
+            .filter(|&time_offset| {
+                schedule_start_time + time_offset <= end_time
+                    && schedule_end_time + time_offset >= start_time
+                    && train.runs_on(operating_day, time_offset)
+            })
+            .collect();
+        time_offsets.sort();
+        time_offsets.dedup();
+        let mut time_offsets = time_offsets.into_iter();
+        let time_offset = time_offsets.next()?;
+        let current = visible_entries(train, start_time, end_time, time_offset);
+        Some(Self {
+            clear: false,
+            train,
+            start_time,
+            end_time,
+            time_offsets,
+            time_offset,
+            current,
+        })
+    }
+}
+
+impl<'a> Iterator for TrainScheduleFixedIterator<'a> {
+    type Item = IterateScheduleEntry<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some((&arrival_time, original_entry)) = self.current.next() else {
+            // move on to the next departure
+            self.time_offset = self.time_offsets.next()?;
+            self.current =
+                visible_entries(self.train, self.start_time, self.end_time, self.time_offset);
             self.clear = true;
             return self.next();
         };
@@ -224,9 +475,140 @@ impl<'a> Iterator for TrainScheduleRepeatingIterator<'a> {
             arrival: arrival_time + self.time_offset,
             departure: original_entry.departure + self.time_offset,
             clear: self.clear,
+            interpolated: false,
+            kind: original_entry.kind,
             original_entry,
         });
         self.clear = false;
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CalendarBuilder, NetworkBuilder, StationBuilder, TrainBuilder};
+
+    fn train(train: TrainBuilder) -> Train {
+        let mut network = NetworkBuilder::new()
+            .station("A", StationBuilder::new())
+            .station("B", StationBuilder::new())
+            .interval("A", "B", IntervalLength::new(10000), true)
+            .train("T", train)
+            .build()
+            .unwrap();
+        network.trains.drain().next().unwrap().1
+    }
+
+    fn hm(hours: i32, minutes: i32) -> Time {
+        Time::new(hours * 3600 + minutes * 60)
+    }
+
+    fn date(day: u8) -> Date {
+        Date::from_ymd(2026, 10, day).unwrap()
+    }
+
+    #[test]
+    fn runs_on_day_of_departure() {
+        // leaves on Mondays at 23:30, and arrives on Tuesdays at 0:30
+        let overnight = train(
+            TrainBuilder::new()
+                .stop("A", hm(23, 30), hm(23, 30))
+                .stop("B", hm(24, 30), hm(24, 30))
+                .calendar(CalendarBuilder::new().weekdays([Weekday::Monday], false)),
+        );
+        let monday = Some(OperatingDay::Date(date(19)));
+        let tuesday = Some(OperatingDay::Date(date(20)));
+        assert!(overnight.runs_on(monday, Time::new(0)));
+        assert!(overnight.runs_on(Some(OperatingDay::Weekday(Weekday::Monday)), Time::new(0)));
+        // the arrival on Tuesday does not make it a Tuesday train
+        assert!(!overnight.runs_on(tuesday, Time::new(0)));
+        // on the diagram of Tuesday, the instance a day earlier is the one that left on Monday
+        assert!(overnight.runs_on(tuesday, Time::new(-86400)));
+        assert!(!overnight.runs_on(monday, Time::new(-86400)));
+        // shifting it past midnight makes it leave on Tuesday
+        assert!(!overnight.runs_on(monday, Time::new(3600)));
+        assert!(overnight.runs_on(monday, Time::new(1799)));
+        // trains without a calendar, and diagrams without a day, take every train
+        assert!(overnight.runs_on(None, Time::new(3600)));
+        let daily = train(TrainBuilder::new().stop("A", hm(8, 0), hm(8, 0)).stop(
+            "B",
+            hm(9, 0),
+            hm(9, 0),
+        ));
+        assert!(daily.runs_on(tuesday, Time::new(86400)));
+    }
+    /// The arrival times of the instances between `start` and `end`, in hours and minutes.
+    fn instances(train: &Train, start: (i32, i32), end: (i32, i32)) -> Vec<Vec<(i32, i32)>> {
+        let Some(iter) = train
+            .iter_schedule(hm(start.0, start.1), hm(end.0, end.1), None)
+            .unwrap()
+        else {
+            return Vec::new();
+        };
+        let mut instances: Vec<Vec<(i32, i32)>> = Vec::new();
+        for entry in iter {
+            if entry.clear || instances.is_empty() {
+                instances.push(Vec::new());
+            }
+            let minutes = entry.arrival.seconds() / 60;
+            instances
+                .last_mut()
+                .unwrap()
+                .push((minutes / 60, minutes % 60));
+        }
+        instances
+    }
+
+    #[test]
+    fn iterate_repeating_schedule() {
+        // every half hour from 7:00 to 10:00, except at 8:30, taking 20 minutes
+        let repeating = train(
+            TrainBuilder::new()
+                .stop("A", hm(8, 0), hm(8, 0))
+                .stop("B", hm(8, 20), hm(8, 20))
+                .frequency(TrainFrequency::Repeating(Repetition {
+                    interval: Time::new(1800),
+                    first: Some(hm(7, 0)),
+                    last: Some(hm(10, 0)),
+                    except: vec![hm(8, 30)],
+                })),
+        );
+        let starts = |instances: Vec<Vec<(i32, i32)>>| -> Vec<(i32, i32)> {
+            instances.iter().map(|instance| instance[0]).collect()
+        };
+        // the instances at the first and the last times run, and the skipped one does not
+        assert_eq!(
+            starts(instances(&repeating, (6, 0), (12, 0))),
+            [(7, 0), (7, 30), (8, 0), (9, 0), (9, 30), (10, 0)]
+        );
+        // a window that starts before the first instance
+        assert_eq!(instances(&repeating, (0, 0), (7, 5)), [[(7, 0), (7, 20)]]);
+        // instances running at the borders keep their entries outside of the window, so that
+        // their lines could be clipped
+        assert_eq!(
+            instances(&repeating, (7, 10), (8, 10)),
+            [[(7, 0), (7, 20)], [(7, 30), (7, 50)], [(8, 0), (8, 20)]]
+        );
+        // nothing runs in a window that only the skipped instance would cross
+        assert!(instances(&repeating, (8, 25), (8, 55)).is_empty());
+        assert!(instances(&repeating, (10, 30), (12, 0)).is_empty());
+
+        // bounds between the instances round inwards
+        let bounded = train(
+            TrainBuilder::new()
+                .stop("A", hm(8, 0), hm(8, 0))
+                .stop("B", hm(8, 20), hm(8, 20))
+                .frequency(TrainFrequency::Repeating(Repetition {
+                    interval: Time::new(1800),
+                    first: Some(hm(7, 10)),
+                    last: Some(hm(9, 50)),
+                    except: Vec::new(),
+                })),
+        );
+        assert_eq!(
+            starts(instances(&bounded, (6, 0), (12, 0))),
+            [(7, 30), (8, 0), (8, 30), (9, 0), (9, 30)]
+        );
+    }
+}
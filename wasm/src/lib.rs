@@ -1,25 +1,78 @@
 use anyhow::Context;
 use ciborium::{from_reader, into_writer};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use typst_wasm_protocol::wasm_export;
 
+/// The layout engine for Rust programs, without going through CBOR. Networks and configurations
+/// are made with the builders, and `layout` places the trains and their labels.
+pub mod api;
 mod chinese_railway_type;
+#[cfg(feature = "cli")]
+pub mod cli;
 mod collision;
+mod diagnostic;
+mod foreign;
 mod input;
+mod occupancy;
 mod output;
+mod placement;
+#[cfg(feature = "cli")]
+mod render;
 mod types;
 mod utils;
-use input::{Network, NetworkConfig};
+use diagnostic::{Diagnostic, ErrorCode, with_default_code};
+use input::{Network, NetworkConfig, OccupancyConfig, SCHEMA_VERSION};
+use occupancy::OccupancyOutput;
 use output::Output;
 
+/// What the exports return instead of their output if they fail.
+#[derive(Serialize)]
+struct Failure {
+    version: u32,
+    errors: Vec<Diagnostic>,
+}
+
+/// Returns the output, or a `Failure` with the errors as records.
+fn respond(result: anyhow::Result<Vec<u8>>) -> Result<Vec<u8>, String> {
+    result.or_else(|error| {
+        let failure = Failure {
+            version: SCHEMA_VERSION,
+            errors: vec![Diagnostic::of(&error)],
+        };
+        let mut serialized_failure = Vec::new();
+        into_writer(&failure, &mut serialized_failure).map_err(|_| format_error_chain(error))?;
+        Ok(serialized_failure)
+    })
+}
+
+/// Read a document given to an export, e.g. a config.
+fn read_document<T: DeserializeOwned>(data: &[u8], name: &str) -> anyhow::Result<T> {
+    from_reader(data).with_context(|| {
+        Diagnostic::error(
+            ErrorCode::InvalidDocument,
+            format!("Failed to deserialize {name}"),
+        )
+    })
+}
+
+/// Read a network given to an export, upgrading it to the current version first.
+fn read_network(data: &[u8]) -> anyhow::Result<Network> {
+    let network: ciborium::Value = read_document(data, "network")?;
+    Network::try_from(network).context("Failed to read network")
+}
+
 #[wasm_export]
 fn process(network_data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
-    process_internal(network_data, config_data).map_err(format_error_chain)
+    respond(process_internal(network_data, config_data))
 }
 
 fn process_internal(network_data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
-    let network: Network = from_reader(network_data).context("Failed to deserialize network")?;
+    // the documents are converted outside of serde, which would flatten the errors into strings
+    let network = read_network(network_data)?;
 
-    let config: NetworkConfig = from_reader(config_data).context("Failed to deserialize config")?;
+    let config: ciborium::Value = read_document(config_data, "config")?;
+    let config = NetworkConfig::try_from(config).context("Failed to read config")?;
 
     let mut output = Output::new(config);
     output
@@ -32,6 +85,211 @@ fn process_internal(network_data: &[u8], config_data: &[u8]) -> anyhow::Result<V
     Ok(serialized_result)
 }
 
+/// The JSON Schema of the network and the config `process` takes, and of the output it returns.
+#[wasm_export]
+fn schema() -> Result<&'static [u8], String> {
+    Ok(input::JSON_SCHEMA.as_bytes())
+}
+
+#[wasm_export]
+fn process_occupancy(network_data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(process_occupancy_internal(network_data, config_data))
+}
+
+fn process_occupancy_internal(network_data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let network = read_network(network_data)?;
+
+    let config: ciborium::Value = read_document(config_data, "config")?;
+    let config = OccupancyConfig::try_from(config).context("Failed to read config")?;
+
+    let mut output = OccupancyOutput::new(config);
+    output
+        .populate(network)
+        .context("Failed to populate occupancy from network and config")?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&output, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_qetrc(data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_qetrc_internal(data, config_data))
+}
+
+fn import_qetrc_internal(data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let config: foreign::qetrc::QetrcConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::qetrc::import(data, config).map_err(|error| {
+        with_default_code(error, ErrorCode::InvalidFile, "Failed to import qETRC file")
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_oudia(data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_oudia_internal(data, config_data))
+}
+
+fn import_oudia_internal(data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let config: foreign::oudia::OudiaConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::oudia::import(data, config).map_err(|error| {
+        with_default_code(error, ErrorCode::InvalidFile, "Failed to import OuDia file")
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_gtfs(data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_gtfs_internal(data, config_data))
+}
+
+fn import_gtfs_internal(data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let config: foreign::gtfs::GtfsConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::gtfs::import(data, config).map_err(|error| {
+        with_default_code(error, ErrorCode::InvalidFile, "Failed to import GTFS feed")
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_etrc(data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_etrc_internal(data))
+}
+
+fn import_etrc_internal(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let imported = foreign::etrc::import(data).map_err(|error| {
+        with_default_code(error, ErrorCode::InvalidFile, "Failed to import ETRC file")
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_jgrpp(vehicles_data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_jgrpp_internal(vehicles_data, config_data))
+}
+
+fn import_jgrpp_internal(vehicles_data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let vehicles: Vec<foreign::jgrpp::JgrppVehicle> =
+        read_document(vehicles_data, "orders exports")?;
+
+    let config: foreign::jgrpp::JgrppConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::jgrpp::import(vehicles, config).map_err(|error| {
+        with_default_code(
+            error,
+            ErrorCode::InvalidFile,
+            "Failed to import JGRPP orders",
+        )
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_railml(data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_railml_internal(data, config_data))
+}
+
+fn import_railml_internal(data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let config: foreign::railml::RailmlConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::railml::import(data, config).map_err(|error| {
+        with_default_code(
+            error,
+            ErrorCode::InvalidFile,
+            "Failed to import railML file",
+        )
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn export_gtfs(network_data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(export_gtfs_internal(network_data, config_data))
+}
+
+fn export_gtfs_internal(network_data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let network = read_network(network_data)?;
+
+    let config: foreign::gtfs_export::GtfsExportConfig = read_document(config_data, "config")?;
+
+    let exported =
+        foreign::gtfs_export::export(&network, config).context("Failed to export GTFS feed")?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&exported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn import_spreadsheet(data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(import_spreadsheet_internal(data, config_data))
+}
+
+fn import_spreadsheet_internal(data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let config: foreign::spreadsheet::SpreadsheetConfig = read_document(config_data, "config")?;
+
+    let imported = foreign::spreadsheet::import(data, config).map_err(|error| {
+        with_default_code(
+            error,
+            ErrorCode::InvalidFile,
+            "Failed to import spreadsheet",
+        )
+    })?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&imported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
+#[wasm_export]
+fn export_spreadsheet(network_data: &[u8], config_data: &[u8]) -> Result<Vec<u8>, String> {
+    respond(export_spreadsheet_internal(network_data, config_data))
+}
+
+fn export_spreadsheet_internal(network_data: &[u8], config_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let network = read_network(network_data)?;
+
+    let config: foreign::spreadsheet::SpreadsheetConfig = read_document(config_data, "config")?;
+
+    let exported =
+        foreign::spreadsheet::export(&network, config).context("Failed to export spreadsheet")?;
+
+    let mut serialized_result = Vec::new();
+    into_writer(&exported, &mut serialized_result).context("Failed to serialize output")?;
+
+    Ok(serialized_result)
+}
+
 fn format_error_chain(error: anyhow::Error) -> String {
     let mut formatted_result = format!("Error: {error}");
     let mut current_error = error.source();
@@ -48,10 +306,5 @@ fn format_error_chain(error: anyhow::Error) -> String {
 pub fn match_type(name: &[u8]) -> Result<&'static [u8], String> {
     let name =
         std::str::from_utf8(name).map_err(|_| "Invalid UTF-8 sequence in name".to_string())?;
-    for pt in chinese_railway_type::PATTERNS.iter() {
-        if pt.regex.is_match(name) {
-            return Ok(pt.typ.as_bytes());
-        }
-    }
-    Ok("普客".as_bytes())
+    Ok(chinese_railway_type::match_type(name).as_bytes())
 }
@@ -0,0 +1,352 @@
+use crate::collision::*;
+use crate::diagnostic::*;
+use crate::input::*;
+use crate::output::{LabelPlacement, OVERFLOW_GAP};
+use crate::types::*;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct OccupancyTrack {
+    name: String,
+    main: bool,
+    /// Whether the track was added to hold trains that do not fit on the station's tracks.
+    extra: bool,
+    position: GraphLength,
+}
+
+#[derive(Serialize)]
+struct OccupancyBlock {
+    train: String,
+    /// The index of the track in `tracks`.
+    track: usize,
+    /// Whether the track is assigned in the schedule, or picked automatically.
+    assigned: bool,
+    arrival: Time,
+    departure: Time,
+    /// What the train does at the station.
+    kind: EntryKind,
+    /// The top left and the bottom right corners of the block.
+    rect: (Node, Node),
+    /// The bottom left corner of the train label.
+    label: Node,
+    /// What happened to the train label.
+    placement: LabelPlacement,
+    /// Whether the block overlaps another block on the same track.
+    conflict: bool,
+    #[serde(skip)]
+    label_size: (GraphLength, GraphLength),
+}
+
+/// A track occupancy diagram of a single station.
+#[derive(Serialize)]
+pub struct OccupancyOutput {
+    /// The version of the schema. See `SCHEMA_VERSION`.
+    version: u32,
+    collision_manager: CollisionManager,
+    tracks: Vec<OccupancyTrack>,
+    blocks: Vec<OccupancyBlock>,
+    /// Pairs of indices to `blocks` that overlap on the same track.
+    conflicts: Vec<(usize, usize)>,
+    /// Labels that could not be placed without overlapping others.
+    warnings: Vec<Diagnostic>,
+    #[serde(skip)]
+    config: OccupancyConfig,
+    /// Where the next label goes in the overflow area, and the height of the current row.
+    #[serde(skip)]
+    overflow_cursor: Option<(Node, GraphLength)>,
+}
+
+impl OccupancyOutput {
+    pub fn new(config: OccupancyConfig) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            collision_manager: CollisionManager::new(config.unit_length),
+            tracks: Vec::new(),
+            blocks: Vec::new(),
+            conflicts: Vec::new(),
+            warnings: Vec::new(),
+            config,
+            overflow_cursor: None,
+        }
+    }
+
+    pub fn populate(&mut self, network: Network) -> Result<()> {
+        let Some(station) = network.stations.get(&self.config.station) else {
+            return Err(Diagnostic::error(
+                ErrorCode::UnknownStation,
+                format!(
+                    "Station {} not found",
+                    self.config.names.display(self.config.station)
+                ),
+            )
+            .station(
+                self.config
+                    .names
+                    .get(self.config.station)
+                    .unwrap_or_default(),
+            )
+            .into());
+        };
+        self.tracks = station
+            .tracks
+            .iter()
+            .map(|track| OccupancyTrack {
+                name: track.name.clone(),
+                main: track.main,
+                extra: false,
+                position: 0.0.into(),
+            })
+            .collect();
+
+        // collect all visits to the station in the diagram
+        let mut occupations: Vec<(&Train, Time, Time, Option<u16>, EntryKind)> = Vec::new();
+        for train_id in &station.trains {
+            let Some(train) = network.trains.get(train_id) else {
+                continue;
+            };
+            let Some(schedule) = train.iter_schedule(
+                self.config.start_time,
+                self.config.end_time,
+                self.config.operating_day,
+            )?
+            else {
+                continue;
+            };
+            for entry in schedule {
+                if entry.original_entry.station != self.config.station
+                    || entry.departure < self.config.start_time
+                    || entry.arrival > self.config.end_time
+                {
+                    continue;
+                }
+                occupations.push((
+                    train,
+                    entry.arrival,
+                    entry.departure,
+                    entry.original_entry.track,
+                    entry.kind,
+                ));
+            }
+        }
+        occupations.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.name.cmp(&b.0.name)));
+
+        // occupations on each track, with the index to the block
+        let mut track_usage: Vec<Vec<(Time, Time, usize)>> = vec![Vec::new(); self.tracks.len()];
+        let overlaps = |usage: &(Time, Time, usize), arrival: Time, departure: Time| {
+            usage.0 < departure && arrival < usage.1
+        };
+
+        // place trains with assigned tracks first, since they cannot move
+        for &(train, arrival, departure, track, kind) in &occupations {
+            let Some(track) = track else {
+                continue;
+            };
+            let track = track as usize;
+            let block_idx = self.blocks.len();
+            for usage in &track_usage[track] {
+                if overlaps(usage, arrival, departure) {
+                    self.conflicts.push((usage.2, block_idx));
+                }
+            }
+            track_usage[track].push((arrival, departure, block_idx));
+            self.blocks.push(Self::make_block(
+                train, track, true, arrival, departure, kind,
+            ));
+        }
+
+        // then pick the first free track for the rest. Main tracks are preferred over sidings.
+        let mut track_order: Vec<usize> = (0..self.tracks.len()).collect();
+        track_order.sort_by_key(|&idx| !self.tracks[idx].main);
+        for &(train, arrival, departure, track, kind) in &occupations {
+            if track.is_some() {
+                continue;
+            }
+            let free_track = track_order.iter().copied().find(|&idx| {
+                !track_usage[idx]
+                    .iter()
+                    .any(|usage| overlaps(usage, arrival, departure))
+            });
+            let track = match free_track {
+                Some(track) => track,
+                None => {
+                    // add an extra track to hold the train
+                    self.tracks.push(OccupancyTrack {
+                        name: format!("?{}", self.tracks.len() + 1),
+                        main: false,
+                        extra: true,
+                        position: 0.0.into(),
+                    });
+                    track_usage.push(Vec::new());
+                    track_order.push(self.tracks.len() - 1);
+                    self.tracks.len() - 1
+                }
+            };
+            track_usage[track].push((arrival, departure, self.blocks.len()));
+            self.blocks.push(Self::make_block(
+                train, track, false, arrival, departure, kind,
+            ));
+        }
+
+        for &(first, second) in &self.conflicts {
+            self.blocks[first].conflict = true;
+            self.blocks[second].conflict = true;
+        }
+
+        self.place_blocks()
+    }
+
+    fn make_block(
+        train: &Train,
+        track: usize,
+        assigned: bool,
+        arrival: Time,
+        departure: Time,
+        kind: EntryKind,
+    ) -> OccupancyBlock {
+        OccupancyBlock {
+            train: train.name.clone(),
+            track,
+            assigned,
+            arrival,
+            departure,
+            kind,
+            rect: (Node(0.0.into(), 0.0.into()), Node(0.0.into(), 0.0.into())),
+            label: Node(0.0.into(), 0.0.into()),
+            placement: LabelPlacement::Placed,
+            conflict: false,
+            label_size: train.label_size,
+        }
+    }
+
+    /// Calculate the positions of tracks and blocks, then place the train labels.
+    fn place_blocks(&mut self) -> Result<()> {
+        let unit_length = self.config.unit_length * self.config.time_axis_scale;
+        let track_space = self.config.track_space;
+        let half_height = track_space * 0.25;
+        for (track_idx, track) in self.tracks.iter_mut().enumerate() {
+            track.position = track_space * track_idx as f64;
+        }
+
+        self.collision_manager.update_x_min(0.0.into());
+        self.collision_manager.update_x_max(
+            (self.config.end_time - self.config.start_time).to_graph_length(unit_length),
+        );
+        self.collision_manager.update_y_min(track_space * -0.5);
+        self.collision_manager
+            .update_y_max(track_space * (self.tracks.len() as f64 - 0.5));
+
+        // blocks are obstacles for labels
+        for block in &mut self.blocks {
+            let y = self.tracks[block.track].position;
+            let x_start = (block.arrival.max(self.config.start_time) - self.config.start_time)
+                .to_graph_length(unit_length);
+            let x_end = (block.departure.min(self.config.end_time) - self.config.start_time)
+                .to_graph_length(unit_length);
+            block.rect = (Node(x_start, y - half_height), Node(x_end, y + half_height));
+            self.collision_manager.add_collision(vec![
+                Node(x_start, y - half_height),
+                Node(x_end, y - half_height),
+                Node(x_end, y + half_height),
+                Node(x_start, y + half_height),
+            ])?;
+        }
+
+        // label the blocks from left to right
+        let mut block_indices: Vec<usize> = (0..self.blocks.len()).collect();
+        block_indices.sort_by_key(|&idx| (self.blocks[idx].arrival, self.blocks[idx].track));
+        for block_idx in block_indices {
+            let block = &self.blocks[block_idx];
+            let (width, height) = block.label_size;
+            let Node(x, top) = block.rect.0;
+            // labels go above the block, and move upwards if there is no space
+            let polygon = vec![
+                Node(x, top - height),
+                Node(x + width, top - height),
+                Node(x + width, top),
+                Node(x, top),
+            ];
+            let placement = match self
+                .collision_manager
+                .slide(polygon, -90.0f64.to_radians())?
+            {
+                Slide::Free(polygon) => {
+                    self.blocks[block_idx].label =
+                        self.collision_manager.add_collision(polygon)?[3];
+                    LabelPlacement::Placed
+                }
+                Slide::Blocked(polygon) => match self.config.label_fallback {
+                    LabelFallback::Drop => LabelPlacement::Dropped,
+                    LabelFallback::BestEffort => {
+                        self.blocks[block_idx].label =
+                            self.collision_manager.add_collision(polygon)?[3];
+                        LabelPlacement::BestEffort
+                    }
+                    LabelFallback::Overflow => {
+                        let corner = self.place_in_overflow(width, height)?;
+                        self.blocks[block_idx].label = corner;
+                        LabelPlacement::Overflow(corner)
+                    }
+                },
+            };
+            self.blocks[block_idx].placement = placement;
+            let decision = match placement {
+                LabelPlacement::Placed => continue,
+                LabelPlacement::Dropped => "it was dropped",
+                LabelPlacement::BestEffort => "it overlaps other labels",
+                LabelPlacement::Overflow(_) => "it was moved below the diagram",
+            };
+            let train_name = &self.blocks[block_idx].train;
+            self.warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::LabelPlacementFailed,
+                    format!(
+                        "No place was found for the label of train '{}', so {}",
+                        train_name, decision
+                    ),
+                )
+                .train(train_name),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Place a label in the overflow area below the tracks, in rows from the left. Returns the
+    /// bottom left corner of the label.
+    fn place_in_overflow(
+        &mut self,
+        label_width: GraphLength,
+        label_height: GraphLength,
+    ) -> Result<Node> {
+        let graph_width = (self.config.end_time - self.config.start_time)
+            .to_graph_length(self.config.unit_length * self.config.time_axis_scale);
+        let (mut cursor, mut row_height) = self.overflow_cursor.unwrap_or_else(|| {
+            let graph_height = self.config.track_space * (self.tracks.len() as f64 - 0.5);
+            (
+                Node(0.0.into(), graph_height + OVERFLOW_GAP.into()),
+                0.0.into(),
+            )
+        });
+        if cursor.0 > 0.0.into() && cursor.0 + label_width > graph_width {
+            cursor = Node(0.0.into(), cursor.1 + row_height + OVERFLOW_GAP.into());
+            row_height = 0.0.into();
+        }
+        let polygon = vec![
+            cursor,
+            Node(cursor.0 + label_width, cursor.1),
+            Node(cursor.0 + label_width, cursor.1 + label_height),
+            Node(cursor.0, cursor.1 + label_height),
+        ];
+        let corner = self.collision_manager.add_collision(polygon)?[3];
+        self.overflow_cursor = Some((
+            Node(cursor.0 + label_width + OVERFLOW_GAP.into(), cursor.1),
+            if label_height > row_height {
+                label_height
+            } else {
+                row_height
+            },
+        ));
+        Ok(corner)
+    }
+}
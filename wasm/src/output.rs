@@ -1,12 +1,27 @@
 use crate::collision::*;
+use crate::diagnostic::*;
 use crate::input::*;
+use crate::placement::{self, Candidate};
 use crate::types::*;
 use crate::utils::intersection;
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use multimap::MultiMap;
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 
+/// The space between the labels in the overflow area.
+pub(crate) const OVERFLOW_GAP: f64 = 3.0;
+/// How many times a candidate label is moved away from its line, by the height of the label. If a
+/// greedy layout would move the label further, the candidates reach as far in up to
+/// `MAX_LEADER_STEPS` steps.
+const LEADER_STEPS: u32 = 8;
+const MAX_LEADER_STEPS: u32 = 16;
+/// How far candidate labels are moved along their lines, by the width of the label.
+const ALONG_LINE_SHIFTS: [f64; 2] = [0.0, 0.5];
+/// The cost of putting a label on the other side of its line.
+const FLIP_COST: f64 = 10.0;
+
+#[derive(Clone, Copy)]
 enum LabelDirection {
     Up,
     Down,
@@ -14,56 +29,209 @@ enum LabelDirection {
     // Right,
 }
 
+#[derive(Clone, Copy)]
 enum LabelPosition {
     Beg(LabelDirection),
     End(LabelDirection),
 }
 
+/// Where a label goes on its edge, and the angle of the label.
+#[derive(Clone, Copy)]
+struct LabelPlace {
+    position: LabelPosition,
+    label_angle: f64,
+}
+
+#[derive(Serialize)]
+pub struct OutputTrain {
+    pub(crate) edges: Vec<OutputEdge>,
+    pub(crate) name: String,
+}
+
 #[derive(Serialize)]
-struct OutputTrain {
-    edges: Vec<OutputEdge>,
-    name: String,
+pub struct OutputEdge {
+    pub(crate) edges: Vec<Node>,
+    /// The schedule entries drawn on station lines along the edge.
+    pub(crate) entries: Vec<OutputEntry>,
+    pub(crate) labels: Option<OutputLabel>,
 }
 
 #[derive(Serialize)]
-struct OutputEdge {
-    edges: Vec<Node>,
-    labels: Option<OutputLabel>,
+pub struct OutputEntry {
+    /// Where the train arrives at the station line.
+    pub(crate) arrival: Node,
+    /// Where the train departs from the station line. The same as `arrival` if the train does
+    /// not dwell at the station.
+    pub(crate) departure: Node,
+    /// The index of the track the train uses.
+    pub(crate) track: Option<u16>,
+    /// Whether the train does not list the station, and the time is interpolated.
+    pub(crate) interpolated: bool,
+    /// What the train does at the station.
+    pub(crate) kind: EntryKind,
 }
 
 #[derive(Serialize)]
-struct OutputLabel {
-    angles: (f64, f64),
+pub(crate) struct OutputLabel {
+    pub(crate) angles: (f64, f64),
+    /// What happened to the labels at the beginning and the end of the edge.
+    pub(crate) placements: (LabelPlacement, LabelPlacement),
+}
+
+/// What happened to a train label.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelPlacement {
+    /// The label was placed where it does not overlap other labels.
+    Placed,
+    /// No place was found for the label, and it is not drawn.
+    Dropped,
+    /// No place was found for the label, and it is drawn where it overlaps the least.
+    BestEffort,
+    /// No place was found for the label, and it is drawn level in the area below the diagram.
+    /// Holds the bottom left corner of the label.
+    Overflow(Node),
 }
 
 #[derive(Serialize)]
 pub struct Output {
-    collision_manager: CollisionManager,
-    trains: Vec<OutputTrain>,
-    graph_intervals: Vec<GraphLength>,
+    /// The version of the schema. See `SCHEMA_VERSION`.
+    version: u32,
+    pub(crate) collision_manager: CollisionManager,
+    pub(crate) trains: Vec<OutputTrain>,
+    pub(crate) graph_intervals: Vec<GraphLength>,
+    /// Parts of the network and the config that were ignored or approximated.
+    pub(crate) warnings: Vec<Diagnostic>,
+    /// The actual lengths of the intervals in `graph_intervals`, if known.
     #[serde(skip)]
-    station_draw_info: Vec<(StationID, GraphLength, LineCollisionManager)>,
+    interval_lengths: Vec<Option<IntervalLength>>,
+    #[serde(skip)]
+    pub(crate) station_draw_info: Vec<(StationID, GraphLength, LineCollisionManager)>,
     #[serde(skip)]
     station_indices: MultiMap<StationID, usize>,
+    /// Where the next label goes in the overflow area, and the height of the current row.
+    #[serde(skip)]
+    overflow_cursor: Option<(Node, GraphLength)>,
     #[serde(skip)]
-    config: NetworkConfig,
+    pub(crate) config: NetworkConfig,
+}
+
+impl OutputTrain {
+    /// The name of the train.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The lines drawn for the train. A train is split into several edges where it leaves the
+    /// diagram, or where it jumps between station lines.
+    pub fn edges(&self) -> &[OutputEdge] {
+        &self.edges
+    }
+}
+
+impl OutputEdge {
+    /// The points of the line, including the labels at both ends.
+    pub fn nodes(&self) -> &[Node] {
+        &self.edges
+    }
+
+    /// The schedule entries drawn on station lines along the edge.
+    pub fn entries(&self) -> &[OutputEntry] {
+        &self.entries
+    }
+
+    /// The angles of the labels at the beginning and the end of the edge, in radians.
+    pub fn label_angles(&self) -> Option<(f64, f64)> {
+        self.labels.as_ref().map(|labels| labels.angles)
+    }
+
+    /// What happened to the labels at the beginning and the end of the edge.
+    pub fn label_placements(&self) -> Option<(LabelPlacement, LabelPlacement)> {
+        self.labels.as_ref().map(|labels| labels.placements)
+    }
+}
+
+impl OutputEntry {
+    /// Where the train arrives at the station line.
+    pub fn arrival(&self) -> Node {
+        self.arrival
+    }
+
+    /// Where the train departs from the station line.
+    pub fn departure(&self) -> Node {
+        self.departure
+    }
+
+    /// The index of the track the train uses.
+    pub fn track(&self) -> Option<u16> {
+        self.track
+    }
+
+    /// Whether the train does not list the station, and the time is interpolated.
+    pub fn interpolated(&self) -> bool {
+        self.interpolated
+    }
+
+    /// What the train does at the station.
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
 }
 
 impl Output {
+    /// The trains that are drawn.
+    pub fn trains(&self) -> &[OutputTrain] {
+        &self.trains
+    }
+
+    /// The heights of the intervals between the station lines, from the top.
+    pub fn graph_intervals(&self) -> &[GraphLength] {
+        &self.graph_intervals
+    }
+
+    /// Parts of the network and the config that were ignored or approximated.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// The names of the stations drawn and the positions of their lines, from the top.
+    pub fn stations(&self) -> impl Iterator<Item = (&str, GraphLength)> {
+        self.station_draw_info.iter().map(|(station, position, _)| {
+            (
+                self.config.names.get(*station).unwrap_or_default(),
+                *position,
+            )
+        })
+    }
+
+    /// The area taken by the diagram and its labels, as the top left and the bottom right corners.
+    pub fn bounds(&self) -> (Node, Node) {
+        let bounds = &self.collision_manager;
+        (
+            Node(bounds.x_min, bounds.y_min),
+            Node(bounds.x_max, bounds.y_max),
+        )
+    }
+
     pub fn new(config: NetworkConfig) -> Self {
         let collision_manager = CollisionManager::new(config.unit_length);
 
         Self {
+            version: SCHEMA_VERSION,
             collision_manager,
             trains: Vec::new(),
+            warnings: Vec::new(),
             station_draw_info: Vec::with_capacity(config.stations_to_draw.len()),
             station_indices: MultiMap::with_capacity(config.stations_to_draw.len()),
+            overflow_cursor: None,
             graph_intervals: Vec::with_capacity(config.stations_to_draw.len().saturating_sub(1)),
+            interval_lengths: Vec::with_capacity(config.stations_to_draw.len().saturating_sub(1)),
             config,
         }
     }
 
-    pub fn populate(&mut self, network: Network) -> Result<()> {
+    pub fn populate(&mut self, mut network: Network) -> Result<()> {
+        self.warnings.append(&mut network.warnings);
         let train_ids_to_draw =
             self.make_station_draw_info(&network.stations, &network.intervals)?;
 
@@ -86,28 +254,76 @@ impl Output {
                 .map_or(0.0, |(_, y, _)| y.value()),
         ));
 
-        self.trains.reserve(train_ids_to_draw.len());
+        let mut trains_to_draw = Vec::with_capacity(train_ids_to_draw.len());
         for train_id in train_ids_to_draw {
-            let output_train = self.make_train(network.trains.get(&train_id).unwrap())?;
+            let Some(train) = network.trains.get(&train_id) else {
+                return Err(anyhow!(
+                    "Train {} not found",
+                    network.names.display(train_id)
+                ));
+            };
+            trains_to_draw.push(train);
+        }
+        // labels are placed greedily, so the trains laid out first get the preferred spots
+        trains_to_draw.sort_by_cached_key(|train| self.layout_key(train));
+
+        self.trains.reserve(trains_to_draw.len());
+        for train in &trains_to_draw {
+            let output_train = self
+                .make_train(train)
+                .map_err(|error| with_train(error, &train.name))
+                .with_context(|| format!("Failed to draw train '{}'", train.name))?;
             self.trains.push(output_train);
         }
+        if self.config.label_layout == LabelLayout::Global {
+            self.place_labels_globally(&trains_to_draw)?;
+        }
 
         Ok(())
     }
 
+    /// Trains are laid out in the ascending order of their keys.
+    fn layout_key<'a>(&self, train: &'a Train) -> (i64, Option<Time>, &'a str) {
+        let rank = match self.config.layout_order {
+            LayoutOrder::FirstDeparture => 0,
+            LayoutOrder::Priority => -i64::from(train.priority),
+            LayoutOrder::Class => train
+                .class()
+                .and_then(|class| {
+                    self.config
+                        .class_priorities
+                        .iter()
+                        .position(|other| other == class)
+                })
+                .unwrap_or(self.config.class_priorities.len())
+                as i64,
+        };
+        (rank, train.first_departure(), &train.name)
+    }
+
     fn make_station_draw_info(
         &mut self,
         stations: &HashMap<StationID, Station>,
         intervals: &HashMap<IntervalID, Interval>,
     ) -> Result<HashSet<TrainID>> {
         if self.config.stations_to_draw.is_empty() {
-            return Err(anyhow!("No stations to draw"));
+            return Err(
+                Diagnostic::error(ErrorCode::NoStationsToDraw, "No stations to draw").into(),
+            );
         }
 
         // check if all stations to draw exist
         for &station_id in &self.config.stations_to_draw {
             if !stations.contains_key(&station_id) {
-                return Err(anyhow!("Station {} not found", station_id));
+                let mut diagnostic = Diagnostic::error(
+                    ErrorCode::UnknownStation,
+                    format!(
+                        "Station {} not found",
+                        self.config.names.display(station_id)
+                    ),
+                );
+                diagnostic.station = self.config.names.get(station_id).map(str::to_string);
+                return Err(diagnostic.into());
             }
         }
 
@@ -131,23 +347,34 @@ impl Output {
             .push((first_station, position, LineCollisionManager::new()));
         self.station_indices.insert(first_station, 0);
         // handle the first station label
-        let (width, height) = stations.get(&first_station).unwrap().label_size;
-        self.collision_manager.add_collision(vec![
-            Node(label_start - width - 3.0.into(), position - height * 0.5),
-            Node(label_start - 3.0.into(), position - height * 0.5),
-            Node(label_start - 3.0.into(), position + height * 0.5),
-            Node(label_start - width - 3.0.into(), position + height * 0.5),
-        ])?;
+        let station = stations.get(&first_station).unwrap();
+        let (width, height) = station.label_size;
+        self.collision_manager
+            .add_collision(vec![
+                Node(label_start - width - 3.0.into(), position - height * 0.5),
+                Node(label_start - 3.0.into(), position - height * 0.5),
+                Node(label_start - 3.0.into(), position + height * 0.5),
+                Node(label_start - width - 3.0.into(), position + height * 0.5),
+            ])
+            .with_context(|| format!("Failed to place the label of station '{}'", station.name))?;
 
         for (window_idx, window) in self.config.stations_to_draw.windows(2).enumerate() {
             let [start_station, end_station] = window else {
                 continue;
             };
             if *start_station == *end_station {
-                return Err(anyhow!("Consecutive stations cannot be the same"));
+                let mut diagnostic = Diagnostic::error(
+                    ErrorCode::InvalidStationOrder,
+                    format!(
+                        "Consecutive stations cannot be the same: {}",
+                        self.config.names.display(*start_station)
+                    ),
+                );
+                diagnostic.station = self.config.names.get(*start_station).map(str::to_string);
+                return Err(diagnostic.into());
             }
 
-            let interval_length = match (
+            let actual_length = match (
                 intervals
                     .get(&(*start_station, *end_station))
                     .map(|it| it.length),
@@ -156,36 +383,51 @@ impl Output {
                     .map(|it| it.length),
             ) {
                 (Some(len1), Some(len2)) => {
-                    IntervalLength::new((len1.meters() + len2.meters()) / 2)
-                        .to_graph_length(unit_length, self.config.position_axis_scale_mode)
-                }
-                (Some(len), None) | (None, Some(len)) => {
-                    len.to_graph_length(unit_length, self.config.position_axis_scale_mode)
+                    Some(IntervalLength::new((len1.meters() + len2.meters()) / 2))
                 }
-                (None, None) => {
+                (Some(len), None) | (None, Some(len)) => Some(len),
+                (None, None) => None,
+            };
+            let interval_length = match actual_length {
+                Some(len) => len.to_graph_length(unit_length, self.config.position_axis_scale_mode),
+                None => {
                     // return Err(anyhow!(
                     //     "No interval found between stations {} and {}",
                     //     start_station,
                     //     end_station
                     // ));
+                    self.warnings.push(Diagnostic::warning(
+                        ErrorCode::MissingInterval,
+                        format!(
+                            "No interval found between stations {} and {}, drawn one unit apart",
+                            self.config.names.display(*start_station),
+                            self.config.names.display(*end_station)
+                        ),
+                    ));
                     self.config.unit_length
                 }
             };
 
             self.graph_intervals.push(interval_length);
+            self.interval_lengths.push(actual_length);
             position += interval_length;
             self.station_draw_info
                 .push((*end_station, position, LineCollisionManager::new()));
             self.station_indices.insert(*end_station, window_idx + 1);
 
-            let (width, height) = stations.get(end_station).unwrap().label_size;
+            let station = stations.get(end_station).unwrap();
+            let (width, height) = station.label_size;
             // insert station label. nodes are in absolute coordinates
-            self.collision_manager.add_collision(vec![
-                Node(label_start - width - 3.0.into(), position - height * 0.5),
-                Node(label_start - 3.0.into(), position - height * 0.5),
-                Node(label_start - 3.0.into(), position + height * 0.5),
-                Node(label_start - width - 3.0.into(), position + height * 0.5),
-            ])?;
+            self.collision_manager
+                .add_collision(vec![
+                    Node(label_start - width - 3.0.into(), position - height * 0.5),
+                    Node(label_start - 3.0.into(), position - height * 0.5),
+                    Node(label_start - 3.0.into(), position + height * 0.5),
+                    Node(label_start - width - 3.0.into(), position + height * 0.5),
+                ])
+                .with_context(|| {
+                    format!("Failed to place the label of station '{}'", station.name)
+                })?;
         }
 
         Ok(train_ids)
@@ -193,7 +435,11 @@ impl Output {
 
     /// Make edges and place labels for each train
     fn make_train(&mut self, train: &Train) -> Result<OutputTrain> {
-        let Some(schedule) = train.iter_schedule(self.config.start_time, self.config.end_time)?
+        let Some(schedule) = train.iter_schedule(
+            self.config.start_time,
+            self.config.end_time,
+            self.config.operating_day,
+        )?
         else {
             return Ok(OutputTrain {
                 edges: Vec::new(),
@@ -201,11 +447,30 @@ impl Output {
             });
         };
         let schedule: Vec<IterateScheduleEntry> = schedule.collect();
+        // add the drawn stations that the train passes without listing them
+        let passes = self.interpolate_passes(&schedule);
+        let mut schedule_with_passes: Vec<IterateScheduleEntry> =
+            Vec::with_capacity(schedule.len() + passes.len());
+        let mut passes_iter = passes.iter().peekable();
+        for (entry_idx, entry) in schedule.into_iter().enumerate() {
+            while let Some((_, time, pass)) = passes_iter.next_if(|(idx, ..)| *idx == entry_idx) {
+                schedule_with_passes.push(IterateScheduleEntry {
+                    arrival: *time,
+                    departure: *time,
+                    clear: false,
+                    interpolated: true,
+                    kind: EntryKind::Pass,
+                    original_entry: pass,
+                });
+            }
+            schedule_with_passes.push(entry);
+        }
+        let schedule = schedule_with_passes;
         // the GLOBAL edge group
         let mut output_edges: Vec<OutputEdge> = Vec::new();
         // the LOCAL edge group containing all WIP edges. The second element in the tuple
         // is the index to station_draw_info, which holds all station lines.
-        let mut local_edges: Vec<(Vec<Node>, usize)> = Vec::new();
+        let mut local_edges: Vec<(OutputEdge, usize)> = Vec::new();
         let unit_length = self.config.unit_length * self.config.time_axis_scale;
         let time_offset = -self.config.start_time;
         let map_end = (self.config.end_time + time_offset).to_graph_length(unit_length);
@@ -229,72 +494,129 @@ impl Output {
                 if local_edges.is_empty() {
                     continue;
                 }
-                output_edges.extend(std::mem::take(&mut local_edges).into_iter().map(
-                    |(edge_nodes, _)| OutputEdge {
-                        edges: edge_nodes,
-                        labels: None,
-                    },
-                ));
+                output_edges.extend(
+                    std::mem::take(&mut local_edges)
+                        .into_iter()
+                        .map(|(edge, _)| edge),
+                );
                 continue;
             };
-            let mut remaining_edges: Vec<(Vec<Node>, usize)> = Vec::new();
-            let mut remaining_edge: Option<(Vec<Node>, usize)> = None;
+            let mut remaining_edges: Vec<(OutputEdge, usize)> = Vec::new();
+            let mut remaining_edge: Option<(OutputEdge, usize)> = None;
             for &current_line_index in current_indices {
                 if let Some(it) = remaining_edge.take() {
                     remaining_edges.push(it);
                 };
                 let (_, current_base_height, ref mut current_collision_manager) =
                     self.station_draw_info[current_line_index];
-                let current_height = if ce.departure != ce.arrival {
-                    current_collision_manager
-                        .resolve_collisions(current_edge_start, current_edge_end)?
-                        as f64
-                        * self.config.line_stack_space
-                        + current_base_height
+                let current_height = if ce.kind.is_stop() && ce.departure != ce.arrival {
+                    // only real stops are stacked.
+                    // stack by the assigned track, or by the first free level if there is none
+                    let level = match ce.original_entry.track {
+                        Some(track) => current_collision_manager.occupy(
+                            current_edge_start,
+                            current_edge_end,
+                            track as usize,
+                        ),
+                        None => current_collision_manager
+                            .resolve_collisions(current_edge_start, current_edge_end),
+                    };
+                    let level = match level {
+                        Ok(level) => level,
+                        Err(error) if code_of(&error) == Some(ErrorCode::LineStackingFailed) => {
+                            // draw it on the station line, over the other lines
+                            self.warnings.push(
+                                Diagnostic::warning(
+                                    ErrorCode::LineStackingFailed,
+                                    format!(
+                                        "There is no room to stack the stop of train '{}' at station {}, so it is drawn on the station line",
+                                        train.name,
+                                        self.config.names.display(ce.original_entry.station)
+                                    ),
+                                )
+                                .train(&train.name)
+                                .times([ce.arrival, ce.departure]),
+                            );
+                            current_collision_manager.occupy(
+                                current_edge_start,
+                                current_edge_end,
+                                0,
+                            )?
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    level as f64 * self.config.line_stack_space + current_base_height
                 } else {
                     current_base_height
                 };
                 let previous_line_index = local_edges
                     .iter()
                     .position(|(_, idx)| current_line_index.abs_diff(*idx) <= 1);
-                let mut matched_edge = if ce.clear || previous_line_index.is_none() {
-                    // there is no matching edge, so create a new one
-                    Vec::new()
-                } else {
+                let mut matched_edge = match previous_line_index {
                     // there is a matching edge in the local edges
-                    let previous_line_index = previous_line_index.unwrap();
-                    local_edges.swap_remove(previous_line_index).0
+                    Some(previous_line_index) if !ce.clear => {
+                        local_edges.swap_remove(previous_line_index).0
+                    }
+                    // there is no matching edge, so create a new one
+                    _ => OutputEdge {
+                        edges: Vec::new(),
+                        entries: Vec::new(),
+                        labels: None,
+                    },
                 };
+                let entry_start = matched_edge.edges.len();
 
                 if ce.arrival < self.config.start_time {
                     if ce.departure < self.config.start_time {
                         // do nothing
                     } else if ce.departure <= self.config.end_time {
-                        matched_edge.push(Node(map_start, current_height));
+                        matched_edge.edges.push(Node(map_start, current_height));
                         if ce.departure != ce.arrival {
-                            matched_edge.push(Node(current_edge_end, current_height));
+                            matched_edge
+                                .edges
+                                .push(Node(current_edge_end, current_height));
                         }
                     } else {
-                        matched_edge.push(Node(map_start, current_height));
+                        matched_edge.edges.push(Node(map_start, current_height));
                         if ce.departure != ce.arrival {
-                            matched_edge.push(Node(map_end, current_height));
+                            matched_edge.edges.push(Node(map_end, current_height));
                         }
                     }
                 } else if ce.arrival <= self.config.end_time {
                     // ce.departure is always >= ce.arrival
                     if ce.departure <= self.config.end_time {
-                        matched_edge.push(Node(current_edge_start, current_height));
+                        matched_edge
+                            .edges
+                            .push(Node(current_edge_start, current_height));
                         if ce.departure != ce.arrival {
-                            matched_edge.push(Node(current_edge_end, current_height));
+                            matched_edge
+                                .edges
+                                .push(Node(current_edge_end, current_height));
                         }
                     } else {
-                        matched_edge.push(Node(current_edge_start, current_height));
+                        matched_edge
+                            .edges
+                            .push(Node(current_edge_start, current_height));
                         if ce.departure != ce.arrival {
-                            matched_edge.push(Node(map_end, current_height));
+                            matched_edge.edges.push(Node(map_end, current_height));
                         }
                     }
                 }
 
+                // record where the entry is drawn on the station line
+                if let (Some(&arrival), Some(&departure)) = (
+                    matched_edge.edges.get(entry_start),
+                    matched_edge.edges.last(),
+                ) {
+                    matched_edge.entries.push(OutputEntry {
+                        arrival,
+                        departure,
+                        track: ce.original_entry.track,
+                        interpolated: ce.interpolated,
+                        kind: ce.kind,
+                    });
+                }
+
                 // The same station cannot appear twice in a row, neither can they appear
                 // with only one station in between. This means that for this specific entry,
                 // there can only be one adjacent next station on the graph.
@@ -341,15 +663,15 @@ impl Output {
                     if ne.arrival < self.config.start_time {
                         // do nothing
                     } else if ne.arrival <= self.config.end_time {
-                        matched_edge.push(start_intersection()?);
+                        matched_edge.edges.push(start_intersection()?);
                     } else {
-                        matched_edge.push(start_intersection()?);
-                        matched_edge.push(end_intersection()?);
+                        matched_edge.edges.push(start_intersection()?);
+                        matched_edge.edges.push(end_intersection()?);
                     }
                 } else if ne.arrival <= self.config.end_time {
                     // do nothing for now
                 } else {
-                    matched_edge.push(end_intersection()?);
+                    matched_edge.edges.push(end_intersection()?);
                 }
                 remaining_edge = Some((matched_edge, current_line_index));
             }
@@ -357,12 +679,11 @@ impl Output {
                 remaining_edges.push(remaining_edge);
             }
             if !local_edges.is_empty() {
-                output_edges.extend(std::mem::take(&mut local_edges).into_iter().map(
-                    |(edge_nodes, _)| OutputEdge {
-                        edges: edge_nodes,
-                        labels: None,
-                    },
-                ));
+                output_edges.extend(
+                    std::mem::take(&mut local_edges)
+                        .into_iter()
+                        .map(|(edge, _)| edge),
+                );
             }
             local_edges = remaining_edges;
             if let Some(ne) = ne {
@@ -371,19 +692,28 @@ impl Output {
         }
 
         // handle the remaining local edges
-        output_edges.extend(local_edges.into_iter().map(|(edge_nodes, _)| OutputEdge {
-            edges: edge_nodes,
-            labels: None,
-        }));
+        output_edges.extend(local_edges.into_iter().map(|(edge, _)| edge));
         // Filter out edges with less than 2 nodes before processing labels
         output_edges.retain(|output_edge| output_edge.edges.len() >= 2);
 
+        // the labels of all trains are placed together once every train is laid out
+        if self.config.label_layout == LabelLayout::Global {
+            return Ok(OutputTrain {
+                edges: output_edges,
+                name: train.name.clone(),
+            });
+        }
+
         // iterate over all edges and add collision nodes
         let (label_width, label_height) = train.label_size;
         for output_edge in &mut output_edges {
-            let angles =
+            let ((start_angle, start_placement), (end_angle, end_placement)) =
                 self.add_train_labels_to_edge(&mut output_edge.edges, label_width, label_height)?;
-            output_edge.labels = Some(OutputLabel { angles })
+            self.warn_about_labels(&train.name, (start_placement, end_placement));
+            output_edge.labels = Some(OutputLabel {
+                angles: (start_angle, end_angle),
+                placements: (start_placement, end_placement),
+            })
         }
 
         Ok(OutputTrain {
@@ -392,6 +722,96 @@ impl Output {
         })
     }
 
+    /// Find the drawn stations between two consecutive entries that are not next to each other
+    /// on the diagram, and interpolate the times the train passes them by the interval lengths.
+    /// Returns the index of the entry each pass goes before, the time, and the pass itself.
+    fn interpolate_passes(
+        &self,
+        schedule: &[IterateScheduleEntry],
+    ) -> Vec<(usize, Time, ScheduleEntry)> {
+        let mut passes = Vec::new();
+        for (entry_idx, window) in schedule.windows(2).enumerate() {
+            let [ce, ne] = window else {
+                continue;
+            };
+            if ne.clear {
+                continue;
+            }
+            let (Some(current_indices), Some(next_indices)) = (
+                self.station_indices.get_vec(&ce.original_entry.station),
+                self.station_indices.get_vec(&ne.original_entry.station),
+            ) else {
+                continue;
+            };
+            let line_pairs = current_indices
+                .iter()
+                .flat_map(|&i| next_indices.iter().map(move |&j| (i, j)));
+            if line_pairs.clone().any(|(i, j)| i.abs_diff(j) <= 1) {
+                // the stations are already next to each other
+                continue;
+            }
+            let Some((current_line_index, next_line_index)) =
+                line_pairs.min_by_key(|(i, j)| i.abs_diff(*j))
+            else {
+                continue;
+            };
+            let (low, high) = if current_line_index < next_line_index {
+                (current_line_index, next_line_index)
+            } else {
+                (next_line_index, current_line_index)
+            };
+            // use the actual lengths if all of them are known, otherwise use the lengths on the
+            // diagram
+            let lengths: Vec<f64> = if self.interval_lengths[low..high].iter().all(Option::is_some)
+            {
+                self.interval_lengths[low..high]
+                    .iter()
+                    .flatten()
+                    .map(|len| len.meters() as f64)
+                    .collect()
+            } else {
+                self.graph_intervals[low..high]
+                    .iter()
+                    .map(|len| len.value())
+                    .collect()
+            };
+            let total_length: f64 = lengths.iter().sum();
+            let total_time = (ne.arrival - ce.departure).seconds() as f64;
+            let mut travelled_length = 0.0;
+            let intermediate_indices: Box<dyn Iterator<Item = usize>> =
+                if current_line_index < next_line_index {
+                    Box::new(low + 1..high)
+                } else {
+                    Box::new((low + 1..high).rev())
+                };
+            for line_index in intermediate_indices {
+                // the interval between the previous station and this one
+                travelled_length += if current_line_index < next_line_index {
+                    lengths[line_index - 1 - low]
+                } else {
+                    lengths[line_index - low]
+                };
+                let ratio = if total_length > 0.0 {
+                    travelled_length / total_length
+                } else {
+                    0.0
+                };
+                let time = ce.departure + Time::new((total_time * ratio).round() as i32);
+                passes.push((
+                    entry_idx + 1,
+                    time,
+                    ScheduleEntry {
+                        departure: time,
+                        station: self.station_draw_info[line_index].0,
+                        track: None,
+                        kind: EntryKind::Pass,
+                    },
+                ));
+            }
+        }
+        passes
+    }
+
     fn create_label_polygon(
         &self,
         anchor: Node,
@@ -444,70 +864,55 @@ impl Output {
         }
     }
 
+    /// Warn about the labels at the beginning and the end of an edge that were not placed.
+    fn warn_about_labels(
+        &mut self,
+        train_name: &str,
+        placements: (LabelPlacement, LabelPlacement),
+    ) {
+        for (placement, end) in [(placements.0, "beginning"), (placements.1, "end")] {
+            let decision = match placement {
+                LabelPlacement::Placed => continue,
+                LabelPlacement::Dropped => "it was dropped",
+                LabelPlacement::BestEffort => "it overlaps other labels",
+                LabelPlacement::Overflow(_) => "it was moved below the diagram",
+            };
+            self.warnings.push(
+                Diagnostic::warning(
+                    ErrorCode::LabelPlacementFailed,
+                    format!(
+                        "No place was found for the label at the {} of an edge of train '{}', so {}",
+                        end, train_name, decision
+                    ),
+                )
+                .train(train_name),
+            );
+        }
+    }
+
     fn add_train_labels_to_edge(
         &mut self,
         edge: &mut Vec<Node>,
         label_width: GraphLength,
         label_height: GraphLength,
-    ) -> Result<(f64, f64)> {
+    ) -> Result<((f64, LabelPlacement), (f64, LabelPlacement))> {
         let current_edge_start = *edge.first().unwrap();
         let current_edge_end = *edge.last().unwrap();
 
-        let start_label_direction = if edge.len() > 2 {
-            // check the first three nodes to determine general direction
-            let (first, second, third) = (edge[0], edge[1], edge[2]);
-            // check if the general trend is upwards
-            // typst logic is reversed, so the directions are reversed
-            if (second.1 > first.1) || (third.1 > second.1) {
-                LabelPosition::Beg(LabelDirection::Down)
-            } else {
-                LabelPosition::Beg(LabelDirection::Up)
-            }
-        } else {
-            let (first, last) = (edge[0], edge[1]);
-            if first.1 < last.1 {
-                LabelPosition::Beg(LabelDirection::Down)
-            } else {
-                LabelPosition::Beg(LabelDirection::Up)
-            }
-        };
-
         // Add label at the beginning of the edge
-        let start_label_angle = self.add_label_to_edge(
+        let start_label = self.add_label_to_edge(
             edge,
             current_edge_start,
             label_width,
             label_height,
-            &start_label_direction,
+            &start_label_position(edge),
         )?;
 
-        // Add label at the end of the edge (only if edge has more than one node)
-
         // Determine direction for end label (might be different from beginning)
-        let end_label_direction = if edge.len() > 2 {
-            // check the last three nodes to determine general direction
-            let (last, second_last, third_last) = (
-                edge[edge.len() - 1],
-                edge[edge.len() - 2],
-                edge[edge.len() - 3],
-            );
-            // check if the general trend is upwards
-            if (last.1 < second_last.1) || (second_last.1 < third_last.1) {
-                LabelPosition::End(LabelDirection::Up)
-            } else {
-                LabelPosition::End(LabelDirection::Down)
-            }
-        } else {
-            let (first, last) = (edge[0], edge[1]);
-            if last.1 < first.1 {
-                LabelPosition::End(LabelDirection::Up)
-            } else {
-                LabelPosition::End(LabelDirection::Down)
-            }
-        };
+        let end_label_direction = end_label_position(edge);
 
         // Insert at the end
-        let end_label_angle = self.add_label_to_edge(
+        let end_label = self.add_label_to_edge(
             edge,
             current_edge_end,
             label_width,
@@ -515,7 +920,268 @@ impl Output {
             &end_label_direction,
         )?;
 
-        Ok((start_label_angle, end_label_angle))
+        Ok((start_label, end_label))
+    }
+
+    /// Place the labels of all trains together, choosing among candidate places for each label.
+    /// `trains` are the trains of `self.trains`, in the same order. The search starts from the
+    /// places of the greedy layout, and never places fewer labels than it.
+    fn place_labels_globally(&mut self, trains: &[&Train]) -> Result<()> {
+        let mut greedy = self.greedy_labels(trains)?.into_iter();
+        // the labels at both ends of each edge, in the layout order, with their anchors
+        let mut slots: Vec<(usize, usize, Node, LabelPosition)> = Vec::new();
+        let mut labels: Vec<Vec<Candidate<LabelPlace>>> = Vec::new();
+        let mut initial: Vec<Option<usize>> = Vec::new();
+        for (train_idx, (output_train, train)) in self.trains.iter().zip(trains).enumerate() {
+            let (label_width, label_height) = train.label_size;
+            for (edge_idx, output_edge) in output_train.edges.iter().enumerate() {
+                let edge = &output_edge.edges;
+                for (anchor, position) in [
+                    (edge[0], start_label_position(edge)),
+                    (edge[edge.len() - 1], end_label_position(edge)),
+                ] {
+                    let greedy = greedy.next().flatten();
+                    // candidates reach as far from the line as the greedy place
+                    let reach = greedy.as_ref().map_or(0.0, |candidate| candidate.cost);
+                    let mut candidates =
+                        self.label_candidates(edge, label_width, label_height, position, reach)?;
+                    initial.push(greedy.map(|candidate| {
+                        candidates.push(candidate);
+                        candidates.len() - 1
+                    }));
+                    labels.push(candidates);
+                    slots.push((train_idx, edge_idx, anchor, position));
+                }
+            }
+        }
+
+        let choices = placement::search(
+            &labels,
+            &initial,
+            self.config.unit_length,
+            self.config.label_search_steps,
+            self.config.label_search_seed,
+        );
+        // fewer overlaps could still leave fewer labels that fit, since the search only compares
+        // labels with each other
+        let choices =
+            if self.count_fitting(&labels, &choices)? < self.count_fitting(&labels, &initial)? {
+                initial
+            } else {
+                choices
+            };
+
+        // the labels that fit are placed first, so that the others could not push them away
+        let mut results: Vec<Option<(f64, LabelPlacement)>> = vec![None; labels.len()];
+        for (label_idx, choice) in choices.iter().enumerate() {
+            let Some(candidate) = choice.map(|choice| &labels[label_idx][choice]) else {
+                continue;
+            };
+            if !self
+                .collision_manager
+                .fits(&candidate.polygon, candidate.angle)?
+            {
+                continue;
+            }
+            let (train_idx, edge_idx, ..) = slots[label_idx];
+            let polygon = self
+                .collision_manager
+                .add_collision(candidate.polygon.clone())?;
+            attach_label(
+                &mut self.trains[train_idx].edges[edge_idx].edges,
+                polygon,
+                &candidate.place.position,
+            );
+            results[label_idx] = Some((candidate.place.label_angle, LabelPlacement::Placed));
+        }
+        // the rest are placed as the greedy layout would place them
+        for (label_idx, &(train_idx, edge_idx, anchor, position)) in slots.iter().enumerate() {
+            if results[label_idx].is_some() {
+                continue;
+            }
+            let (label_width, label_height) = trains[train_idx].label_size;
+            let mut edge = std::mem::take(&mut self.trains[train_idx].edges[edge_idx].edges);
+            let result =
+                self.add_label_to_edge(&mut edge, anchor, label_width, label_height, &position);
+            self.trains[train_idx].edges[edge_idx].edges = edge;
+            results[label_idx] = Some(result?);
+        }
+
+        // the two labels of each edge are next to each other
+        for (slots, results) in slots.chunks(2).zip(results.chunks(2)) {
+            let (
+                &[(train_idx, edge_idx, ..), _],
+                &[
+                    Some((start_angle, start_placement)),
+                    Some((end_angle, end_placement)),
+                ],
+            ) = (slots, results)
+            else {
+                continue;
+            };
+            let train_name = self.trains[train_idx].name.clone();
+            self.warn_about_labels(&train_name, (start_placement, end_placement));
+            self.trains[train_idx].edges[edge_idx].labels = Some(OutputLabel {
+                angles: (start_angle, end_angle),
+                placements: (start_placement, end_placement),
+            });
+        }
+        Ok(())
+    }
+
+    /// Where the greedy layout would put the labels, in the order `place_labels_globally` takes
+    /// them, without changing the diagram. Labels it could not place are `None`. The cost of each
+    /// place is how far the label was moved.
+    fn greedy_labels(&self, trains: &[&Train]) -> Result<Vec<Option<Candidate<LabelPlace>>>> {
+        let mut collision_manager = self.collision_manager.clone();
+        let mut results = Vec::new();
+        for (output_train, train) in self.trains.iter().zip(trains) {
+            let (label_width, label_height) = train.label_size;
+            for output_edge in &output_train.edges {
+                let mut edge = output_edge.edges.clone();
+                let (start, end) = (edge[0], edge[edge.len() - 1]);
+                for (anchor, at_start) in [(start, true), (end, false)] {
+                    // the label at the end is placed after the one at the beginning is attached
+                    let position = if at_start {
+                        start_label_position(&edge)
+                    } else {
+                        end_label_position(&edge)
+                    };
+                    let (polygon, movement_angle, label_angle) =
+                        self.create_label_polygon(anchor, label_width, label_height, &position);
+                    let origin = polygon[0];
+                    let (polygon, result) =
+                        match collision_manager.slide(polygon, movement_angle)? {
+                            Slide::Free(polygon) => {
+                                let distance = (polygon[0].0 - origin.0)
+                                    .value()
+                                    .hypot((polygon[0].1 - origin.1).value());
+                                let candidate = Candidate {
+                                    polygon: polygon.clone(),
+                                    angle: movement_angle,
+                                    cost: distance,
+                                    place: LabelPlace {
+                                        position,
+                                        label_angle,
+                                    },
+                                };
+                                (Some(polygon), Some(candidate))
+                            }
+                            Slide::Blocked(polygon)
+                                if self.config.label_fallback == LabelFallback::BestEffort =>
+                            {
+                                (Some(polygon), None)
+                            }
+                            Slide::Blocked(_) => (None, None),
+                        };
+                    if let Some(polygon) = polygon {
+                        attach_label(
+                            &mut edge,
+                            collision_manager.add_collision(polygon)?,
+                            &position,
+                        );
+                    }
+                    results.push(result);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// How many of the chosen candidates fit, if they were placed in order.
+    fn count_fitting(
+        &self,
+        labels: &[Vec<Candidate<LabelPlace>>],
+        choices: &[Option<usize>],
+    ) -> Result<usize> {
+        let mut collision_manager = self.collision_manager.clone();
+        let mut count = 0;
+        for (candidates, choice) in labels.iter().zip(choices) {
+            let Some(candidate) = choice.map(|choice| &candidates[choice]) else {
+                continue;
+            };
+            if collision_manager.fits(&candidate.polygon, candidate.angle)? {
+                collision_manager.add_collision(candidate.polygon.clone())?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The places the label at one end of an edge could be put without overlapping what is
+    /// already on the diagram: on either side of the line, moved along the line, and moved away
+    /// from it, at least as far as `reach`.
+    fn label_candidates(
+        &self,
+        edge: &[Node],
+        label_width: GraphLength,
+        label_height: GraphLength,
+        preferred: LabelPosition,
+        reach: f64,
+    ) -> Result<Vec<Candidate<LabelPlace>>> {
+        let leader_steps =
+            ((reach / label_height.value()).ceil() as u32).clamp(LEADER_STEPS, MAX_LEADER_STEPS);
+        let leader_step = label_height.value().max(reach / f64::from(leader_steps));
+        let flipped = match preferred {
+            LabelPosition::Beg(LabelDirection::Up) => LabelPosition::Beg(LabelDirection::Down),
+            LabelPosition::Beg(LabelDirection::Down) => LabelPosition::Beg(LabelDirection::Up),
+            LabelPosition::End(LabelDirection::Up) => LabelPosition::End(LabelDirection::Down),
+            LabelPosition::End(LabelDirection::Down) => LabelPosition::End(LabelDirection::Up),
+        };
+        let (anchor, towards) = match preferred {
+            LabelPosition::Beg(_) => (edge[0], edge[1]),
+            LabelPosition::End(_) => (edge[edge.len() - 1], edge[edge.len() - 2]),
+        };
+        let (dx, dy) = (
+            (towards.0 - anchor.0).value(),
+            (towards.1 - anchor.1).value(),
+        );
+        let segment_length = dx.hypot(dy);
+
+        let mut candidates = Vec::new();
+        for (position, side_cost) in [(preferred, 0.0), (flipped, FLIP_COST)] {
+            for shift in ALONG_LINE_SHIFTS {
+                let shift = label_width.value() * shift;
+                if shift > 0.0 && shift >= segment_length {
+                    break;
+                }
+                let ratio = if shift > 0.0 {
+                    shift / segment_length
+                } else {
+                    0.0
+                };
+                let shifted_anchor = Node(
+                    anchor.0 + (dx * ratio).into(),
+                    anchor.1 + (dy * ratio).into(),
+                );
+                let (polygon, movement_angle, label_angle) =
+                    self.create_label_polygon(shifted_anchor, label_width, label_height, &position);
+                for step in 0..=leader_steps {
+                    let distance = leader_step * f64::from(step);
+                    let (move_x, move_y) = (
+                        movement_angle.cos() * distance,
+                        movement_angle.sin() * distance,
+                    );
+                    let polygon: Vec<Node> = polygon
+                        .iter()
+                        .map(|node| Node(node.0 + move_x.into(), node.1 + move_y.into()))
+                        .collect();
+                    if !self.collision_manager.fits(&polygon, movement_angle)? {
+                        continue;
+                    }
+                    candidates.push(Candidate {
+                        polygon,
+                        angle: movement_angle,
+                        cost: side_cost + shift + distance,
+                        place: LabelPlace {
+                            position,
+                            label_angle,
+                        },
+                    });
+                }
+            }
+        }
+        Ok(candidates)
     }
 
     fn add_label_to_edge(
@@ -525,26 +1191,248 @@ impl Output {
         label_width: GraphLength,
         label_height: GraphLength,
         label_direction: &LabelPosition,
-    ) -> Result<f64> {
+    ) -> Result<(f64, LabelPlacement)> {
         let (polygon, movement_angle, label_angle) =
             self.create_label_polygon(anchor_point, label_width, label_height, label_direction);
 
-        let (resolved_polygon, _) = self
+        let (resolved_polygon, placement) =
+            match self.collision_manager.slide(polygon, movement_angle)? {
+                Slide::Free(polygon) => (
+                    self.collision_manager.add_collision(polygon)?,
+                    LabelPlacement::Placed,
+                ),
+                Slide::Blocked(polygon) => match self.config.label_fallback {
+                    // leave the edge as it is, so that the other labels could use the space
+                    LabelFallback::Drop => return Ok((label_angle, LabelPlacement::Dropped)),
+                    LabelFallback::BestEffort => (
+                        self.collision_manager.add_collision(polygon)?,
+                        LabelPlacement::BestEffort,
+                    ),
+                    LabelFallback::Overflow => {
+                        let corner = self.place_in_overflow(label_width, label_height)?;
+                        return Ok((label_angle, LabelPlacement::Overflow(corner)));
+                    }
+                },
+            };
+
+        attach_label(edge, resolved_polygon, label_direction);
+
+        Ok((label_angle, placement))
+    }
+
+    /// Place a label in the overflow area below the diagram, in rows from the left. Returns the
+    /// bottom left corner of the label.
+    fn place_in_overflow(
+        &mut self,
+        label_width: GraphLength,
+        label_height: GraphLength,
+    ) -> Result<Node> {
+        let graph_width = (self.config.end_time - self.config.start_time)
+            .to_graph_length(self.config.unit_length * self.config.time_axis_scale);
+        let (mut cursor, mut row_height) = self.overflow_cursor.unwrap_or_else(|| {
+            let graph_height = self
+                .station_draw_info
+                .last()
+                .map_or(GraphLength::from(0.0), |(_, y, _)| *y);
+            (
+                Node(0.0.into(), graph_height + self.config.unit_length),
+                0.0.into(),
+            )
+        });
+        if cursor.0 > 0.0.into() && cursor.0 + label_width > graph_width {
+            cursor = Node(0.0.into(), cursor.1 + row_height + OVERFLOW_GAP.into());
+            row_height = 0.0.into();
+        }
+        let polygon = vec![
+            cursor,
+            Node(cursor.0 + label_width, cursor.1),
+            Node(cursor.0 + label_width, cursor.1 + label_height),
+            Node(cursor.0, cursor.1 + label_height),
+        ];
+        // move down past the labels that hang below the diagram
+        let polygon = match self
             .collision_manager
-            .resolve_collisions(polygon, movement_angle)?;
+            .slide(polygon, 90.0f64.to_radians())?
+        {
+            Slide::Free(polygon) | Slide::Blocked(polygon) => polygon,
+        };
+        let corner = self.collision_manager.add_collision(polygon)?[3];
+        self.overflow_cursor = Some((
+            Node(cursor.0 + label_width + OVERFLOW_GAP.into(), cursor.1),
+            if label_height > row_height {
+                label_height
+            } else {
+                row_height
+            },
+        ));
+        Ok(corner)
+    }
+}
 
-        // Insert label nodes based on direction
-        match label_direction {
-            LabelPosition::Beg(_) => {
-                edge.insert(0, resolved_polygon[2]);
-                edge.insert(0, resolved_polygon[3]);
-            }
-            LabelPosition::End(_) => {
-                edge.push(resolved_polygon[3]);
-                edge.push(resolved_polygon[2]);
-            }
+/// Which way the label at the beginning of an edge goes, following the general direction of the
+/// edge.
+fn start_label_position(edge: &[Node]) -> LabelPosition {
+    if edge.len() > 2 {
+        // check the first three nodes to determine general direction
+        let (first, second, third) = (edge[0], edge[1], edge[2]);
+        // check if the general trend is upwards
+        // typst logic is reversed, so the directions are reversed
+        if (second.1 > first.1) || (third.1 > second.1) {
+            LabelPosition::Beg(LabelDirection::Down)
+        } else {
+            LabelPosition::Beg(LabelDirection::Up)
+        }
+    } else {
+        let (first, last) = (edge[0], edge[1]);
+        if first.1 < last.1 {
+            LabelPosition::Beg(LabelDirection::Down)
+        } else {
+            LabelPosition::Beg(LabelDirection::Up)
         }
+    }
+}
+
+/// Which way the label at the end of an edge goes, following the general direction of the edge.
+fn end_label_position(edge: &[Node]) -> LabelPosition {
+    if edge.len() > 2 {
+        // check the last three nodes to determine general direction
+        let (last, second_last, third_last) = (
+            edge[edge.len() - 1],
+            edge[edge.len() - 2],
+            edge[edge.len() - 3],
+        );
+        // check if the general trend is upwards
+        if (last.1 < second_last.1) || (second_last.1 < third_last.1) {
+            LabelPosition::End(LabelDirection::Up)
+        } else {
+            LabelPosition::End(LabelDirection::Down)
+        }
+    } else {
+        let (first, last) = (edge[0], edge[1]);
+        if last.1 < first.1 {
+            LabelPosition::End(LabelDirection::Up)
+        } else {
+            LabelPosition::End(LabelDirection::Down)
+        }
+    }
+}
+
+/// Extend an edge to the label placed at one of its ends, so that the line runs under the label.
+fn attach_label(edge: &mut Vec<Node>, polygon: &[Node], position: &LabelPosition) {
+    match position {
+        LabelPosition::Beg(_) => {
+            edge.insert(0, polygon[2]);
+            edge.insert(0, polygon[3]);
+        }
+        LabelPosition::End(_) => {
+            edge.push(polygon[3]);
+            edge.push(polygon[2]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::*;
 
-        Ok(label_angle)
+    /// 40 trains running close to each other, so that not every label fits.
+    fn dense_network() -> Network {
+        let mut network = NetworkBuilder::new()
+            .station("A", StationBuilder::new().label_size(10.0, 8.0))
+            .station("B", StationBuilder::new().label_size(10.0, 8.0))
+            .interval("A", "B", IntervalLength::new(5000), true);
+        for train_idx in 0..40 {
+            let departure = Time::new(3600 + train_idx * 90);
+            let arrival = Time::new(3600 + train_idx * 90 + 600);
+            let train = if train_idx % 2 == 0 {
+                TrainBuilder::new()
+                    .stop("A", departure, departure)
+                    .stop("B", arrival, arrival)
+            } else {
+                TrainBuilder::new()
+                    .stop("B", departure, departure)
+                    .stop("A", arrival, arrival)
+            };
+            network = network.train(format!("T{train_idx}"), train.label_size(40.0, 10.0));
+        }
+        network.build().unwrap()
+    }
+
+    fn placed_labels(output: &Output) -> usize {
+        output
+            .trains()
+            .iter()
+            .flat_map(OutputTrain::edges)
+            .filter_map(OutputEdge::label_placements)
+            .flat_map(|(start, end)| [start, end])
+            .filter(|placement| *placement == LabelPlacement::Placed)
+            .count()
+    }
+
+    #[test]
+    fn interpolate_passes_by_interval_length() {
+        let network = NetworkBuilder::new()
+            .station("A", StationBuilder::new())
+            .station("B", StationBuilder::new())
+            .station("C", StationBuilder::new())
+            .station("D", StationBuilder::new())
+            .interval("A", "B", IntervalLength::new(1000), true)
+            .interval("B", "C", IntervalLength::new(3000), true)
+            .interval("C", "D", IntervalLength::new(6000), true)
+            // an express that only lists the stations it stops at
+            .train(
+                "Express",
+                TrainBuilder::new()
+                    .stop("A", Time::new(3600), Time::new(3600))
+                    .stop("D", Time::new(3600 + 600), Time::new(3600 + 600)),
+            )
+            .build()
+            .unwrap();
+        // the stations are evenly spaced on the diagram, but the times follow the real lengths
+        let config = NetworkConfigBuilder::new(["A", "B", "C", "D"])
+            .time_range(Time::new(0), Time::new(2 * 3600))
+            .position_axis(ScaleMode::Uniform, 1.0)
+            .build()
+            .unwrap();
+        let output = layout(network, config).unwrap();
+
+        // the line runs through B and C without a break
+        let edges = output.trains()[0].edges();
+        assert_eq!(edges.len(), 1);
+        let entries = edges[0].entries();
+        assert_eq!(entries.len(), 4);
+        let interpolated: Vec<bool> = entries.iter().map(OutputEntry::interpolated).collect();
+        assert_eq!(interpolated, [false, true, true, false]);
+        assert!(matches!(entries[1].kind(), EntryKind::Pass));
+        assert!(matches!(entries[2].kind(), EntryKind::Pass));
+        let intervals = output.graph_intervals();
+        assert!(intervals.iter().all(|interval| *interval == intervals[0]));
+        let positions: Vec<f64> = output.stations().map(|(_, y)| y.value()).collect();
+        for (entry, y) in entries.iter().zip(&positions) {
+            assert_eq!(entry.arrival().1.value(), *y);
+        }
+
+        // B is a tenth of the way and C is four tenths of the way, so they are passed one and
+        // four minutes after leaving A
+        let x = |entry: &OutputEntry| entry.arrival().0.value();
+        let (start, end) = (x(&entries[0]), x(&entries[3]));
+        assert!((x(&entries[1]) - (start + (end - start) * 0.1)).abs() < 1e-6);
+        assert!((x(&entries[2]) - (start + (end - start) * 0.4)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn global_layout_places_as_many_labels_as_greedy() {
+        let config = || {
+            NetworkConfigBuilder::new(["A", "B"]).time_range(Time::new(3600), Time::new(3 * 3600))
+        };
+        let greedy = layout(dense_network(), config().build().unwrap()).unwrap();
+        for seed in 0..2 {
+            let global = layout(
+                dense_network(),
+                config().label_search(10_000, seed).build().unwrap(),
+            )
+            .unwrap();
+            assert!(placed_labels(&global) >= placed_labels(&greedy));
+        }
     }
 }
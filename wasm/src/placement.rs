@@ -0,0 +1,225 @@
+use crate::collision::{SLIDE_TOLERANCE, aabb, distance_along, sat_collision_test_with_mtv};
+use crate::types::*;
+use std::collections::HashMap;
+
+/// The cost of each point two overlapping labels would have to move apart, on top of the cost of
+/// a dropped label.
+const OVERLAP_DEPTH_COST: f64 = 10.0;
+/// How much more a dropped label costs than the most expensive candidate, so that a label is only
+/// dropped when all of its candidates overlap other labels.
+const DROP_MARGIN: f64 = 1000.0;
+/// The temperatures at the beginning and the end of the search.
+const START_TEMPERATURE: f64 = 200.0;
+const END_TEMPERATURE: f64 = 0.5;
+
+/// A place a label could be put, which does not overlap what is already on the diagram.
+pub struct Candidate<T> {
+    pub polygon: Vec<Node>,
+    /// The angle the label would slide along. Overlaps are measured along it, as when sliding.
+    pub angle: f64,
+    /// The cost of the place by itself, e.g. how far it is from the anchor.
+    pub cost: f64,
+    /// What the caller needs to put the label there.
+    pub place: T,
+}
+
+/// A small deterministic random number generator (SplitMix64), so that the same seed always
+/// gives the same places.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// A number in `0.0..1.0`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The candidates of all labels, and which of them overlap each other.
+struct Problem<'a, T> {
+    labels: &'a [Vec<Candidate<T>>],
+    /// The index of the first candidate of each label. Candidates are numbered across labels.
+    offsets: Vec<usize>,
+    /// The label of each candidate.
+    owners: Vec<usize>,
+    /// The candidates of other labels each candidate overlaps, and the cost of the overlap.
+    conflicts: Vec<Vec<(usize, f64)>>,
+    /// The cost of a label that is not drawn.
+    drop_cost: f64,
+}
+
+impl<'a, T> Problem<'a, T> {
+    fn new(labels: &'a [Vec<Candidate<T>>], cell_size: GraphLength) -> Self {
+        let mut offsets = Vec::with_capacity(labels.len());
+        let mut owners = Vec::new();
+        let mut boxes = Vec::new();
+        for (label_idx, candidates) in labels.iter().enumerate() {
+            offsets.push(owners.len());
+            for candidate in candidates {
+                owners.push(label_idx);
+                boxes.push(aabb(&candidate.polygon));
+            }
+        }
+
+        // bucket the candidates by the cells their bounding boxes cover
+        let cell_size = cell_size.value().max(1.0);
+        let cell = |length: GraphLength| (length.value() / cell_size).floor() as i64;
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (id, bounds) in boxes.iter().enumerate() {
+            let Some((x_min, x_max, y_min, y_max)) = *bounds else {
+                continue;
+            };
+            for i in cell(x_min)..=cell(x_max) {
+                for j in cell(y_min)..=cell(y_max) {
+                    cells.entry((i, j)).or_default().push(id);
+                }
+            }
+        }
+
+        // overlapping labels cost more than dropping one of them, and dropping a label costs more
+        // than any of its candidates
+        let drop_cost = labels
+            .iter()
+            .flatten()
+            .map(|candidate| candidate.cost)
+            .fold(0.0, f64::max)
+            + DROP_MARGIN;
+        let mut conflicts = vec![Vec::new(); owners.len()];
+        for (id, bounds) in boxes.iter().enumerate() {
+            let Some((x_min, x_max, y_min, y_max)) = *bounds else {
+                continue;
+            };
+            let mut nearby: Vec<usize> = Vec::new();
+            for i in cell(x_min)..=cell(x_max) {
+                for j in cell(y_min)..=cell(y_max) {
+                    if let Some(ids) = cells.get(&(i, j)) {
+                        // each pair is tested once, from its lower end
+                        nearby.extend(
+                            ids.iter()
+                                .filter(|&&other| other > id && owners[other] != owners[id]),
+                        );
+                    }
+                }
+            }
+            nearby.sort_unstable();
+            nearby.dedup();
+            for other in nearby {
+                let Some((other_x_min, other_x_max, other_y_min, other_y_max)) = boxes[other]
+                else {
+                    continue;
+                };
+                if other_x_min > x_max
+                    || other_x_max < x_min
+                    || other_y_min > y_max
+                    || other_y_max < y_min
+                {
+                    continue;
+                }
+                let candidate = &labels[owners[id]][id - offsets[owners[id]]];
+                let other_candidate = &labels[owners[other]][other - offsets[owners[other]]];
+                let Some((mtv_x, mtv_y, depth)) =
+                    sat_collision_test_with_mtv(&candidate.polygon, &other_candidate.polygon)
+                else {
+                    continue;
+                };
+                // either of them could be placed first
+                let distance = f64::max(
+                    distance_along((mtv_x, mtv_y), depth, candidate.angle),
+                    distance_along((mtv_x, mtv_y), depth, other_candidate.angle),
+                );
+                if distance > SLIDE_TOLERANCE {
+                    let cost = drop_cost + distance * OVERLAP_DEPTH_COST;
+                    conflicts[id].push((other, cost));
+                    conflicts[other].push((id, cost));
+                }
+            }
+        }
+
+        Self {
+            labels,
+            offsets,
+            owners,
+            conflicts,
+            drop_cost,
+        }
+    }
+
+    /// The cost of a choice for a label, given the choices of the other labels.
+    fn cost(&self, choice: Option<usize>, chosen: &[Option<usize>]) -> f64 {
+        let Some(id) = choice else {
+            return self.drop_cost;
+        };
+        let label_idx = self.owners[id];
+        self.labels[label_idx][id - self.offsets[label_idx]].cost
+            + self.conflicts[id]
+                .iter()
+                .filter(|(other, _)| chosen[self.owners[*other]] == Some(*other))
+                .map(|(_, cost)| cost)
+                .sum::<f64>()
+    }
+}
+
+/// Choose a candidate for each label, or `None` if the label is better dropped. The search starts
+/// from the `initial` choices, e.g. the places of a greedy layout, and improves them by simulated
+/// annealing for `steps` steps. The result never costs more than the initial choices.
+/// `cell_size` is the size of the cells candidates are bucketed by.
+pub fn search<T>(
+    labels: &[Vec<Candidate<T>>],
+    initial: &[Option<usize>],
+    cell_size: GraphLength,
+    steps: u32,
+    seed: u64,
+) -> Vec<Option<usize>> {
+    let problem = Problem::new(labels, cell_size);
+    // the chosen candidates, numbered across labels
+    let mut chosen: Vec<Option<usize>> = initial
+        .iter()
+        .zip(&problem.offsets)
+        .map(|(choice, offset)| choice.map(|choice| offset + choice))
+        .collect();
+    // only the differences of the total cost matter
+    let mut total_cost = 0.0;
+
+    let mut best = chosen.clone();
+    let mut best_cost = total_cost;
+    let mut rng = Rng(seed);
+    for step in 0..steps {
+        if labels.is_empty() {
+            break;
+        }
+        let temperature = START_TEMPERATURE
+            * (END_TEMPERATURE / START_TEMPERATURE).powf(f64::from(step) / f64::from(steps));
+        let label_idx = rng.below(labels.len());
+        let pick = rng.below(labels[label_idx].len() + 1);
+        let choice = (pick < labels[label_idx].len()).then(|| problem.offsets[label_idx] + pick);
+        if choice == chosen[label_idx] {
+            continue;
+        }
+        let delta = problem.cost(choice, &chosen) - problem.cost(chosen[label_idx], &chosen);
+        if delta <= 0.0 || rng.unit() < (-delta / temperature).exp() {
+            chosen[label_idx] = choice;
+            total_cost += delta;
+            if total_cost < best_cost - f64::EPSILON {
+                best_cost = total_cost;
+                best.clone_from(&chosen);
+            }
+        }
+    }
+
+    best.into_iter()
+        .zip(&problem.offsets)
+        .map(|(choice, offset)| choice.map(|id| id - offset))
+        .collect()
+}
@@ -0,0 +1,51 @@
+use crate::types::GraphLength;
+
+/// The height of capital letters, in em.
+pub(crate) const CAP_HEIGHT: f64 = 0.7;
+/// The space below the baseline of train labels, in em, the same as `make-train-label`.
+pub(crate) const TRAIN_LABEL_PADDING: f64 = 0.14;
+
+/// The estimated advance of a character, in em.
+fn advance(c: char) -> f64 {
+    match c {
+        // CJK, Hangul, and full-width forms
+        '\u{1100}'..='\u{115f}'
+        | '\u{2e80}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{20000}'..='\u{3fffd}' => 1.0,
+        // half-width katakana
+        '\u{ff61}'..='\u{ff9f}' => 0.5,
+        ' ' => 0.25,
+        'i' | 'j' | 'l' | 'f' | 't' | 'r' | 'I' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 0.3,
+        '(' | ')' | '[' | ']' | '-' => 0.35,
+        'm' | 'w' | 'M' | 'W' => 0.85,
+        'A'..='Z' => 0.65,
+        _ => 0.5,
+    }
+}
+
+/// The estimated width of a line of text. Typst measures the labels before calling the plugin,
+/// but there is no font here, so the width is guessed from the widths of common glyphs.
+pub(crate) fn text_width(text: &str, font_size: f64) -> f64 {
+    text.chars().map(advance).sum::<f64>() * font_size
+}
+
+/// The size of a station label, which is its name from the cap height to the baseline.
+pub(crate) fn station_label_size(name: &str, font_size: f64) -> (GraphLength, GraphLength) {
+    (
+        text_width(name, font_size).into(),
+        (CAP_HEIGHT * font_size).into(),
+    )
+}
+
+/// The size of a train label, which is its name with some space below it.
+pub(crate) fn train_label_size(name: &str, font_size: f64) -> (GraphLength, GraphLength) {
+    (
+        text_width(name, font_size).into(),
+        ((CAP_HEIGHT + TRAIN_LABEL_PADDING) * font_size).into(),
+    )
+}
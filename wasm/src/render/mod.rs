@@ -0,0 +1,331 @@
+pub mod metrics;
+pub mod png;
+pub mod svg;
+
+use crate::input::*;
+use crate::output::{LabelPlacement, Output};
+use crate::types::*;
+use metrics::{CAP_HEIGHT, TRAIN_LABEL_PADDING};
+use std::collections::HashMap;
+
+/// The colour of the grid, the same as `gray` in Typst.
+const GRID_COLOR: Color = Color(0xaa, 0xaa, 0xaa);
+/// The space between the station lines and the station labels.
+const STATION_LABEL_GAP: f64 = 3.0;
+/// The space between the top of the diagram and the hour labels.
+const HOUR_LABEL_GAP: f64 = 5.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub const BLACK: Color = Color(0, 0, 0);
+    pub const WHITE: Color = Color(0xff, 0xff, 0xff);
+
+    /// Parse `#RRGGBB` or `#RGB`, with or without the `#`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let hex = value.trim().trim_start_matches('#');
+        let digit = |idx: usize, len: usize| u8::from_str_radix(hex.get(idx..idx + len)?, 16).ok();
+        match hex.len() {
+            6 => Some(Color(digit(0, 2)?, digit(2, 2)?, digit(4, 2)?)),
+            3 => Some(Color(
+                digit(0, 1)? * 17,
+                digit(1, 1)? * 17,
+                digit(2, 1)? * 17,
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f64,
+    /// Lengths of the dashes and the gaps between them. Solid if empty.
+    pub dash: Vec<f64>,
+    /// Whether the ends of the lines and dashes are round, which turns empty dashes into dots.
+    pub round_cap: bool,
+}
+
+impl Stroke {
+    pub fn solid(color: Color, width: f64) -> Self {
+        Self {
+            color,
+            width,
+            dash: Vec::new(),
+            round_cap: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// A line of text. It is rotated by `angle` around `origin`, and `offset` is where its baseline
+/// starts, middles, or ends before the rotation, relative to `origin`.
+pub struct Text {
+    pub origin: (f64, f64),
+    pub offset: (f64, f64),
+    /// In radians, clockwise.
+    pub angle: f64,
+    pub anchor: Anchor,
+    pub content: String,
+    pub size: f64,
+    /// Whether the text is outlined in white to stand out from the lines under it.
+    pub halo: bool,
+}
+
+pub enum Item {
+    Line {
+        points: Vec<(f64, f64)>,
+        stroke: Stroke,
+    },
+    Text(Text),
+}
+
+/// Everything drawn on a diagram, in points, from the back to the front.
+pub struct Scene {
+    pub width: f64,
+    pub height: f64,
+    pub items: Vec<Item>,
+}
+
+/// Names and strokes that the layout does not keep. They are collected from the network before
+/// it is laid out.
+pub struct Decorations {
+    station_names: HashMap<StationID, String>,
+    train_strokes: HashMap<String, Stroke>,
+}
+
+impl Decorations {
+    pub fn new(network: &Network) -> Self {
+        Self {
+            station_names: network
+                .stations
+                .iter()
+                .map(|(id, station)| (*id, station.name.clone()))
+                .collect(),
+            train_strokes: network
+                .trains
+                .values()
+                .map(|train| (train.name.clone(), train_stroke(&train.metadata)))
+                .collect(),
+        }
+    }
+}
+
+/// The stroke of a train, from the `color`, `line_width`, and `line_style` metadata given by the
+/// importers. Line styles are the ones of qETRC: 2 is dashed, 3 is dotted, and 4 is dash-dotted.
+fn train_stroke(metadata: &Metadata) -> Stroke {
+    let color = metadata
+        .get("color")
+        .and_then(|color| color.as_text())
+        .and_then(Color::parse)
+        .unwrap_or(Color::BLACK);
+    let width = match metadata.get("line_width") {
+        Some(ciborium::Value::Float(width)) => *width,
+        Some(ciborium::Value::Integer(width)) => i128::from(*width) as f64,
+        _ => 1.0,
+    };
+    let dash = match metadata
+        .get("line_style")
+        .and_then(|style| style.as_integer())
+    {
+        Some(style) if style == 2.into() => vec![4.0 * width, 2.0 * width],
+        Some(style) if style == 3.into() => vec![width, 2.0 * width],
+        Some(style) if style == 4.into() => vec![4.0 * width, 2.0 * width, width, 2.0 * width],
+        _ => Vec::new(),
+    };
+    Stroke {
+        color,
+        width,
+        dash,
+        round_cap: false,
+    }
+}
+
+/// Draw the output of the layout the way `paiagram` in Typst does: the time grid, the station
+/// lines and labels, the hour labels, and the trains with their labels.
+pub fn draw(output: &Output, decorations: &Decorations, font_size: f64) -> Scene {
+    let config = &output.config;
+    let bounds = &output.collision_manager;
+    // leave room for the hour labels above the diagram
+    let margin = CAP_HEIGHT * font_size + HOUR_LABEL_GAP;
+    let dx = margin - bounds.x_min.value();
+    let dy = margin - bounds.y_min.value();
+    let point = |node: Node| (node.0.value() + dx, node.1.value() + dy);
+    let mut items = Vec::new();
+
+    let time_unit_length = config.unit_length * config.time_axis_scale;
+    let x_of = |time: Time| {
+        (time - config.start_time)
+            .to_graph_length(time_unit_length)
+            .value()
+    };
+    let graph_width = x_of(config.end_time);
+    let graph_height: f64 = output.graph_intervals.iter().map(GraphLength::value).sum();
+
+    // vertical lines every ten minutes, in the same style as the grid in Typst
+    let step = 600;
+    let mut time = config.start_time.seconds().div_euclid(step) * step;
+    if time < config.start_time.seconds() {
+        time += step;
+    }
+    while time <= config.end_time.seconds() {
+        let x = x_of(Time::new(time));
+        let stroke = if time.rem_euclid(3600) == 0 {
+            Stroke::solid(GRID_COLOR, 1.0)
+        } else if time.rem_euclid(1800) == 0 {
+            Stroke {
+                dash: vec![3.0, 6.0],
+                round_cap: true,
+                ..Stroke::solid(GRID_COLOR, 1.0)
+            }
+        } else {
+            Stroke {
+                dash: vec![0.0, 4.0],
+                round_cap: true,
+                ..Stroke::solid(GRID_COLOR, 1.0)
+            }
+        };
+        items.push(Item::Line {
+            points: vec![(x + dx, dy), (x + dx, graph_height + dy)],
+            stroke,
+        });
+        if time.rem_euclid(3600) == 0 {
+            items.push(Item::Text(Text {
+                origin: (x + dx, dy),
+                offset: (0.0, -HOUR_LABEL_GAP),
+                angle: 0.0,
+                anchor: Anchor::Middle,
+                content: (time / 3600).rem_euclid(24).to_string(),
+                size: font_size,
+                halo: false,
+            }));
+        }
+        time += step;
+    }
+
+    // station lines and labels
+    for (station, y, _) in &output.station_draw_info {
+        let y = y.value() + dy;
+        items.push(Item::Line {
+            points: vec![(dx, y), (graph_width + dx, y)],
+            stroke: Stroke::solid(GRID_COLOR, 1.0),
+        });
+        let name = decorations
+            .station_names
+            .get(station)
+            .cloned()
+            .unwrap_or_else(|| config.names.display(*station));
+        items.push(Item::Text(Text {
+            origin: (dx, y),
+            offset: (-STATION_LABEL_GAP, CAP_HEIGHT * font_size / 2.0),
+            angle: 0.0,
+            anchor: Anchor::End,
+            content: name,
+            size: font_size,
+            halo: false,
+        }));
+    }
+
+    // trains and their labels
+    let default_stroke = Stroke::solid(Color::BLACK, 1.0);
+    for train in &output.trains {
+        let stroke = decorations
+            .train_strokes
+            .get(&train.name)
+            .unwrap_or(&default_stroke);
+        for edge in &train.edges {
+            let (Some(first), Some(last)) = (edge.edges.first(), edge.edges.last()) else {
+                continue;
+            };
+            items.push(Item::Line {
+                points: edge.edges.iter().copied().map(point).collect(),
+                stroke: stroke.clone(),
+            });
+            let Some(labels) = &edge.labels else {
+                continue;
+            };
+            let (start_angle, end_angle) = labels.angles;
+            let (start_placement, end_placement) = labels.placements;
+            for (node, angle, anchor, placement) in [
+                (first, start_angle, Anchor::Start, start_placement),
+                (last, end_angle, Anchor::End, end_placement),
+            ] {
+                let (node, angle, anchor) = match placement {
+                    LabelPlacement::Placed | LabelPlacement::BestEffort => (*node, angle, anchor),
+                    LabelPlacement::Dropped => continue,
+                    LabelPlacement::Overflow(corner) => (corner, 0.0, Anchor::Start),
+                };
+                items.push(Item::Text(Text {
+                    origin: point(node),
+                    offset: (0.0, -TRAIN_LABEL_PADDING * font_size),
+                    angle,
+                    anchor,
+                    content: train.name.clone(),
+                    size: font_size,
+                    halo: true,
+                }));
+            }
+        }
+    }
+
+    Scene {
+        width: bounds.x_max.value() - bounds.x_min.value() + 2.0 * margin,
+        height: bounds.y_max.value() - bounds.y_min.value() + 2.0 * margin,
+        items,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+    use crate::api::*;
+
+    /// The colour of the train of `scene`, given in its metadata.
+    pub const TRAIN_COLOR: Color = Color(0xc0, 0x20, 0x40);
+
+    /// A train running from 8:00 to 9:00 between two stations, drawn from 7:00 to 10:00. The
+    /// names need escaping in SVG.
+    pub fn scene() -> Scene {
+        let network = NetworkBuilder::new()
+            .station("A & B", StationBuilder::new().label_size(30.0, 8.0))
+            .station("<C>", StationBuilder::new().label_size(30.0, 8.0))
+            .interval("A & B", "<C>", IntervalLength::new(20000), true)
+            .train(
+                "T\"1\"",
+                TrainBuilder::new()
+                    .stop("A & B", Time::new(8 * 3600), Time::new(8 * 3600))
+                    .stop("<C>", Time::new(9 * 3600), Time::new(9 * 3600))
+                    .label_size(20.0, 10.0)
+                    .metadata("color", "#c02040".into())
+                    .metadata("line_width", 3.into()),
+            )
+            .build()
+            .unwrap();
+        let decorations = Decorations::new(&network);
+        let config = NetworkConfigBuilder::new(["A & B", "<C>"])
+            .time_range(Time::new(7 * 3600), Time::new(10 * 3600))
+            .build()
+            .unwrap();
+        draw(&layout(network, config).unwrap(), &decorations, 10.0)
+    }
+
+    /// The points of the line of the train.
+    pub fn train_line(scene: &Scene) -> &[(f64, f64)] {
+        scene
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Line { points, stroke } if stroke.color == TRAIN_COLOR => Some(&points[..]),
+                _ => None,
+            })
+            .unwrap()
+    }
+}
@@ -0,0 +1,235 @@
+use super::*;
+use ab_glyph::{Font, FontArc, GlyphId, OutlineCurve};
+use anyhow::{Result, anyhow};
+use tiny_skia::{
+    FillRule, LineCap, LineJoin, Paint, Path, PathBuilder, Pixmap, StrokeDash, Transform,
+};
+
+/// The length of dots, which are empty dashes with round caps. Dashes of length zero are
+/// dropped when rasterising.
+const DOT_LENGTH: f32 = 0.01;
+
+/// Fonts to draw the labels with. Characters are drawn with the first font that has them, and
+/// the built-in font is tried last.
+pub struct Fonts(Vec<FontArc>);
+
+impl Fonts {
+    /// Use the given font files, which are TrueType or OpenType fonts.
+    pub fn new(files: Vec<Vec<u8>>) -> Result<Self> {
+        let mut fonts = files
+            .into_iter()
+            .map(|file| FontArc::try_from_vec(file).map_err(|_| anyhow!("Invalid font file")))
+            .collect::<Result<Vec<_>>>()?;
+        fonts.push(
+            FontArc::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT)
+                .map_err(|_| anyhow!("Invalid built-in font"))?,
+        );
+        Ok(Self(fonts))
+    }
+
+    /// The font to draw a character with.
+    fn pick(&self, c: char) -> (&FontArc, GlyphId) {
+        self.0
+            .iter()
+            .map(|font| (font, font.glyph_id(c)))
+            .find(|(_, glyph)| glyph.0 != 0)
+            .unwrap_or_else(|| (&self.0[0], GlyphId(0)))
+    }
+}
+
+/// Rasterise a scene into a PNG image. There are `dpi` pixels per inch, or per 72 points.
+pub fn write(scene: &Scene, dpi: f64, fonts: &Fonts) -> Result<Vec<u8>> {
+    let scale = (dpi / 72.0) as f32;
+    let (width, height) = (
+        (scene.width as f32 * scale).ceil() as u32,
+        (scene.height as f32 * scale).ceil() as u32,
+    );
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("Cannot make an image of {} by {} pixels", width, height))?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    let base = Transform::from_scale(scale, scale);
+
+    for item in &scene.items {
+        match item {
+            Item::Line { points, stroke } => {
+                let mut builder = PathBuilder::new();
+                for (idx, (x, y)) in points.iter().enumerate() {
+                    if idx == 0 {
+                        builder.move_to(*x as f32, *y as f32);
+                    } else {
+                        builder.line_to(*x as f32, *y as f32);
+                    }
+                }
+                let Some(path) = builder.finish() else {
+                    continue;
+                };
+                let dash = if stroke.dash.is_empty() {
+                    None
+                } else {
+                    let mut dash: Vec<f32> = stroke
+                        .dash
+                        .iter()
+                        .map(|length| (*length as f32).max(DOT_LENGTH))
+                        .collect();
+                    // the dashes must come in pairs
+                    if dash.len() % 2 == 1 {
+                        dash.extend(dash.clone());
+                    }
+                    StrokeDash::new(dash, 0.0)
+                };
+                let line_stroke = tiny_skia::Stroke {
+                    width: stroke.width as f32,
+                    line_cap: if stroke.round_cap {
+                        LineCap::Round
+                    } else {
+                        LineCap::Butt
+                    },
+                    dash,
+                    ..Default::default()
+                };
+                pixmap.stroke_path(&path, &paint(stroke.color), &line_stroke, base, None);
+            }
+            Item::Text(text) => draw_text(&mut pixmap, base, text, fonts),
+        }
+    }
+
+    pixmap
+        .encode_png()
+        .map_err(|error| anyhow!("Failed to encode the image: {}", error))
+}
+
+fn paint(Color(r, g, b): Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, 0xff);
+    paint.anti_alias = true;
+    paint
+}
+
+/// Draw the outlines of the glyphs, with the halo under all of them.
+fn draw_text(pixmap: &mut Pixmap, base: Transform, text: &Text, fonts: &Fonts) {
+    // lay out the glyphs in points along the baseline
+    let mut glyphs: Vec<(Path, Transform, f32)> = Vec::new();
+    let mut pen = 0.0f32;
+    let mut previous: Option<(&FontArc, GlyphId)> = None;
+    let size = text.size as f32;
+    for c in text.content.chars() {
+        let (font, glyph) = fonts.pick(c);
+        let units_per_em = font.units_per_em().unwrap_or(1000.0);
+        let unit = size / units_per_em;
+        if let Some((previous_font, previous_glyph)) = previous
+            && std::ptr::eq(previous_font, font)
+        {
+            pen += font.kern_unscaled(previous_glyph, glyph) * unit;
+        }
+        if let Some(path) = font
+            .outline(glyph)
+            .and_then(|outline| outline_path(&outline.curves))
+        {
+            glyphs.push((
+                path,
+                Transform::from_row(unit, 0.0, 0.0, -unit, pen, 0.0),
+                units_per_em,
+            ));
+        }
+        pen += font.h_advance_unscaled(glyph) * unit;
+        previous = Some((font, glyph));
+    }
+    let start = text.offset.0 as f32
+        - match text.anchor {
+            Anchor::Start => 0.0,
+            Anchor::Middle => pen / 2.0,
+            Anchor::End => pen,
+        };
+    let line = base
+        .pre_translate(text.origin.0 as f32, text.origin.1 as f32)
+        .pre_rotate(text.angle.to_degrees() as f32)
+        .pre_translate(start, text.offset.1 as f32);
+
+    if text.halo {
+        for (path, transform, units_per_em) in &glyphs {
+            let halo = tiny_skia::Stroke {
+                // the same as the halo of `make-train-label`, which is .1em
+                width: 0.1 * units_per_em,
+                line_join: LineJoin::Round,
+                ..Default::default()
+            };
+            pixmap.stroke_path(
+                path,
+                &paint(Color::WHITE),
+                &halo,
+                line.pre_concat(*transform),
+                None,
+            );
+        }
+    }
+    for (path, transform, _) in &glyphs {
+        pixmap.fill_path(
+            path,
+            &paint(Color::BLACK),
+            FillRule::Winding,
+            line.pre_concat(*transform),
+            None,
+        );
+    }
+}
+
+/// Turn the outline of a glyph into a path, in font units.
+fn outline_path(curves: &[OutlineCurve]) -> Option<Path> {
+    let mut builder = PathBuilder::new();
+    let mut last = None;
+    for curve in curves {
+        let start = match curve {
+            OutlineCurve::Line(start, _)
+            | OutlineCurve::Quad(start, ..)
+            | OutlineCurve::Cubic(start, ..) => *start,
+        };
+        // curves that do not continue the previous one start a new contour
+        if last != Some(start) {
+            if last.is_some() {
+                builder.close();
+            }
+            builder.move_to(start.x, start.y);
+        }
+        last = Some(match curve {
+            OutlineCurve::Line(_, end) => {
+                builder.line_to(end.x, end.y);
+                *end
+            }
+            OutlineCurve::Quad(_, control, end) => {
+                builder.quad_to(control.x, control.y, end.x, end.y);
+                *end
+            }
+            OutlineCurve::Cubic(_, first, second, end) => {
+                builder.cubic_to(first.x, first.y, second.x, second.y, end.x, end.y);
+                *end
+            }
+        });
+    }
+    builder.close();
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::test_util::*;
+
+    #[test]
+    fn write_scene() {
+        let scene = scene();
+        let image = write(&scene, 144.0, &Fonts::new(Vec::new()).unwrap()).unwrap();
+        let pixmap = Pixmap::decode_png(&image).unwrap();
+        assert_eq!(pixmap.width(), (scene.width * 2.0).ceil() as u32);
+        assert_eq!(pixmap.height(), (scene.height * 2.0).ceil() as u32);
+
+        let pixel = |(x, y): (f64, f64)| {
+            let pixel = pixmap.pixel((x * 2.0) as u32, (y * 2.0) as u32).unwrap();
+            Color(pixel.red(), pixel.green(), pixel.blue())
+        };
+        assert!(pixel((0.0, 0.0)) == Color::WHITE);
+        // the middle of the line, away from the labels at its ends
+        let line = train_line(&scene);
+        let (first, last) = (line[0], line[line.len() - 1]);
+        assert!(pixel(((first.0 + last.0) / 2.0, (first.1 + last.1) / 2.0)) == TRAIN_COLOR);
+    }
+}
@@ -0,0 +1,166 @@
+use super::*;
+use std::fmt::Write;
+
+/// The fonts of the labels, starting with the default font of Typst.
+const FONT_FAMILY: &str = "'Libertinus Serif', 'Noto Serif CJK SC', 'Noto Serif CJK JP', serif";
+
+/// Write a scene as an SVG document. Lengths are in points.
+pub fn write(scene: &Scene) -> String {
+    let mut svg = String::new();
+    let (width, height) = (number(scene.width), number(scene.height));
+    // writing to a string never fails
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}pt" height="{height}pt" viewBox="0 0 {width} {height}" font-family="{FONT_FAMILY}">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="{}"/>"#,
+        color(Color::WHITE)
+    );
+    for item in &scene.items {
+        match item {
+            Item::Line { points, stroke } => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|(x, y)| format!("{},{}", number(*x), number(*y)))
+                    .collect();
+                let _ = writeln!(
+                    svg,
+                    r#"<polyline points="{}" fill="none"{}/>"#,
+                    points.join(" "),
+                    stroke_attributes(stroke)
+                );
+            }
+            Item::Text(text) => {
+                let anchor = match text.anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                };
+                let mut transform = format!(
+                    "translate({} {})",
+                    number(text.origin.0),
+                    number(text.origin.1)
+                );
+                if text.angle != 0.0 {
+                    let _ = write!(transform, " rotate({})", number(text.angle.to_degrees()));
+                }
+                let halo = if text.halo {
+                    format!(
+                        r#" stroke="{}" stroke-width="{}" paint-order="stroke" stroke-linejoin="round""#,
+                        color(Color::WHITE),
+                        number(text.size * 0.1)
+                    )
+                } else {
+                    String::new()
+                };
+                let _ = writeln!(
+                    svg,
+                    r#"<text transform="{transform}" x="{}" y="{}" font-size="{}" text-anchor="{anchor}"{halo}>{}</text>"#,
+                    number(text.offset.0),
+                    number(text.offset.1),
+                    number(text.size),
+                    escape(&text.content)
+                );
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn stroke_attributes(stroke: &Stroke) -> String {
+    let mut attributes = format!(
+        r#" stroke="{}" stroke-width="{}""#,
+        color(stroke.color),
+        number(stroke.width)
+    );
+    if !stroke.dash.is_empty() {
+        let dash: Vec<String> = stroke.dash.iter().map(|length| number(*length)).collect();
+        let _ = write!(attributes, r#" stroke-dasharray="{}""#, dash.join(" "));
+    }
+    if stroke.round_cap {
+        attributes.push_str(r#" stroke-linecap="round""#);
+    }
+    attributes
+}
+
+fn color(Color(r, g, b): Color) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Write a length with at most two decimals.
+fn number(value: f64) -> String {
+    let formatted = format!("{value:.2}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    if formatted == "-0" {
+        "0".to_string()
+    } else {
+        formatted.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::test_util::*;
+
+    #[test]
+    fn write_scene() {
+        let svg = write(&scene());
+        assert!(svg.starts_with(r##"<svg xmlns="http://www.w3.org/2000/svg" width="397.16pt" height="137.58pt" viewBox="0 0 397.16 137.58""##));
+        assert!(svg.ends_with("</svg>\n"));
+
+        // a line every ten minutes from 7:00 to 10:00, dashed every half hour and dotted between
+        let grid = r##"fill="none" stroke="#aaaaaa" stroke-width="1""##;
+        let vertical_lines = svg
+            .lines()
+            .filter(|line| {
+                line.contains(grid) && line.contains(",30.66 ") && line.contains(",115.58\"")
+            })
+            .count();
+        assert_eq!(vertical_lines, 19);
+        let count = |pattern: &str| svg.matches(pattern).count();
+        assert_eq!(
+            count(r##"stroke-dasharray="3 6" stroke-linecap="round""##),
+            3
+        );
+        assert_eq!(
+            count(r##"stroke-dasharray="0 4" stroke-linecap="round""##),
+            12
+        );
+        assert!(svg.contains(r#"text-anchor="middle">10</text>"#));
+
+        // the station lines, with the names escaped
+        assert!(svg.contains(&format!(
+            r##"<polyline points="45,30.66 385.16,30.66" {grid}/>
+<text transform="translate(45 30.66)" x="-3" y="3.5" font-size="10" text-anchor="end">A &amp; B</text>"##
+        )));
+        assert!(svg.contains(&format!(
+            r##"<polyline points="45,115.58 385.16,115.58" {grid}/>
+<text transform="translate(45 115.58)" x="-3" y="3.5" font-size="10" text-anchor="end">&lt;C&gt;</text>"##
+        )));
+
+        // the train from 8:00 to 9:00, with its labels along the line
+        assert!(svg.contains(
+            r##"<polyline points="141.07,20.66 158.39,30.66 158.39,30.66 271.77,115.58 271.77,115.58 289.09,125.58" fill="none" stroke="#c02040" stroke-width="3"/>"##
+        ));
+        let halo =
+            r##"stroke="#ffffff" stroke-width="1" paint-order="stroke" stroke-linejoin="round""##;
+        assert!(svg.contains(&format!(
+            r##"<text transform="translate(141.07 20.66) rotate(30)" x="0" y="-1.4" font-size="10" text-anchor="start" {halo}>T&quot;1&quot;</text>"##
+        )));
+        assert!(svg.contains(&format!(
+            r##"<text transform="translate(289.09 125.58) rotate(30)" x="0" y="-1.4" font-size="10" text-anchor="end" {halo}>T&quot;1&quot;</text>"##
+        )));
+        assert!(!svg.contains("A & B") && !svg.contains("<C>"));
+    }
+}
@@ -21,11 +21,13 @@ impl IntervalIDExt for IntervalID {
     Debug,
     Clone,
     Copy,
+    Default,
     PartialEq,
     Eq,
     Add,
     Sub,
     Deserialize,
+    Serialize,
     Ord,
     PartialOrd,
     Neg,
@@ -120,7 +122,175 @@ impl std::fmt::Display for Time {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Add, Sub, Deserialize)]
+impl std::str::FromStr for Time {
+    type Err = anyhow::Error;
+    /// Parses `HH:MM` or `HH:MM:SS`. Hours could be larger than 23 for times after midnight.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .trim()
+            .split(':')
+            .map(|part| part.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow::anyhow!("Invalid time '{}'", s))?;
+        let (hours, minutes, seconds) = match parts[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, seconds] => (hours, minutes, seconds),
+            _ => return Err(anyhow::anyhow!("Invalid time '{}'", s)),
+        };
+        if minutes >= 60 || seconds >= 60 {
+            return Err(anyhow::anyhow!("Invalid time '{}'", s));
+        }
+        // hours come from user files, and could be too large for a time
+        i32::try_from(hours)
+            .ok()
+            .and_then(|hours| hours.checked_mul(3600))
+            .and_then(|seconds_of_hours| seconds_of_hours.checked_add(minutes as i32 * 60))
+            .and_then(|total| total.checked_add(seconds as i32))
+            .map(Time)
+            .ok_or_else(|| anyhow::anyhow!("Invalid time '{}'", s))
+    }
+}
+
+/// Calendar date representation in days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct Date(i32);
+
+impl Date {
+    /// Create a new Date instance from a year, a month (1-12) and a day (1-31).
+    pub fn from_ymd(year: i32, month: u8, day: u8) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+        let (year, month, day) = (year - i32::from(month <= 2), month as i32, day as i32);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Some(Date(era * 146097 + day_of_era - 719468))
+    }
+    /// return the year, month and day of the date
+    pub fn ymd(&self) -> (i32, u8, u8) {
+        // civil from days
+        let days = self.0 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = year_of_era + era * 400 + i32::from(month <= 2);
+        (year, month, day)
+    }
+    #[inline]
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 is a Thursday
+        Weekday::from_index((self.0 + 3).rem_euclid(7) as u8)
+    }
+    #[inline]
+    pub fn add_days(self, days: i32) -> Self {
+        Date(self.0 + days)
+    }
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl std::str::FromStr for Date {
+    type Err = anyhow::Error;
+    /// Parses `YYYY-MM-DD` or `YYYYMMDD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: String = s.chars().filter(|c| *c != '-').collect();
+        if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow::anyhow!("Invalid date '{}'", s));
+        }
+        let year = digits[0..4].parse()?;
+        let month = digits[4..6].parse()?;
+        let day = digits[6..8].parse()?;
+        Date::from_ymd(year, month, day).ok_or_else(|| anyhow::anyhow!("Invalid date '{}'", s))
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    /// Accepts strings such as `"2025-01-31"` or `"20250131"`, and integers such as `20250131`.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateVisitor;
+        impl serde::de::Visitor<'_> for DateVisitor {
+            type Value = Date;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a date in the YYYY-MM-DD or YYYYMMDD format")
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Date, E> {
+                v.parse().map_err(E::custom)
+            }
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Date, E> {
+                self.visit_str(&v.to_string())
+            }
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Date, E> {
+                self.visit_str(&v.to_string())
+            }
+        }
+        deserializer.deserialize_any(DateVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Monday is 0, and Sunday is 6.
+    #[inline]
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+    pub fn from_index(index: u8) -> Self {
+        match index % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+    #[inline]
+    pub fn add_days(self, days: i32) -> Self {
+        Weekday::from_index((self.index() as i32 + days).rem_euclid(7) as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Add, Sub, Deserialize, Serialize)]
 pub struct IntervalLength(u32);
 
 impl IntervalLength {
@@ -145,7 +315,7 @@ impl IntervalLength {
 }
 
 #[derive(
-    Debug, Clone, Copy, PartialEq, PartialOrd, Add, Sub, Deserialize, Serialize, AddAssign,
+    Debug, Clone, Copy, Default, PartialEq, PartialOrd, Add, Sub, Deserialize, Serialize, AddAssign,
 )]
 pub struct GraphLength(f64);
 
@@ -200,5 +370,25 @@ pub enum ScaleMode {
     Squared,
 }
 
-#[derive(Debug, Serialize, Clone, Copy, Deserialize)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Deserialize)]
 pub struct Node(pub GraphLength, pub GraphLength);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time() {
+        assert_eq!(
+            "08:30".parse::<Time>().unwrap(),
+            Time::new(8 * 3600 + 30 * 60)
+        );
+        assert_eq!(
+            "25:00:05".parse::<Time>().unwrap(),
+            Time::new(25 * 3600 + 5)
+        );
+        assert!("08:60".parse::<Time>().is_err());
+        assert!("596524:00:00".parse::<Time>().is_err());
+        assert!("4294967295:00".parse::<Time>().is_err());
+    }
+}